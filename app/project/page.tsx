@@ -9,9 +9,11 @@ import { FolderViewer } from "@/components/projects/FolderViewer";
 import { ExportEvaluationsModal } from "@/components/projects/ExportEvaluationsModal";
 import { ExportResultModal } from "@/components/projects/ExportResultModal";
 import MoveToFolderModal from "@/components/projects/MoveToFolderModal";
+import EvaluationProviderModal from "@/components/projects/EvaluationProviderModal";
 import useProjectImages from "@/components/projects/useProjectImages";
 import useImageEvaluation from "@/components/projects/useImageEvaluation";
 import useProjectFolders from "@/components/projects/useProjectFolders";
+import useEvaluationProvider from "@/components/projects/useEvaluationProvider";
 import useOpenAIApiKey from "@/lib/hooks/useOpenAIApiKey";
 import useCustomPrompt from "@/lib/hooks/useCustomPrompt";
 import getTauriCommands from "@/lib/hooks/getTauriCommands";
@@ -31,19 +33,21 @@ export default function ProjectPage() {
   const {
     imagePreviews, selectedImage, imageEvaluations,
     isLoadingPreviews, isLoadingFullImage, isEvaluating,
-    selectImage, addImages, deleteImage, moveImagesToFolder,
-    refreshPreviews, refreshEvaluations,
+    selectImage, addImages, addFolder, deleteImage, moveImagesToFolder,
+    selectNearDuplicates, refreshPreviews, refreshEvaluations,
   } = useProjectImages();
   const {
     evaluateSelectedImage, evaluateNewImages, reevaluateAll,
     evaluateNewImagesInFolder, reevaluateAllInFolder,
   } = useImageEvaluation();
   const {
-    folders, focusedFolder, setFocusedFolder,
+    folders, folderTree, focusedFolder, setFocusedFolder,
     createFolder, renameFolder, deleteFolder, refreshFolders,
   } = useProjectFolders();
+  const { provider, saveProvider, needsApiKey } = useEvaluationProvider();
 
-  const evaluatedImageNames = useMemo(() => imageEvaluations.map((e) => e.imageName), [imageEvaluations]);
+  // Images edited since their evaluation count as unevaluated so they get picked up again
+  const evaluatedImageNames = useMemo(() => imageEvaluations.filter((e) => !e.stale).map((e) => e.imageName), [imageEvaluations]);
   const evaluatedWithSuffixImageNames = useMemo(() => imageEvaluations.filter((e) => e.result?.newSuggestedFilepathSuffix).map((e) => e.imageName), [imageEvaluations]);
   const selectedImageEvaluation = useMemo(() => selectedImage ? imageEvaluations.find((e) => e.imageName === selectedImage.imageName) : undefined, [selectedImage, imageEvaluations]);
   const hasImages = imagePreviews.length > 0;
@@ -62,6 +66,7 @@ export default function ProjectPage() {
   const [exportResultErrors, setExportResultErrors] = useState<string[]>([]);
   const [exportResultPath, setExportResultPath] = useState("");
   const [moveModalOpen, setMoveModalOpen] = useState(false);
+  const [providerModalOpen, setProviderModalOpen] = useState(false);
   const [scrollToImageName, setScrollToImageName] = useState<string | null>(null);
   const canMoveToFolder = selectedImageNames.length > 0 && folders.length > 0;
 
@@ -89,7 +94,9 @@ export default function ProjectPage() {
 
   if (!activeProjectName) return null;
 
-  const k = openAIApiKey;
+  // Providers other than OpenAI don't need a key, so they get an empty one
+  const k = needsApiKey ? openAIApiKey : (openAIApiKey ?? "");
+  const canEvaluate = k !== null;
 
   return (
     <div className="flex h-[calc(100vh-2.5rem)] flex-col bg-background overflow-hidden">
@@ -97,13 +104,15 @@ export default function ProjectPage() {
         projectName={activeProjectName}
         onGoHome={() => { reset(); router.push("/"); }}
         onAddImages={addImages}
-        onEvaluateThisImage={() => k && evaluateSelectedImage(k)}
-        onEvaluateNewImages={() => k && evaluateNewImages(k)}
-        onReevaluateAll={() => k && reevaluateAll(k)}
-        onEvaluateNewInFolder={() => k && focusedFolder && evaluateNewImagesInFolder(k, focusedFolder)}
-        onReevaluateAllInFolder={() => k && focusedFolder && reevaluateAllInFolder(k, focusedFolder)}
+        onAddFolder={addFolder}
+        onEvaluateThisImage={() => canEvaluate && evaluateSelectedImage(k)}
+        onEvaluateNewImages={() => canEvaluate && evaluateNewImages(k)}
+        onReevaluateAll={() => canEvaluate && reevaluateAll(k)}
+        onEvaluateNewInFolder={() => canEvaluate && focusedFolder && evaluateNewImagesInFolder(k, focusedFolder)}
+        onReevaluateAllInFolder={() => canEvaluate && focusedFolder && reevaluateAllInFolder(k, focusedFolder)}
         onMoveToFolder={() => setMoveModalOpen(true)}
         canMoveToFolder={canMoveToFolder}
+        onOpenProviderSettings={() => setProviderModalOpen(true)}
         onExportAll={() => {
           setExportMode("all");
           setExportModalOpen(true);
@@ -113,10 +122,10 @@ export default function ProjectPage() {
           setExportModalOpen(true);
         }}
         isEvaluating={isEvaluating}
-        canEvaluateThisImage={!!selectedImage && !!k}
+        canEvaluateThisImage={!!selectedImage && canEvaluate}
         hasUnevaluatedImages={hasUnevaluatedImages}
         hasImages={hasImages}
-        hasApiKey={!!k}
+        hasApiKey={canEvaluate}
         hasEvaluatedImages={hasEvaluatedImages}
         focusedFolder={focusedFolder}
         hasFolderUnevaluatedImages={hasFolderUnevaluatedImages}
@@ -131,17 +140,19 @@ export default function ProjectPage() {
         onExportComplete={handleExportComplete}
       />
       <ExportResultModal open={exportResultOpen} onOpenChange={setExportResultOpen} errors={exportResultErrors} outputPath={exportResultPath} />
+      <EvaluationProviderModal open={providerModalOpen} onOpenChange={setProviderModalOpen} provider={provider} onSave={saveProvider} />
       <MoveToFolderModal open={moveModalOpen} onOpenChange={setMoveModalOpen} folders={folders} imageCount={selectedImageNames.length} onConfirm={handleMoveConfirm} />
       <div className="flex flex-1 overflow-hidden">
         <ImageSidebar
           imagePreviews={imagePreviews} selectedImage={selectedImage}
           evaluatedImageNames={evaluatedImageNames} evaluatedWithSuffixImageNames={evaluatedWithSuffixImageNames} evaluatingImageNames={evaluatingImageNames}
-          isLoading={isLoadingPreviews} folders={folders} focusedFolder={focusedFolder}
+          isLoading={isLoadingPreviews} folders={folderTree} focusedFolder={focusedFolder}
           onSelectImage={selectImage} onOpenExternal={handleOpenExternal} onDeleteImage={deleteImage}
           scrollToImageName={scrollToImageName} onScrollHandled={() => setScrollToImageName(null)}
           onFocusFolder={setFocusedFolder} onCreateFolder={createFolder}
           onDeleteFolder={handleDeleteFolder}
           onRenameFolder={handleRenameFolder}
+          onSelectNearDuplicates={selectNearDuplicates}
         />
         {focusedFolder ? <FolderViewer folderName={focusedFolder} evaluations={focusedFolderEvaluations} evaluatedCount={focusedFolderEvaluated.length} undeterminedCount={focusedFolderUndeterminedCount} onSelectImage={handleFolderItemSelect} /> : <ImageViewer selectedImage={selectedImage} evaluation={selectedImageEvaluation} isLoading={isLoadingFullImage} />}
       </div>
@@ -12,16 +12,20 @@ import {
 import { Button } from "@/components/ui/button";
 import { Input } from "@/components/ui/input";
 import { Label } from "@/components/ui/label";
+import { getErrorMessage } from "@/lib/utils";
 
 interface CreateFolderDialogProps {
   open: boolean;
   onOpenChange: (open: boolean) => void;
+  /** Folder the new folder is created in, the project root if null */
+  parentFolder?: string | null;
   onCreateFolder: (folderName: string) => Promise<void>;
 }
 
 export default function CreateFolderDialog({
   open,
   onOpenChange,
+  parentFolder,
   onCreateFolder,
 }: CreateFolderDialogProps) {
   const [folderName, setFolderName] = useState("");
@@ -45,9 +49,7 @@ export default function CreateFolderDialog({
       setFolderName("");
       onOpenChange(false);
     } catch (err) {
-      setError(
-        err instanceof Error ? err.message : "Failed to create folder"
-      );
+      setError(getErrorMessage(err));
     } finally {
       setIsCreating(false);
     }
@@ -70,7 +72,9 @@ export default function CreateFolderDialog({
           <DialogHeader>
             <DialogTitle>New Folder</DialogTitle>
             <DialogDescription>
-              Enter a name for the new folder.
+              {parentFolder
+                ? `Enter a name for the new folder in ${parentFolder}.`
+                : "Enter a name for the new folder."}
             </DialogDescription>
           </DialogHeader>
           <div className="py-4">
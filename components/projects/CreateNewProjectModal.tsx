@@ -12,6 +12,7 @@ import {
 import { Button } from "@/components/ui/button";
 import { Input } from "@/components/ui/input";
 import { Label } from "@/components/ui/label";
+import { getErrorMessage } from "@/lib/utils";
 
 interface CreateNewProjectModalProps {
   open: boolean;
@@ -45,7 +46,7 @@ export default function CreateNewProjectModal({
       setProjectName("");
       onOpenChange(false);
     } catch (err) {
-      setError(err instanceof Error ? err.message : "Failed to create project");
+      setError(getErrorMessage(err));
     } finally {
       setIsCreating(false);
     }
@@ -1,7 +1,7 @@
 "use client";
 
 import { Card } from "@/components/ui/card";
-import { AlertCircle, FileText, Sparkles } from "lucide-react";
+import { AlertCircle, FileText, QrCode, Sparkles } from "lucide-react";
 import type { ImageEvaluation } from "@/lib/hooks/models";
 
 interface EvaluationCardProps {
@@ -28,6 +28,12 @@ export function EvaluationCard({ evaluation }: EvaluationCardProps) {
             <p className="text-sm text-muted-foreground mt-1">
               {evaluation.failReason}
             </p>
+            {!!evaluation.retries && (
+              <p className="text-xs text-muted-foreground mt-1">
+                Gave up after {evaluation.retries}{" "}
+                {evaluation.retries === 1 ? "retry" : "retries"}.
+              </p>
+            )}
           </div>
         </div>
       </Card>
@@ -49,6 +55,18 @@ export function EvaluationCard({ evaluation }: EvaluationCardProps) {
               {result.briefDescription}
             </p>
           </div>
+          {!!evaluation.retries && (
+            <p className="text-xs text-muted-foreground">
+              Succeeded after {evaluation.retries}{" "}
+              {evaluation.retries === 1 ? "retry" : "retries"}.
+            </p>
+          )}
+          {evaluation.stale && (
+            <p className="text-xs text-amber-600 dark:text-amber-500">
+              The image changed since it was evaluated. Re-evaluate it to update
+              this result.
+            </p>
+          )}
           {result.newSuggestedFilepathSuffix && (
             <div>
               <p className="text-sm font-medium flex items-center gap-1.5">
@@ -60,6 +78,27 @@ export function EvaluationCard({ evaluation }: EvaluationCardProps) {
               </p>
             </div>
           )}
+          {!!evaluation.barcodes?.length && (
+            <div>
+              <p className="text-sm font-medium flex items-center gap-1.5">
+                <QrCode className="w-4 h-4" />
+                Barcodes
+              </p>
+              <ul className="mt-1 space-y-0.5">
+                {evaluation.barcodes.map((barcode, i) => (
+                  <li
+                    key={i}
+                    className="text-sm text-muted-foreground font-mono break-all"
+                  >
+                    {barcode.payload}
+                    <span className="ml-2 text-xs font-sans">
+                      ({barcode.format})
+                    </span>
+                  </li>
+                ))}
+              </ul>
+            </div>
+          )}
         </div>
       </div>
     </Card>
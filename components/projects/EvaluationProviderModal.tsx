@@ -0,0 +1,187 @@
+"use client";
+
+import { useEffect, useState } from "react";
+import {
+  Dialog,
+  DialogContent,
+  DialogDescription,
+  DialogFooter,
+  DialogHeader,
+  DialogTitle,
+} from "@/components/ui/dialog";
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+import { Label } from "@/components/ui/label";
+import getTauriCommands from "@/lib/hooks/getTauriCommands";
+import { getErrorMessage } from "@/lib/utils";
+import type { EvaluationProviderConfig } from "@/lib/hooks/models";
+
+type ProviderKind = EvaluationProviderConfig["kind"];
+
+const PROVIDER_OPTIONS: { kind: ProviderKind; label: string }[] = [
+  { kind: "openAi", label: "OpenAI" },
+  { kind: "openAiCompatible", label: "OpenAI compatible server" },
+  { kind: "offlineOcr", label: "Offline OCR" },
+  { kind: "mock", label: "Mock (no model)" },
+];
+
+interface EvaluationProviderModalProps {
+  open: boolean;
+  onOpenChange: (open: boolean) => void;
+  provider: EvaluationProviderConfig;
+  onSave: (config: EvaluationProviderConfig) => Promise<void>;
+}
+
+export default function EvaluationProviderModal({
+  open,
+  onOpenChange,
+  provider,
+  onSave,
+}: EvaluationProviderModalProps) {
+  const [kind, setKind] = useState<ProviderKind>(provider.kind);
+  const [baseUrl, setBaseUrl] = useState("");
+  const [model, setModel] = useState("");
+  const [apiKey, setApiKey] = useState("");
+  const [isSaving, setIsSaving] = useState(false);
+  const [error, setError] = useState<string | null>(null);
+  const [ocrModelsDir, setOcrModelsDir] = useState<string | null>(null);
+
+  useEffect(() => {
+    if (!open || kind !== "offlineOcr" || ocrModelsDir) return;
+    getTauriCommands()
+      .getOcrModelsDir()
+      .then(setOcrModelsDir)
+      .catch(console.error);
+  }, [open, kind, ocrModelsDir]);
+
+  useEffect(() => {
+    if (!open) return;
+    setKind(provider.kind);
+    setBaseUrl(provider.kind === "openAiCompatible" ? provider.baseUrl : "");
+    setModel(provider.kind === "openAiCompatible" ? provider.model : "");
+    setApiKey(
+      provider.kind === "openAiCompatible" ? provider.apiKey ?? "" : ""
+    );
+    setError(null);
+  }, [open, provider]);
+
+  const canSave =
+    kind !== "openAiCompatible" || (!!baseUrl.trim() && !!model.trim());
+
+  const handleSave = async () => {
+    const config: EvaluationProviderConfig =
+      kind === "openAiCompatible"
+        ? {
+            kind,
+            baseUrl: baseUrl.trim(),
+            model: model.trim(),
+            apiKey: apiKey.trim() || null,
+          }
+        : { kind };
+    setIsSaving(true);
+    setError(null);
+    try {
+      await onSave(config);
+      onOpenChange(false);
+    } catch (err) {
+      setError(getErrorMessage(err));
+    } finally {
+      setIsSaving(false);
+    }
+  };
+
+  return (
+    <Dialog open={open} onOpenChange={onOpenChange}>
+      <DialogContent className="max-w-lg">
+        <DialogHeader>
+          <DialogTitle>Evaluation Provider</DialogTitle>
+          <DialogDescription>
+            Choose which model evaluates the images in this project.
+          </DialogDescription>
+        </DialogHeader>
+        <div className="space-y-3 py-2">
+          <div className="flex flex-wrap gap-2">
+            {PROVIDER_OPTIONS.map((option) => (
+              <Button
+                key={option.kind}
+                type="button"
+                size="sm"
+                variant={kind === option.kind ? "default" : "outline"}
+                onClick={() => setKind(option.kind)}
+              >
+                {option.label}
+              </Button>
+            ))}
+          </div>
+          {kind === "openAi" && (
+            <p className="text-sm text-muted-foreground">
+              Uses the OpenAI API key from settings.
+            </p>
+          )}
+          {kind === "openAiCompatible" && (
+            <>
+              <Label htmlFor="provider-base-url">Base URL</Label>
+              <Input
+                id="provider-base-url"
+                value={baseUrl}
+                onChange={(e) => setBaseUrl(e.target.value)}
+                placeholder="http://localhost:11434/v1"
+              />
+              <Label htmlFor="provider-model">Model</Label>
+              <Input
+                id="provider-model"
+                value={model}
+                onChange={(e) => setModel(e.target.value)}
+                placeholder="llava"
+              />
+              <Label htmlFor="provider-api-key">API key (optional)</Label>
+              <Input
+                id="provider-api-key"
+                type="password"
+                value={apiKey}
+                onChange={(e) => setApiKey(e.target.value)}
+              />
+              <p className="text-xs text-muted-foreground">
+                The model must accept images.
+              </p>
+            </>
+          )}
+          {kind === "offlineOcr" && (
+            <p className="text-sm text-muted-foreground">
+              Reads unit tags, serial and model numbers with OCR on this
+              computer. No images leave it. Needs text-detection.rten and
+              text-recognition.rten from the ocrs project in{" "}
+              <span className="font-mono break-all">
+                {ocrModelsDir ?? "the OCR models folder"}
+              </span>
+              .
+            </p>
+          )}
+          {kind === "mock" && (
+            <p className="text-sm text-muted-foreground">
+              Makes up results from the file names without calling any model.
+            </p>
+          )}
+          {error && <p className="text-sm text-destructive">{error}</p>}
+        </div>
+        <DialogFooter>
+          <Button
+            type="button"
+            variant="outline"
+            onClick={() => onOpenChange(false)}
+            disabled={isSaving}
+          >
+            Cancel
+          </Button>
+          <Button
+            type="button"
+            onClick={handleSave}
+            disabled={isSaving || !canSave}
+          >
+            {isSaving ? "Saving..." : "Save"}
+          </Button>
+        </DialogFooter>
+      </DialogContent>
+    </Dialog>
+  );
+}
@@ -14,6 +14,7 @@ import { Button } from "@/components/ui/button";
 import { Label } from "@/components/ui/label";
 import { FolderOpen } from "lucide-react";
 import getTauriCommands from "@/lib/hooks/getTauriCommands";
+import { getErrorMessage } from "@/lib/utils";
 import useTauriStore from "@/lib/hooks/useTauriStore";
 import type { ExportMode, ImageEvaluation } from "@/lib/hooks/models";
 
@@ -73,7 +74,7 @@ export function ExportEvaluationsModal({
       onExportComplete(errors, selectedPath);
     } catch (err) {
       onOpenChange(false);
-      onExportComplete([String(err)], selectedPath);
+      onExportComplete([getErrorMessage(err)], selectedPath);
     } finally {
       setIsExporting(false);
     }
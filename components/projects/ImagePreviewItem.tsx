@@ -2,7 +2,8 @@
 
 import { useEffect, useRef } from "react";
 import { Trash2, CheckCircle2, AlertCircle, Loader2 } from "lucide-react";
-import { cn } from "@/lib/utils";
+import { cn, getThumbnailUrl } from "@/lib/utils";
+import { useProjectStore } from "@/lib/stores/projectStore";
 import type { ImagePreviewModel } from "@/lib/hooks/models";
 
 /** Extract just the filename from a possibly folder-prefixed image name */
@@ -39,6 +40,7 @@ export function ImagePreviewItem({
   onDelete,
 }: ImagePreviewItemProps) {
   const rootRef = useRef<HTMLDivElement>(null);
+  const activeProjectName = useProjectStore((s) => s.activeProjectName);
 
   useEffect(() => {
     if (!shouldScrollIntoView || !rootRef.current) return;
@@ -62,7 +64,12 @@ export function ImagePreviewItem({
       >
         <div className="relative shrink-0">
           <img
-            src={`data:image/jpeg;base64,${preview.base64Preview}`}
+            src={getThumbnailUrl(
+              activeProjectName ?? "",
+              preview.imageName,
+              preview.contentHash
+            )}
+            loading="lazy"
             alt={preview.imageName}
             className="w-12 h-12 object-cover rounded pointer-events-none"
           />
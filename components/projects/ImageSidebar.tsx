@@ -1,14 +1,20 @@
 "use client";
 
 import { useMemo, useState } from "react";
-import { Loader2, ImageIcon, FolderPlus } from "lucide-react";
+import { Loader2, ImageIcon, FolderPlus, Layers } from "lucide-react";
 import { Button } from "@/components/ui/button";
 import { Skeleton } from "@/components/ui/skeleton";
 import SidebarImageList from "./SidebarImageList";
 import SidebarFolderItem from "./SidebarFolderItem";
 import CreateFolderDialog from "./CreateFolderDialog";
 import { useProjectStore } from "@/lib/stores/projectStore";
-import type { ImagePreviewModel, FullImageModel } from "@/lib/hooks/models";
+import type { ImagePreviewModel, FullImageModel, FolderNode } from "@/lib/hooks/models";
+
+/** The folder an image is directly in, "" for the project root */
+function parentFolderOf(imageName: string) {
+  const slash = imageName.lastIndexOf("/");
+  return slash < 0 ? "" : imageName.slice(0, slash);
+}
 
 interface ImageSidebarProps {
   imagePreviews: ImagePreviewModel[];
@@ -17,7 +23,7 @@ interface ImageSidebarProps {
   evaluatedWithSuffixImageNames: string[];
   evaluatingImageNames: string[];
   isLoading: boolean;
-  folders: string[];
+  folders: FolderNode[];
   focusedFolder: string | null;
   onSelectImage: (imageName: string) => void;
   onOpenExternal: (imageName: string) => void;
@@ -28,6 +34,7 @@ interface ImageSidebarProps {
   onCreateFolder: (folderName: string) => Promise<void>;
   onDeleteFolder: (folderName: string) => void;
   onRenameFolder: (oldName: string, newName: string) => void;
+  onSelectNearDuplicates: () => void;
 }
 
 export function ImageSidebar({
@@ -36,7 +43,7 @@ export function ImageSidebar({
   isLoading, folders, focusedFolder,
   onSelectImage, onOpenExternal, onDeleteImage, onFocusFolder,
   scrollToImageName, onScrollHandled,
-  onCreateFolder, onDeleteFolder, onRenameFolder,
+  onCreateFolder, onDeleteFolder, onRenameFolder, onSelectNearDuplicates,
 }: ImageSidebarProps) {
   const [expandedFolders, setExpandedFolders] = useState<Set<string>>(new Set());
   const [showCreateFolder, setShowCreateFolder] = useState(false);
@@ -47,17 +54,21 @@ export function ImageSidebar({
   const setLastClickedImageName = useProjectStore((s) => s.setLastClickedImageName);
 
   const rootImages = imagePreviews.filter((p) => !p.imageName.includes("/"));
+  // Images directly in a folder, not in its subfolders
   const folderImages = (folder: string) =>
-    imagePreviews.filter((p) => p.imageName.startsWith(`${folder}/`));
+    imagePreviews.filter((p) => parentFolderOf(p.imageName) === folder);
+  // Images in a folder and all its subfolders
+  const nestedImageCount = (folder: string) =>
+    imagePreviews.filter((p) => p.imageName.startsWith(`${folder}/`)).length;
 
-  // Flat ordered list of all image names for shift-click range selection
+  // Flat ordered list of all image names for shift-click range selection, in display order
   const flatImageNames = useMemo(() => {
     const names: string[] = rootImages.map((p) => p.imageName);
-    for (const folder of folders) {
-      for (const p of folderImages(folder)) {
-        names.push(p.imageName);
-      }
-    }
+    const addFolder = (node: FolderNode) => {
+      for (const child of node.children) addFolder(child);
+      for (const p of folderImages(node.path)) names.push(p.imageName);
+    };
+    folders.forEach(addFolder);
     return names;
   }, [imagePreviews, folders]); // eslint-disable-line react-hooks/exhaustive-deps
 
@@ -108,14 +119,38 @@ export function ImageSidebar({
 
   const totalCount = imagePreviews.length;
 
+  const createFolder = (folderName: string) =>
+    onCreateFolder(focusedFolder ? `${focusedFolder}/${folderName}` : folderName);
+
+  const renderFolder = (node: FolderNode): React.ReactNode => {
+    const parentPrefix = node.path.slice(0, node.path.length - node.name.length);
+    return (
+      <SidebarFolderItem
+        key={node.path} folderName={node.name} imageCount={nestedImageCount(node.path)}
+        isFocused={focusedFolder === node.path} isExpanded={expandedFolders.has(node.path)}
+        onToggleExpand={() => toggleFolder(node.path)} onFocus={() => { setSelectedImageNames([]); onFocusFolder(node.path); }}
+        onDelete={() => onDeleteFolder(node.path)}
+        onRename={(newName) => onRenameFolder(node.path, parentPrefix + newName)}
+      >
+        {node.children.map(renderFolder)}
+        <SidebarImageList images={folderImages(node.path)} {...sharedListProps} />
+      </SidebarFolderItem>
+    );
+  };
+
   return (
     <>
       <div className="w-64 border-r flex flex-col overflow-hidden">
         <div className="p-3 border-b flex items-center justify-between">
           <h2 className="text-sm font-medium text-muted-foreground">Images ({totalCount})</h2>
-          <Button variant="ghost" size="icon" className="h-7 w-7" onClick={() => setShowCreateFolder(true)} title="New folder">
-            <FolderPlus className="w-4 h-4" />
-          </Button>
+          <div className="flex items-center">
+            <Button variant="ghost" size="icon" className="h-7 w-7" onClick={onSelectNearDuplicates} disabled={totalCount < 2} title="Select near-duplicates">
+              <Layers className="w-4 h-4" />
+            </Button>
+            <Button variant="ghost" size="icon" className="h-7 w-7" onClick={() => setShowCreateFolder(true)} title={focusedFolder ? `New folder in ${focusedFolder}` : "New folder"}>
+              <FolderPlus className="w-4 h-4" />
+            </Button>
+          </div>
         </div>
         <div
           className="flex-1 overflow-y-auto p-2"
@@ -143,25 +178,12 @@ export function ImageSidebar({
                     </div>
                   </div>
                 ))}
-              {folders.map((folder) => {
-                const images = folderImages(folder);
-                return (
-                  <SidebarFolderItem
-                    key={folder} folderName={folder} imageCount={images.length}
-                    isFocused={focusedFolder === folder} isExpanded={expandedFolders.has(folder)}
-                    onToggleExpand={() => toggleFolder(folder)} onFocus={() => { setSelectedImageNames([]); onFocusFolder(folder); }}
-                    onDelete={() => onDeleteFolder(folder)}
-                    onRename={(newName) => onRenameFolder(folder, newName)}
-                  >
-                    <SidebarImageList images={images} {...sharedListProps} />
-                  </SidebarFolderItem>
-                );
-              })}
+              {folders.map(renderFolder)}
             </div>
           )}
         </div>
       </div>
-      <CreateFolderDialog open={showCreateFolder} onOpenChange={setShowCreateFolder} onCreateFolder={onCreateFolder} />
+      <CreateFolderDialog open={showCreateFolder} onOpenChange={setShowCreateFolder} parentFolder={focusedFolder} onCreateFolder={createFolder} />
     </>
   );
 }
@@ -1,9 +1,17 @@
 "use client";
 
+import { useEffect, useState } from "react";
 import { Loader2, ImageIcon } from "lucide-react";
 import { Card } from "@/components/ui/card";
+import getTauriCommands from "@/lib/hooks/getTauriCommands";
+import { getImageUrl } from "@/lib/utils";
+import { useProjectStore } from "@/lib/stores/projectStore";
 import { EvaluationCard } from "./EvaluationCard";
-import type { FullImageModel, ImageEvaluation } from "@/lib/hooks/models";
+import type {
+  FullImageModel,
+  ImageEvaluation,
+  ImageMetadataModel,
+} from "@/lib/hooks/models";
 
 interface ImageViewerProps {
   selectedImage: FullImageModel | null;
@@ -16,6 +24,26 @@ export function ImageViewer({
   evaluation,
   isLoading,
 }: ImageViewerProps) {
+  const activeProjectName = useProjectStore((s) => s.activeProjectName);
+  const [metadata, setMetadata] = useState<ImageMetadataModel | null>(null);
+
+  useEffect(() => {
+    setMetadata(null);
+    if (!activeProjectName || !selectedImage) return;
+    let cancelled = false;
+    getTauriCommands()
+      .getImageMetadata(activeProjectName, selectedImage.imageName)
+      .then((m) => {
+        if (!cancelled) setMetadata(m);
+      })
+      .catch((error) =>
+        console.error("Failed to load image metadata:", error)
+      );
+    return () => {
+      cancelled = true;
+    };
+  }, [activeProjectName, selectedImage]);
+
   if (isLoading) {
     return (
       <div className="flex-1 flex items-center justify-center p-4 overflow-hidden">
@@ -33,7 +61,11 @@ export function ImageViewer({
         <div className="flex flex-1 flex-col items-center gap-4 min-h-0">
           <div className="flex-1 min-h-0 flex items-center justify-center w-full">
             <img
-              src={`data:image/jpeg;base64,${selectedImage.base64Image}`}
+              src={getImageUrl(
+                activeProjectName ?? "",
+                selectedImage.imageName,
+                String(selectedImage.imageSizeBytes)
+              )}
               alt={selectedImage.imageName}
               className="max-h-full max-w-full object-contain rounded-lg shadow-lg"
             />
@@ -44,6 +76,7 @@ export function ImageViewer({
               {selectedImage.width} x {selectedImage.height} &bull;{" "}
               {formatBytes(selectedImage.imageSizeBytes)}
             </p>
+            {metadata && <MetadataLine metadata={metadata} />}
           </div>
           {evaluation && (
             <div className="shrink-0 w-full max-w-md">
@@ -69,6 +102,28 @@ export function ImageViewer({
   );
 }
 
+/** Capture time, camera and position, skipping whatever the image doesn't have */
+function MetadataLine({ metadata }: { metadata: ImageMetadataModel }) {
+  const camera = [metadata.cameraMake, metadata.cameraModel]
+    .filter(Boolean)
+    .join(" ");
+  const parts = [
+    metadata.capturedAt &&
+      new Date(metadata.capturedAt).toLocaleString(undefined, {
+        dateStyle: "medium",
+        timeStyle: "short",
+      }),
+    camera,
+    metadata.gps &&
+      `${metadata.gps.latitude.toFixed(5)}, ${metadata.gps.longitude.toFixed(5)}`,
+    metadata.originalFileName && `Originally ${metadata.originalFileName}`,
+  ].filter(Boolean);
+  if (parts.length === 0) return null;
+  return (
+    <p className="text-xs text-muted-foreground">{parts.join(" \u2022 ")}</p>
+  );
+}
+
 function formatBytes(bytes: number): string {
   if (bytes === 0) return "0 B";
   const k = 1024;
@@ -2,7 +2,7 @@
 
 import { Button } from "@/components/ui/button";
 import { Separator } from "@/components/ui/separator";
-import { Home, ImagePlus, FolderInput } from "lucide-react";
+import { Home, ImagePlus, FolderInput, FolderUp, Cpu } from "lucide-react";
 import EvaluateDropdown from "./EvaluateDropdown";
 import ExportDropdown from "./ExportDropdown";
 
@@ -10,6 +10,7 @@ interface ProjectHeaderProps {
   projectName: string;
   onGoHome: () => void;
   onAddImages: () => void;
+  onAddFolder: () => void;
   onEvaluateThisImage: () => void;
   onEvaluateNewImages: () => void;
   onReevaluateAll: () => void;
@@ -17,6 +18,7 @@ interface ProjectHeaderProps {
   onReevaluateAllInFolder: () => void;
   onMoveToFolder: () => void;
   canMoveToFolder: boolean;
+  onOpenProviderSettings: () => void;
   onExportAll: () => void;
   onExportFolders: () => void;
   isEvaluating: boolean;
@@ -34,6 +36,7 @@ export function ProjectHeader({
   projectName,
   onGoHome,
   onAddImages,
+  onAddFolder,
   onEvaluateThisImage,
   onEvaluateNewImages,
   onReevaluateAll,
@@ -41,6 +44,7 @@ export function ProjectHeader({
   onReevaluateAllInFolder,
   onMoveToFolder,
   canMoveToFolder,
+  onOpenProviderSettings,
   onExportAll,
   onExportFolders,
   isEvaluating,
@@ -80,6 +84,14 @@ export function ProjectHeader({
           <FolderInput className="w-4 h-4 shrink-0 sm:mr-2" />
           <span className="hidden sm:inline">Move</span>
         </Button>
+        <Button
+          size="sm"
+          variant="ghost"
+          onClick={onOpenProviderSettings}
+          title="Evaluation Provider"
+        >
+          <Cpu className="w-4 h-4 shrink-0" />
+        </Button>
         <EvaluateDropdown
           onEvaluateThisImage={onEvaluateThisImage}
           onEvaluateNewImages={onEvaluateNewImages}
@@ -95,6 +107,15 @@ export function ProjectHeader({
           hasFolderUnevaluatedImages={hasFolderUnevaluatedImages}
           hasFolderImages={hasFolderImages}
         />
+        <Button
+          size="sm"
+          variant="outline"
+          onClick={onAddFolder}
+          title="Import a folder and its subfolders"
+        >
+          <FolderUp className="w-4 h-4 shrink-0 sm:mr-2" />
+          <span className="hidden sm:inline">Add Folder</span>
+        </Button>
         <Button size="sm" onClick={onAddImages} title="Add Images">
           <ImagePlus className="w-4 h-4 shrink-0 sm:mr-2" />
           <span className="hidden sm:inline">Add Images</span>
@@ -13,6 +13,9 @@ import { Button } from "@/components/ui/button";
 import { Textarea } from "@/components/ui/textarea";
 import { Slider } from "@/components/ui/slider";
 import { Label } from "@/components/ui/label";
+import { Input } from "@/components/ui/input";
+import getTauriCommands from "@/lib/hooks/getTauriCommands";
+import type { RetryPolicy } from "@/lib/hooks/models";
 import { useSettingsStore } from "@/lib/stores/settingsStore";
 import useCustomPrompt from "@/lib/hooks/useCustomPrompt";
 
@@ -35,27 +38,56 @@ export default function PromptSettingsModal({
 }: PromptSettingsModalProps) {
   const customPrompt = useSettingsStore((s) => s.customPrompt);
   const customTemperature = useSettingsStore((s) => s.customTemperature);
-  const { saveCustomPrompt, saveCustomTemperature } = useCustomPrompt();
+  const preferBarcodeSerial = useSettingsStore((s) => s.preferBarcodeSerial);
+  const { saveCustomPrompt, saveCustomTemperature, savePreferBarcodeSerial } =
+    useCustomPrompt();
   const [draft, setDraft] = useState("");
   const [tempDraft, setTempDraft] = useState<number | null>(null);
+  const [barcodeDraft, setBarcodeDraft] = useState(false);
+  const [retryDraft, setRetryDraft] = useState<RetryPolicy | null>(null);
 
   useEffect(() => {
     if (isOpen) {
       setDraft(customPrompt ?? "");
       setTempDraft(customTemperature);
+      setBarcodeDraft(preferBarcodeSerial);
     }
-  }, [isOpen, customPrompt, customTemperature]);
+  }, [isOpen, customPrompt, customTemperature, preferBarcodeSerial]);
+
+  useEffect(() => {
+    if (!isOpen) return;
+    getTauriCommands()
+      .getEvaluationRetryPolicy()
+      .then(setRetryDraft)
+      .catch(console.error);
+  }, [isOpen]);
+
+  const setRetryField = (field: keyof RetryPolicy, value: string) => {
+    const parsed = Number.parseInt(value, 10);
+    if (!retryDraft || Number.isNaN(parsed) || parsed < 0) return;
+    setRetryDraft({ ...retryDraft, [field]: parsed });
+  };
 
   const handleSave = async () => {
     const trimmed = draft.trim();
     await saveCustomPrompt(trimmed || null);
     await saveCustomTemperature(tempDraft);
+    await savePreferBarcodeSerial(barcodeDraft);
+    if (retryDraft) {
+      await getTauriCommands()
+        .setEvaluationRetryPolicy({
+          ...retryDraft,
+          maxConcurrentRequests: Math.max(1, retryDraft.maxConcurrentRequests),
+        })
+        .catch(console.error);
+    }
     onOpenChange(false);
   };
 
   const handleReset = () => {
     setDraft("");
     setTempDraft(null);
+    setBarcodeDraft(false);
   };
 
   return (
@@ -116,13 +148,61 @@ export default function PromptSettingsModal({
               )}
             </p>
           </div>
+          <div className="flex items-start gap-2 pt-2">
+            <input
+              id="prefer-barcode-serial"
+              type="checkbox"
+              className="mt-1"
+              checked={barcodeDraft}
+              onChange={(e) => setBarcodeDraft(e.target.checked)}
+            />
+            <div>
+              <Label htmlFor="prefer-barcode-serial">
+                Prefer serial numbers from barcodes
+              </Label>
+              <p className="text-xs text-muted-foreground">
+                When a QR code or barcode on the nameplate encodes a serial
+                number, use it for the filename instead of the AI&apos;s pick.
+              </p>
+            </div>
+          </div>
+          {retryDraft && (
+            <div className="grid grid-cols-2 gap-3 pt-2">
+              <div className="space-y-1">
+                <Label htmlFor="max-retries">Retries per image</Label>
+                <Input
+                  id="max-retries"
+                  type="number"
+                  min={0}
+                  value={retryDraft.maxRetries}
+                  onChange={(e) => setRetryField("maxRetries", e.target.value)}
+                />
+              </div>
+              <div className="space-y-1">
+                <Label htmlFor="max-concurrent">Images at once</Label>
+                <Input
+                  id="max-concurrent"
+                  type="number"
+                  min={1}
+                  value={retryDraft.maxConcurrentRequests}
+                  onChange={(e) =>
+                    setRetryField("maxConcurrentRequests", e.target.value)
+                  }
+                />
+              </div>
+              <p className="col-span-2 text-xs text-muted-foreground">
+                Images that hit a rate limit or a server error are retried with
+                increasing waits.
+              </p>
+            </div>
+          )}
         </div>
         <DialogFooter className="gap-2">
           <Button
             type="button"
             variant="outline"
             onClick={handleReset}
-            disabled={!draft.trim() && tempDraft === null}
+            disabled={!draft.trim() && tempDraft === null && !barcodeDraft}
           >
             Reset to Default
           </Button>
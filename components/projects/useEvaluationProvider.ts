@@ -0,0 +1,39 @@
+import { useCallback, useEffect, useState } from "react";
+import getTauriCommands from "@/lib/hooks/getTauriCommands";
+import { useProjectStore } from "@/lib/stores/projectStore";
+import type { EvaluationProviderConfig } from "@/lib/hooks/models";
+
+const DEFAULT_PROVIDER: EvaluationProviderConfig = { kind: "openAi" };
+
+export default function useEvaluationProvider() {
+  const activeProjectName = useProjectStore((s) => s.activeProjectName);
+  const [provider, setProvider] =
+    useState<EvaluationProviderConfig>(DEFAULT_PROVIDER);
+
+  useEffect(() => {
+    if (!activeProjectName) return;
+    getTauriCommands()
+      .getEvaluationProvider(activeProjectName)
+      .then(setProvider)
+      .catch((error) => {
+        console.error("Failed to load evaluation provider:", error);
+        setProvider(DEFAULT_PROVIDER);
+      });
+  }, [activeProjectName]);
+
+  const saveProvider = useCallback(
+    async (config: EvaluationProviderConfig) => {
+      if (!activeProjectName) return;
+      await getTauriCommands().setEvaluationProvider(activeProjectName, config);
+      setProvider(config);
+    },
+    [activeProjectName]
+  );
+
+  return {
+    provider,
+    saveProvider,
+    /** Only OpenAI needs the key from settings */
+    needsApiKey: provider.kind === "openAi",
+  };
+}
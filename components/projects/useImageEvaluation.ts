@@ -1,13 +1,28 @@
 import { useCallback } from "react";
+import { listen } from "@tauri-apps/api/event";
 import { toast } from "sonner";
-import getTauriCommands from "@/lib/hooks/getTauriCommands";
+import getTauriCommands, {
+  EVALUATION_PROGRESS_EVENT,
+} from "@/lib/hooks/getTauriCommands";
+import type { EvaluationProgress, ImageEvaluation } from "@/lib/hooks/models";
+import { getErrorMessage, isAppError } from "@/lib/utils";
 import { useProjectStore } from "@/lib/stores/projectStore";
 import { useSettingsStore } from "@/lib/stores/settingsStore";
 
+/** Puts a freshly saved evaluation in place of the image's previous one */
+function upsertEvaluation(evaluation: ImageEvaluation) {
+  const { imageEvaluations, setImageEvaluations } = useProjectStore.getState();
+  const others = imageEvaluations.filter(
+    (e) => e.imageName !== evaluation.imageName
+  );
+  setImageEvaluations([...others, evaluation]);
+}
+
 export default function useImageEvaluation() {
   const activeProjectName = useProjectStore((s) => s.activeProjectName);
   const customPrompt = useSettingsStore((s) => s.customPrompt);
   const customTemperature = useSettingsStore((s) => s.customTemperature);
+  const preferBarcodeSerial = useSettingsStore((s) => s.preferBarcodeSerial);
   const imagePreviews = useProjectStore((s) => s.imagePreviews);
   const selectedImage = useProjectStore((s) => s.selectedImage);
   const imageEvaluations = useProjectStore((s) => s.imageEvaluations);
@@ -23,6 +38,38 @@ export default function useImageEvaluation() {
 
       setIsEvaluating(true);
       setEvaluatingImageNames(imageNames);
+      const evaluationId = crypto.randomUUID();
+      let cancelled = false;
+      let done = 0;
+      const action = {
+        label: "Cancel",
+        onClick: () => {
+          cancelled = true;
+          void getTauriCommands().cancelEvaluation(evaluationId);
+        },
+      };
+      const toastId = toast.loading("Evaluating...", { action });
+      // Results are shown as they arrive instead of all at once at the end
+      const unlisten = await listen<EvaluationProgress>(
+        EVALUATION_PROGRESS_EVENT,
+        ({ payload }) => {
+          if (payload.evaluationId !== evaluationId) return;
+          done = payload.done;
+          // Images deleted while being evaluated finish without an evaluation
+          if (payload.evaluation) upsertEvaluation(payload.evaluation);
+          if (payload.status !== "started") {
+            const { evaluatingImageNames } = useProjectStore.getState();
+            setEvaluatingImageNames(
+              evaluatingImageNames.filter((n) => n !== payload.imageName)
+            );
+          }
+          toast.loading(`Evaluated ${payload.done} of ${payload.total}`, {
+            id: toastId,
+            description: payload.imageName,
+            action,
+          });
+        }
+      );
       try {
         const { evaluateImages } = getTauriCommands();
         const evaluations = await evaluateImages(
@@ -30,23 +77,43 @@ export default function useImageEvaluation() {
           {
             openaiApiKey: openAIApiKey,
             imageNames,
+            preferBarcodeSerial,
           },
           customPrompt,
-          customTemperature
+          customTemperature,
+          evaluationId
         );
         setImageEvaluations(evaluations);
         const count = imageNames.length;
-        toast.success(
-          count === 1
-            ? "Image evaluated successfully"
-            : `${count} images evaluated successfully`
-        );
+        if (cancelled) {
+          toast.info("Evaluation cancelled", {
+            description: `Kept the ${done} of ${count} results finished so far`,
+          });
+        } else {
+          toast.success(
+            count === 1
+              ? "Image evaluated successfully"
+              : `${count} images evaluated successfully`
+          );
+        }
       } catch (error) {
         console.error("Failed to evaluate images:", error);
-        toast.error("Failed to evaluate images", {
-          description: String(error),
-        });
+        if (isAppError(error) && error.code === "Auth") {
+          toast.error("OpenAI rejected the API key", {
+            description: "Check the key in settings and try again.",
+          });
+        } else if (isAppError(error) && error.code === "RateLimited") {
+          toast.error("OpenAI rate limit reached", {
+            description: "Wait a moment before evaluating more images.",
+          });
+        } else {
+          toast.error("Failed to evaluate images", {
+            description: getErrorMessage(error),
+          });
+        }
       } finally {
+        unlisten();
+        toast.dismiss(toastId);
         setIsEvaluating(false);
         setEvaluatingImageNames([]);
       }
@@ -55,6 +122,7 @@ export default function useImageEvaluation() {
       activeProjectName,
       customPrompt,
       customTemperature,
+      preferBarcodeSerial,
       setImageEvaluations,
       setIsEvaluating,
       setEvaluatingImageNames,
@@ -72,7 +140,10 @@ export default function useImageEvaluation() {
   const evaluateNewImages = useCallback(
     async (openAIApiKey: string) => {
       if (!activeProjectName) return;
-      const evaluatedNames = imageEvaluations.map((e) => e.imageName);
+      // Stale evaluations belong to images edited since, so those count as new
+      const evaluatedNames = imageEvaluations
+        .filter((e) => !e.stale)
+        .map((e) => e.imageName);
       const toEval = imagePreviews
         .filter((p) => !evaluatedNames.includes(p.imageName))
         .map((p) => p.imageName);
@@ -104,7 +175,10 @@ export default function useImageEvaluation() {
       const folderPreviews = imagePreviews.filter((p) =>
         p.imageName.startsWith(`${folder}/`)
       );
-      const evaluatedNames = imageEvaluations.map((e) => e.imageName);
+      // Stale evaluations belong to images edited since, so those count as new
+      const evaluatedNames = imageEvaluations
+        .filter((e) => !e.stale)
+        .map((e) => e.imageName);
       const toEval = folderPreviews
         .filter((p) => !evaluatedNames.includes(p.imageName))
         .map((p) => p.imageName);
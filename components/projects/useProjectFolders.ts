@@ -1,21 +1,40 @@
-import { useCallback, useEffect, useState } from "react";
+import { useCallback, useEffect, useMemo, useState } from "react";
+import { listen } from "@tauri-apps/api/event";
 import { toast } from "sonner";
-import getTauriCommands from "@/lib/hooks/getTauriCommands";
+import getTauriCommands, {
+  PROJECT_FILES_CHANGED_EVENT,
+} from "@/lib/hooks/getTauriCommands";
+import { getErrorMessage } from "@/lib/utils";
 import { useProjectStore } from "@/lib/stores/projectStore";
+import type { FolderNode, ProjectFilesChanged } from "@/lib/hooks/models";
+
+/** Paths of every folder in the tree, parents before their subfolders */
+function flattenFolderTree(nodes: FolderNode[]): string[] {
+  return nodes.flatMap((node) => [
+    node.path,
+    ...flattenFolderTree(node.children),
+  ]);
+}
+
+/** Whether `folder` is `ancestor` itself or nested somewhere inside it */
+function isSameOrInside(folder: string, ancestor: string) {
+  return folder === ancestor || folder.startsWith(`${ancestor}/`);
+}
 
 export default function useProjectFolders() {
   const activeProjectName = useProjectStore((s) => s.activeProjectName);
   const focusedFolder = useProjectStore((s) => s.focusedFolder);
   const setFocusedFolder = useProjectStore((s) => s.setFocusedFolder);
 
-  const [folders, setFolders] = useState<string[]>([]);
+  const [folderTree, setFolderTree] = useState<FolderNode[]>([]);
+  const folders = useMemo(() => flattenFolderTree(folderTree), [folderTree]);
 
   const loadFolders = useCallback(async () => {
     if (!activeProjectName) return;
     try {
       const { getFoldersInProject } = getTauriCommands();
-      const names = await getFoldersInProject(activeProjectName);
-      setFolders(names);
+      const tree = await getFoldersInProject(activeProjectName);
+      setFolderTree(tree);
     } catch (error) {
       console.error("Failed to load folders:", error);
     }
@@ -25,6 +44,19 @@ export default function useProjectFolders() {
     loadFolders();
   }, [loadFolders]);
 
+  // Folders created or removed outside the app come with image changes
+  useEffect(() => {
+    const unlisten = listen<ProjectFilesChanged>(
+      PROJECT_FILES_CHANGED_EVENT,
+      ({ payload }) => {
+        if (payload.projectName === activeProjectName) loadFolders();
+      }
+    );
+    return () => {
+      unlisten.then((stop) => stop());
+    };
+  }, [activeProjectName, loadFolders]);
+
   const createFolder = useCallback(
     async (folderName: string) => {
       if (!activeProjectName) return;
@@ -41,15 +73,15 @@ export default function useProjectFolders() {
       try {
         const { renameFolderInProject } = getTauriCommands();
         await renameFolderInProject(activeProjectName, oldName, newName);
-        if (focusedFolder === oldName) {
-          setFocusedFolder(newName);
+        if (focusedFolder && isSameOrInside(focusedFolder, oldName)) {
+          setFocusedFolder(newName + focusedFolder.slice(oldName.length));
         }
         await loadFolders();
         await onRefresh();
       } catch (error) {
         console.error("Failed to rename folder:", error);
         toast.error("Failed to rename folder", {
-          description: String(error),
+          description: getErrorMessage(error),
         });
       }
     },
@@ -62,7 +94,7 @@ export default function useProjectFolders() {
       try {
         const { deleteFolderFromProject } = getTauriCommands();
         await deleteFolderFromProject(activeProjectName, folderName);
-        if (focusedFolder === folderName) {
+        if (focusedFolder && isSameOrInside(focusedFolder, folderName)) {
           setFocusedFolder(null);
         }
         await loadFolders();
@@ -70,7 +102,7 @@ export default function useProjectFolders() {
       } catch (error) {
         console.error("Failed to delete folder:", error);
         toast.error("Failed to delete folder", {
-          description: String(error),
+          description: getErrorMessage(error),
         });
       }
     },
@@ -79,6 +111,7 @@ export default function useProjectFolders() {
 
   return {
     folders,
+    folderTree,
     focusedFolder,
     setFocusedFolder,
     createFolder,
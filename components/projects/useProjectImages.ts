@@ -1,41 +1,181 @@
-import { useCallback, useEffect } from "react";
+import { useCallback, useEffect, useRef } from "react";
+import { Channel } from "@tauri-apps/api/core";
+import { listen } from "@tauri-apps/api/event";
 import { open } from "@tauri-apps/plugin-dialog";
 import { toast } from "sonner";
-import getTauriCommands from "@/lib/hooks/getTauriCommands";
+import getTauriCommands, {
+  IMPORT_PROGRESS_EVENT,
+  PROJECT_FILES_CHANGED_EVENT,
+} from "@/lib/hooks/getTauriCommands";
+import type {
+  ImagePreviewModel,
+  ImportProgress,
+  ImportReport,
+  ProjectFilesChanged,
+} from "@/lib/hooks/models";
+import { getErrorMessage } from "@/lib/utils";
 import { useProjectStore } from "@/lib/stores/projectStore";
 
+const IMPORT_EXTENSIONS = [
+  "png",
+  "jpg",
+  "jpeg",
+  "webp",
+  "gif",
+  "bmp",
+  "tif",
+  "tiff",
+  "heic",
+  "heif",
+  "dng",
+  "cr2",
+  "cr3",
+  "nef",
+  "arw",
+  "orf",
+  "rw2",
+  "raf",
+];
+
+/** Runs an import while a toast shows its progress and offers to cancel it */
+async function withImportProgress(
+  run: (importId: string) => Promise<ImportReport>
+): Promise<ImportReport> {
+  const importId = crypto.randomUUID();
+  const action = {
+    label: "Cancel",
+    onClick: () => void getTauriCommands().cancelImport(importId),
+  };
+  const toastId = toast.loading("Importing...", { action });
+  const unlisten = await listen<ImportProgress>(
+    IMPORT_PROGRESS_EVENT,
+    ({ payload }) => {
+      if (payload.importId !== importId) return;
+      const verb = payload.extracting ? "Extracting" : "Importing";
+      toast.loading(`${verb} ${payload.index + 1} of ${payload.total}`, {
+        id: toastId,
+        description: payload.fileName,
+        action,
+      });
+    }
+  );
+  try {
+    return await run(importId);
+  } finally {
+    unlisten();
+    toast.dismiss(toastId);
+  }
+}
+
+/** Only bothers the user when something other than a plain import happened */
+function showImportSummary(report: ImportReport, always = false) {
+  if (report.cancelled) {
+    toast.info("Import cancelled", {
+      description: "Nothing from this import was kept",
+    });
+    return;
+  }
+  const details = [
+    report.folders.length > 0 && `into ${report.folders.length} folders`,
+    report.renamed.length > 0 &&
+      `${report.renamed.length} renamed to avoid name conflicts`,
+    report.skipped.length > 0 && `${report.skipped.length} skipped`,
+    report.duplicates.length > 0 &&
+      `${report.duplicates.length} already in the project`,
+  ].filter(Boolean);
+  if (details.length === 0 && !always) return;
+  toast.info(
+    `Imported ${report.imported.length + report.renamed.length} images`,
+    { description: details.join(", ") }
+  );
+}
+
 export default function useProjectImages() {
   const store = useProjectStore();
   const {
     activeProjectName, imagePreviews, selectedImage, imageEvaluations,
     focusedFolder, isLoadingPreviews, isLoadingFullImage, isEvaluating,
     setImagePreviews, setSelectedImage, setImageEvaluations,
-    setIsLoadingPreviews, setIsLoadingFullImage,
+    setIsLoadingPreviews, setIsLoadingFullImage, setSelectedImageNames,
   } = store;
 
+  /** Id of the preview stream currently filling the grid, if any */
+  const previewStreamId = useRef<string | null>(null);
+
+  const cancelPreviewStream = useCallback(() => {
+    const streamId = previewStreamId.current;
+    if (!streamId) return;
+    previewStreamId.current = null;
+    getTauriCommands()
+      .cancelPreviewStream(streamId)
+      .catch((error) =>
+        console.error("Failed to cancel preview stream:", error)
+      );
+  }, []);
+
   const loadPreviews = useCallback(async () => {
     if (!activeProjectName) return;
+    cancelPreviewStream();
+    const streamId = crypto.randomUUID();
+    previewStreamId.current = streamId;
     setIsLoadingPreviews(true);
+
+    // Previews arrive one by one, the grid is re-rendered in batches
+    const received: ImagePreviewModel[] = [];
+    let flushTimer: ReturnType<typeof setTimeout> | null = null;
+    const flush = () => {
+      flushTimer = null;
+      if (previewStreamId.current !== streamId) return;
+      setImagePreviews(
+        [...received].sort((a, b) => a.imageName.localeCompare(b.imageName))
+      );
+    };
+    const onPreview = new Channel<ImagePreviewModel>();
+    onPreview.onmessage = (preview) => {
+      received.push(preview);
+      flushTimer ??= setTimeout(flush, 100);
+    };
+
     try {
-      const { getImagePreviewsInProject } = getTauriCommands();
-      const previews = await getImagePreviewsInProject(activeProjectName);
-      setImagePreviews(previews);
+      const { streamImagePreviewsInProject } = getTauriCommands();
+      await streamImagePreviewsInProject(
+        activeProjectName,
+        streamId,
+        onPreview
+      );
+      if (flushTimer) clearTimeout(flushTimer);
+      flush();
     } catch (error) {
+      if (flushTimer) clearTimeout(flushTimer);
       console.error("Failed to load image previews:", error);
       toast.error("Failed to load image previews", {
-        description: String(error),
+        description: getErrorMessage(error),
       });
     } finally {
-      setIsLoadingPreviews(false);
+      if (previewStreamId.current === streamId) {
+        previewStreamId.current = null;
+        setIsLoadingPreviews(false);
+      }
     }
-  }, [activeProjectName, setImagePreviews, setIsLoadingPreviews]);
+  }, [
+    activeProjectName,
+    cancelPreviewStream,
+    setImagePreviews,
+    setIsLoadingPreviews,
+  ]);
 
   const loadEvaluations = useCallback(async () => {
     if (!activeProjectName) return;
     try {
       const { getImageEvaluations } = getTauriCommands();
       const evaluations = await getImageEvaluations(activeProjectName);
-      setImageEvaluations(evaluations);
+      setImageEvaluations(evaluations.value);
+      if (evaluations.recoveredFromBackup) {
+        toast.warning("Evaluations restored from backup", {
+          description:
+            "The saved evaluations were damaged. Results from the last save may be missing.",
+        });
+      }
     } catch (error) {
       console.error("Failed to load image evaluations:", error);
     }
@@ -44,7 +184,32 @@ export default function useProjectImages() {
   useEffect(() => {
     loadPreviews();
     loadEvaluations();
-  }, [loadPreviews, loadEvaluations]);
+    // Stop generating previews for a project the user navigated away from
+    return cancelPreviewStream;
+  }, [loadPreviews, loadEvaluations, cancelPreviewStream]);
+
+  // Pick up images added, removed or edited outside the app, e.g. by Explorer or a sync tool
+  useEffect(() => {
+    if (!activeProjectName) return;
+    const { watchProject, unwatchProject } = getTauriCommands();
+    const unlisten = listen<ProjectFilesChanged>(
+      PROJECT_FILES_CHANGED_EVENT,
+      async ({ payload }) => {
+        if (payload.projectName !== activeProjectName) return;
+        await loadPreviews();
+        await loadEvaluations();
+      }
+    );
+    watchProject(activeProjectName).catch((error) =>
+      console.error("Failed to watch project files:", error)
+    );
+    return () => {
+      unlisten.then((stop) => stop());
+      unwatchProject(activeProjectName).catch((error) =>
+        console.error("Failed to stop watching project files:", error)
+      );
+    };
+  }, [activeProjectName, loadPreviews, loadEvaluations]);
 
   const selectImage = useCallback(
     async (imageName: string) => {
@@ -60,7 +225,7 @@ export default function useProjectImages() {
       } catch (error) {
         console.error("Failed to load full image:", error);
         toast.error(`Failed to load "${imageName}"`, {
-          description: String(error),
+          description: getErrorMessage(error),
         });
       } finally {
         setIsLoadingFullImage(false);
@@ -75,24 +240,88 @@ export default function useProjectImages() {
     if (!activeProjectName) return;
     const selected = await open({
       multiple: true,
-      filters: [{ name: "Images", extensions: ["png", "jpg", "jpeg"] }],
+      filters: [
+        { name: "Images", extensions: [...IMPORT_EXTENSIONS, "zip"] },
+      ],
     });
     if (!selected || selected.length === 0) return;
-    setPendingImageCount(selected.length);
+    const archives = selected.filter((p) => p.toLowerCase().endsWith(".zip"));
+    const images = selected.filter((p) => !archives.includes(p));
+    setPendingImageCount(images.length);
     try {
-      const { importImagesToProject } = getTauriCommands();
-      await importImagesToProject(activeProjectName, selected, focusedFolder);
+      const { importImagesToProject, importZipToProject } = getTauriCommands();
+      if (images.length > 0) {
+        // HEIC and RAW photos are stored as JPEG so evaluation and export work on them
+        const report = await withImportProgress((importId) =>
+          importImagesToProject(
+            activeProjectName,
+            images,
+            focusedFolder,
+            undefined,
+            true,
+            importId
+          )
+        );
+        showImportSummary(report);
+        if (report.cancelled) return;
+      }
+      // Archives go into a folder named after them unless a folder is focused
+      for (const archivePath of archives) {
+        const report = await withImportProgress((importId) =>
+          importZipToProject(
+            activeProjectName,
+            {
+              archivePath,
+              folder: focusedFolder,
+              useArchiveFolder: true,
+              transcodeToJpeg: true,
+            },
+            importId
+          )
+        );
+        showImportSummary(report, true);
+        if (report.cancelled) break;
+      }
       await loadPreviews();
     } catch (error) {
       console.error("Failed to import images:", error);
       toast.error("Failed to import images", {
-        description: String(error),
+        description: getErrorMessage(error),
       });
     } finally {
       setPendingImageCount(0);
     }
   }, [activeProjectName, focusedFolder, loadPreviews, setPendingImageCount]);
 
+  const addFolder = useCallback(async () => {
+    if (!activeProjectName) return;
+    const directoryPath = await open({ directory: true });
+    if (!directoryPath) return;
+    try {
+      const { importDirectoryToProject } = getTauriCommands();
+      const report = await withImportProgress((importId) =>
+        importDirectoryToProject(
+          activeProjectName,
+          {
+            directoryPath,
+            folder: focusedFolder,
+            preserveStructure: true,
+            transcodeToJpeg: true,
+          },
+          importId
+        )
+      );
+      await loadPreviews();
+      // The file count isn't known up front, so always say how it went
+      showImportSummary(report, true);
+    } catch (error) {
+      console.error("Failed to import folder:", error);
+      toast.error("Failed to import folder", {
+        description: getErrorMessage(error),
+      });
+    }
+  }, [activeProjectName, focusedFolder, loadPreviews]);
+
   const deleteImage = useCallback(
     async (imageName: string) => {
       if (!activeProjectName) return;
@@ -106,7 +335,7 @@ export default function useProjectImages() {
       } catch (error) {
         console.error("Failed to delete image:", error);
         toast.error(`Failed to delete "${imageName}"`, {
-          description: String(error),
+          description: getErrorMessage(error),
         });
       }
     },
@@ -124,13 +353,37 @@ export default function useProjectImages() {
       } catch (error) {
         console.error("Failed to move images:", error);
         toast.error("Failed to move images", {
-          description: String(error),
+          description: getErrorMessage(error),
         });
       }
     },
     [activeProjectName, loadPreviews, loadEvaluations]
   );
 
+  /** Selects every near-duplicate except the first image of each group, ready to be pruned */
+  const selectNearDuplicates = useCallback(async () => {
+    if (!activeProjectName) return;
+    try {
+      const { findNearDuplicatesInProject } = getTauriCommands();
+      const groups = await findNearDuplicatesInProject(activeProjectName);
+      const extras = groups.flatMap((g) => g.imageNames.slice(1));
+      if (extras.length === 0) {
+        toast.info("No near-duplicate images found");
+        return;
+      }
+      setSelectedImageNames(extras);
+      toast.info(
+        `Selected ${extras.length} near-duplicate images in ${groups.length} groups`,
+        { description: "The first image of each group was left unselected." }
+      );
+    } catch (error) {
+      console.error("Failed to find near-duplicates:", error);
+      toast.error("Failed to find near-duplicates", {
+        description: getErrorMessage(error),
+      });
+    }
+  }, [activeProjectName, setSelectedImageNames]);
+
   return {
     activeProjectName,
     imagePreviews,
@@ -142,8 +395,10 @@ export default function useProjectImages() {
     isEvaluating,
     selectImage,
     addImages,
+    addFolder,
     deleteImage,
     moveImagesToFolder,
+    selectNearDuplicates,
     refreshPreviews: loadPreviews,
     refreshEvaluations: loadEvaluations,
   };
@@ -1,3 +1,4 @@
+import { toast } from "sonner";
 import getTauriCommands from "@/lib/hooks/getTauriCommands";
 import { useProjectStore } from "@/lib/stores/projectStore";
 import { useCallback, useEffect, useState } from "react";
@@ -38,7 +39,12 @@ export default function useProjects() {
     async (projectName: string) => {
       try {
         const { recordProjectOpened } = getTauriCommands();
-        await recordProjectOpened(projectName);
+        const info = await recordProjectOpened(projectName);
+        if (info.recoveredFromBackup) {
+          toast.warning("Project info restored from backup", {
+            description: "The project's info file was damaged and was replaced.",
+          });
+        }
       } catch (error) {
         console.error("Failed to record project opened:", error);
       }
@@ -1,41 +1,136 @@
-import { invoke } from "@tauri-apps/api/core";
+import { Channel, invoke } from "@tauri-apps/api/core";
 import type {
+  EvaluationProviderConfig,
   ExportMode,
+  FolderNode,
   FullImageModel,
+  ImageCacheStats,
   ImageEvaluation,
+  ImageMetadataModel,
+  ImportConflictPolicy,
+  ImportReport,
   ImagePreviewModel,
+  JsonRead,
+  NearDuplicateGroup,
+  PreviewPage,
   ProjectInfoModel,
+  ReconcileReport,
+  RequestDirectoryImport,
   RequestImageEvaluation,
+  RequestPreviewPage,
+  RequestZipImport,
+  RetryPolicy,
 } from "@/lib/hooks/models";
 
+/** Event the backend emits with an ImportProgress after each imported file */
+export const IMPORT_PROGRESS_EVENT = "import-progress";
+
+/** Event the backend emits with an EvaluationProgress when an image starts and when it's done */
+export const EVALUATION_PROGRESS_EVENT = "evaluation-progress";
+
+/** Event the backend emits with a ProjectFilesChanged when a watched project changes on disk */
+export const PROJECT_FILES_CHANGED_EVENT = "project-files-changed";
+
 interface TauriCommands {
   newProject: (projectName: string) => Promise<ProjectInfoModel>;
   getProject: (projectName: string) => Promise<ProjectInfoModel>;
   getProjectNames: () => Promise<string[]>;
-  recordProjectOpened: (projectName: string) => Promise<void>;
+  recordProjectOpened: (
+    projectName: string
+  ) => Promise<JsonRead<ProjectInfoModel>>;
   getImagePreviewsInProject: (
     projectName: string
   ) => Promise<ImagePreviewModel[]>;
+  /** One page of previews in the requested order. Only the previews on the page are generated. */
+  getImagePreviewPage: (
+    projectName: string,
+    request: RequestPreviewPage
+  ) => Promise<PreviewPage>;
+  /** Send every preview over onPreview as soon as it is ready. Resolves when all were sent or the stream was cancelled. Starting another stream with the same streamId cancels the previous one. */
+  streamImagePreviewsInProject: (
+    projectName: string,
+    streamId: string,
+    onPreview: Channel<ImagePreviewModel>
+  ) => Promise<void>;
+  /** Stop a preview stream. Returns false if it already finished. */
+  cancelPreviewStream: (streamId: string) => Promise<boolean>;
+  /** Pick up images added, removed or edited outside the app */
+  reconcileProjectImages: (projectName: string) => Promise<ReconcileReport>;
+  /** Start emitting PROJECT_FILES_CHANGED_EVENT when the project's images change on disk */
+  watchProject: (projectName: string) => Promise<void>;
+  /** Stop watching a project. Returns false if it wasn't watched. */
+  unwatchProject: (projectName: string) => Promise<boolean>;
+  /** Group images that look nearly identical. maxDistance is in differing hash bits (0-64), default 10. */
+  findNearDuplicatesInProject: (
+    projectName: string,
+    maxDistance?: number
+  ) => Promise<NearDuplicateGroup[]>;
   loadImageFromProject: (
     projectName: string,
     imageName: string
   ) => Promise<FullImageModel>;
+  /** Capture time, camera, GPS position and original file name of an image */
+  getImageMetadata: (
+    projectName: string,
+    imageName: string
+  ) => Promise<ImageMetadataModel>;
+  /** Hit/miss/eviction counters and memory use of the full image cache */
+  getImageCacheStats: () => Promise<ImageCacheStats>;
+  /** Set the memory budget of the full image cache. Evicts right away if it is now over budget. */
+  setImageCacheCapacity: (capacityBytes: number) => Promise<void>;
+  /** Import images into the project. Name conflicts default to "autoRename". With transcodeToJpeg, HEIC/HEIF and RAW files are stored as JPEG. Progress is sent as IMPORT_PROGRESS_EVENT tagged with importId. */
   importImagesToProject: (
     projectName: string,
     imagePaths: string[],
-    folder?: string | null
-  ) => Promise<void>;
+    folder?: string | null,
+    conflictPolicy?: ImportConflictPolicy,
+    transcodeToJpeg?: boolean,
+    importId?: string
+  ) => Promise<ImportReport>;
+  /** Import every supported image below a directory, optionally mirroring its subdirectories as folders */
+  importDirectoryToProject: (
+    projectName: string,
+    request: RequestDirectoryImport,
+    importId?: string
+  ) => Promise<ImportReport>;
+  /** Import the images in a zip archive. Unsafe paths, oversized entries and zip bombs are skipped. */
+  importZipToProject: (
+    projectName: string,
+    request: RequestZipImport,
+    importId?: string
+  ) => Promise<ImportReport>;
+  /** Cancel a running import and roll back what it copied. Returns false if it already finished. */
+  cancelImport: (importId: string) => Promise<boolean>;
   deleteImagesFromProject: (
     projectName: string,
     imageNames: string[]
   ) => Promise<void>;
+  /** Evaluate images, saving each result as it arrives. Progress is sent as EVALUATION_PROGRESS_EVENT tagged with evaluationId. */
   evaluateImages: (
     projectName: string,
     request: RequestImageEvaluation,
     customPrompt?: string | null,
-    temperature?: number | null
+    temperature?: number | null,
+    evaluationId?: string
   ) => Promise<ImageEvaluation[]>;
-  getImageEvaluations: (projectName: string) => Promise<ImageEvaluation[]>;
+  /** Stop an evaluation, keeping the results saved so far. Resolves false if it already finished. */
+  cancelEvaluation: (evaluationId: string) => Promise<boolean>;
+  getImageEvaluations: (
+    projectName: string
+  ) => Promise<JsonRead<ImageEvaluation[]>>;
+  /** Get the provider the project's images are evaluated with by default */
+  getEvaluationProvider: (
+    projectName: string
+  ) => Promise<EvaluationProviderConfig>;
+  setEvaluationProvider: (
+    projectName: string,
+    config: EvaluationProviderConfig
+  ) => Promise<void>;
+  /** Get the directory offline OCR reads its models from */
+  getOcrModelsDir: () => Promise<string>;
+  getEvaluationRetryPolicy: () => Promise<RetryPolicy>;
+  /** Set how evaluations are retried after rate limits and server errors */
+  setEvaluationRetryPolicy: (policy: RetryPolicy) => Promise<void>;
   /** Export the images to their own folder with their new filepath suffixes defined in the ImageEvaluation models. Returns list of error messages (empty on full success). */
   exportEvaluatedImages: (
     evaluations: ImageEvaluation[],
@@ -59,20 +154,20 @@ interface TauriCommands {
   getArchivedProjectNames: () => Promise<string[]>;
   /** Permanently delete an archived project */
   deleteArchivedProject: (projectName: string) => Promise<void>;
-  /** Create a folder inside a project's images directory */
+  /** Create a folder inside a project's images directory, nested with "/" */
   createFolderInProject: (
     projectName: string,
     folderName: string
   ) => Promise<void>;
-  /** List folder names inside a project's images directory */
-  getFoldersInProject: (projectName: string) => Promise<string[]>;
-  /** Rename a folder inside a project's images directory */
+  /** List the folders inside a project's images directory as a tree */
+  getFoldersInProject: (projectName: string) => Promise<FolderNode[]>;
+  /** Rename or move a folder, along with its subfolders, inside a project */
   renameFolderInProject: (
     projectName: string,
     oldFolderName: string,
     newFolderName: string
   ) => Promise<void>;
-  /** Delete a folder and all its images from a project */
+  /** Delete a folder, its subfolders and all their images from a project */
   deleteFolderFromProject: (
     projectName: string,
     folderName: string
@@ -93,36 +188,108 @@ export default function getTauriCommands(): TauriCommands {
       invoke<ProjectInfoModel>("get_project", { projectName: pn }),
     getProjectNames: () => invoke<string[]>("get_project_names"),
     recordProjectOpened: (pn) =>
-      invoke("record_project_opened", { projectName: pn }),
+      invoke<JsonRead<ProjectInfoModel>>("record_project_opened", {
+        projectName: pn,
+      }),
     getImagePreviewsInProject: (pn) =>
       invoke<ImagePreviewModel[]>("get_image_previews_in_project", {
         projectName: pn,
       }),
+    getImagePreviewPage: (pn, request) =>
+      invoke<PreviewPage>("get_image_preview_page", {
+        projectName: pn,
+        request,
+      }),
+    streamImagePreviewsInProject: (pn, streamId, onPreview) =>
+      invoke("stream_image_previews_in_project", {
+        projectName: pn,
+        streamId,
+        onPreview,
+      }),
+    cancelPreviewStream: (streamId) =>
+      invoke<boolean>("cancel_preview_stream", { streamId }),
+    reconcileProjectImages: (pn) =>
+      invoke<ReconcileReport>("reconcile_project_images", { projectName: pn }),
+    watchProject: (pn) => invoke("watch_project", { projectName: pn }),
+    unwatchProject: (pn) =>
+      invoke<boolean>("unwatch_project", { projectName: pn }),
+    findNearDuplicatesInProject: (pn, maxDistance) =>
+      invoke<NearDuplicateGroup[]>("find_near_duplicates_in_project", {
+        projectName: pn,
+        maxDistance: maxDistance ?? null,
+      }),
     loadImageFromProject: (pn, img) =>
       invoke<FullImageModel>("load_image_from_project", {
         projectName: pn,
         imageName: img,
       }),
-    importImagesToProject: (pn, paths, folder) =>
-      invoke("import_images_to_project", {
+    getImageMetadata: (pn, img) =>
+      invoke<ImageMetadataModel>("get_image_metadata", {
+        projectName: pn,
+        imageName: img,
+      }),
+    getImageCacheStats: () => invoke<ImageCacheStats>("get_image_cache_stats"),
+    setImageCacheCapacity: (capacityBytes) =>
+      invoke("set_image_cache_capacity", { capacityBytes }),
+    importImagesToProject: (
+      pn,
+      paths,
+      folder,
+      conflictPolicy,
+      transcodeToJpeg,
+      importId
+    ) =>
+      invoke<ImportReport>("import_images_to_project", {
         projectName: pn,
         imagePaths: paths,
         folder: folder ?? null,
+        conflictPolicy: conflictPolicy ?? null,
+        transcodeToJpeg: transcodeToJpeg ?? null,
+        importId: importId ?? crypto.randomUUID(),
       }),
+    importDirectoryToProject: (pn, req, importId) =>
+      invoke<ImportReport>("import_directory_to_project", {
+        projectName: pn,
+        request: req,
+        importId: importId ?? crypto.randomUUID(),
+      }),
+    importZipToProject: (pn, req, importId) =>
+      invoke<ImportReport>("import_zip_to_project", {
+        projectName: pn,
+        request: req,
+        importId: importId ?? crypto.randomUUID(),
+      }),
+    cancelImport: (importId) => invoke<boolean>("cancel_import", { importId }),
     deleteImagesFromProject: (pn, names) =>
       invoke("delete_images_from_project", {
         projectName: pn,
         imageNames: names,
       }),
-    evaluateImages: (pn, req, customPrompt, temperature) =>
+    evaluateImages: (pn, req, customPrompt, temperature, evaluationId) =>
       invoke<ImageEvaluation[]>("evaluate_images", {
         projectName: pn,
         request: req,
         customPrompt: customPrompt ?? undefined,
         temperature: temperature ?? undefined,
+        evaluationId: evaluationId ?? crypto.randomUUID(),
       }),
+    cancelEvaluation: (evaluationId) =>
+      invoke<boolean>("cancel_evaluation", { evaluationId }),
     getImageEvaluations: (pn) =>
-      invoke<ImageEvaluation[]>("get_image_evaluations", { projectName: pn }),
+      invoke<JsonRead<ImageEvaluation[]>>("get_image_evaluations", {
+        projectName: pn,
+      }),
+    getEvaluationProvider: (pn) =>
+      invoke<EvaluationProviderConfig>("get_evaluation_provider", {
+        projectName: pn,
+      }),
+    setEvaluationProvider: (pn, config) =>
+      invoke("set_evaluation_provider", { projectName: pn, config }),
+    getOcrModelsDir: () => invoke<string>("get_ocr_models_dir"),
+    getEvaluationRetryPolicy: () =>
+      invoke<RetryPolicy>("get_evaluation_retry_policy"),
+    setEvaluationRetryPolicy: (policy) =>
+      invoke("set_evaluation_retry_policy", { policy }),
     exportEvaluatedImages: (evals, dir, mode) =>
       invoke<string[]>("export_evaluated_images", {
         evaluations: evals,
@@ -146,7 +313,7 @@ export default function getTauriCommands(): TauriCommands {
     createFolderInProject: (pn, fn) =>
       invoke("create_folder_in_project", { projectName: pn, folderName: fn }),
     getFoldersInProject: (pn) =>
-      invoke<string[]>("get_folders_in_project", { projectName: pn }),
+      invoke<FolderNode[]>("get_folders_in_project", { projectName: pn }),
     renameFolderInProject: (pn, oldFn, newFn) =>
       invoke("rename_folder_in_project", {
         projectName: pn,
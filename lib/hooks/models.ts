@@ -1,14 +1,161 @@
+/** Stable error codes returned by every Tauri command */
+export type AppErrorCode =
+  | "NotFound"
+  | "AlreadyExists"
+  | "InvalidName"
+  | "Io"
+  | "Decode"
+  | "Evaluation"
+  | "Auth"
+  | "RateLimited"
+  | "Internal";
+
+/** Error value every Tauri command rejects with */
+export interface AppError {
+  code: AppErrorCode;
+  message: string;
+}
+
+/** Saved project data, possibly restored from its backup because the file was damaged */
+export interface JsonRead<T> {
+  value: T;
+  recoveredFromBackup: boolean;
+}
+
 export interface RequestImageEvaluation {
+  /** Only needed when the images are evaluated by OpenAI */
   openaiApiKey: string;
   imageNames: string[];
+  /** Overrides the project's evaluation provider for this request */
+  provider?: EvaluationProviderConfig;
+  /** Use a serial number decoded from a barcode as the filename suffix, even if the evaluator failed */
+  preferBarcodeSerial?: boolean;
+}
+
+/** How evaluations are retried after a rate limit or a server error, for every project */
+export interface RetryPolicy {
+  maxRetries: number;
+  /** Wait before the first retry, doubled for every retry after it */
+  initialBackoffMs: number;
+  /** Upper bound for the wait, also for waits the provider asks for */
+  maxBackoffMs: number;
+  /** Images being evaluated at the same time, across all projects */
+  maxConcurrentRequests: number;
 }
 
+/** Which model evaluates a project's images */
+export type EvaluationProviderConfig =
+  | { kind: "openAi" }
+  /** Any server with an OpenAI compatible API, e.g. a local Ollama or llama.cpp server */
+  | {
+      kind: "openAiCompatible";
+      baseUrl: string;
+      model: string;
+      apiKey?: string | null;
+    }
+  /** OCR on this machine, without any network */
+  | { kind: "offlineOcr" }
+  /** Made up results derived from the file names, without calling any model */
+  | { kind: "mock" };
+
 export type ExportMode = "all" | "folders";
 
+/** What to do when an imported file has the same name as an existing image */
+export type ImportConflictPolicy =
+  | "skip"
+  | "overwrite"
+  | "autoRename"
+  | "renameByHash";
+
+/** Summary of an import, listing what happened to every source file */
+export interface ImportReport {
+  imported: string[];
+  renamed: { sourcePath: string; imageName: string }[];
+  skipped: { sourcePath: string; reason: string }[];
+  /** Existing images that were replaced. Also listed in `imported`. */
+  overwritten: string[];
+  /** Files whose exact content is already in the project, with the matching image */
+  duplicates: { sourcePath: string; existingImageName: string }[];
+  /** Project folders images were imported into when importing a directory or archive */
+  folders: string[];
+  /** The import was cancelled and everything it copied was removed again */
+  cancelled: boolean;
+}
+
+/** Sent as an "import-progress" event after each source file of an import */
+export interface ImportProgress {
+  importId: string;
+  /** Zero based position of the file among all files of the import */
+  index: number;
+  total: number;
+  fileName: string;
+  /** Size of the source file */
+  bytes: number;
+  /** Combined size of the files handled so far, this one included */
+  bytesDone: number;
+  totalBytes: number;
+  /**
+   * Sent while a zip archive is extracted, before its images are imported. Positions are of the
+   * archive's entries, and sizes are of the compressed entries and the archive.
+   */
+  extracting: boolean;
+}
+
+export type EvaluationImageStatus = "started" | "completed" | "failed";
+
+/** Sent when an image of an evaluation starts and again when it's done */
+export interface EvaluationProgress {
+  evaluationId: string;
+  projectName: string;
+  imageName: string;
+  status: EvaluationImageStatus;
+  /** Images done so far, this one included once it's done */
+  done: number;
+  total: number;
+  /** The evaluation as saved, once the image is done. Null if the image was deleted meanwhile. */
+  evaluation: ImageEvaluation | null;
+}
+
+/** Imports the images in a zip archive */
+export interface RequestZipImport {
+  archivePath: string;
+  /** Project folder to import into. Takes precedence over useArchiveFolder. */
+  folder?: string | null;
+  /** Import into a folder named after the archive, e.g. survey.zip into survey */
+  useArchiveFolder?: boolean;
+  conflictPolicy?: ImportConflictPolicy;
+  /** Store HEIC/HEIF and RAW photos as JPEG */
+  transcodeToJpeg?: boolean;
+}
+
+/** Imports every image in a directory tree */
+export interface RequestDirectoryImport {
+  directoryPath: string;
+  /** Project folder to import into, the project root if not set */
+  folder?: string | null;
+  /** Import images in subdirectories into project folders named after them */
+  preserveStructure?: boolean;
+  conflictPolicy?: ImportConflictPolicy;
+  /** Store HEIC/HEIF and RAW photos as JPEG */
+  transcodeToJpeg?: boolean;
+}
+
 export interface ImageEvaluation {
   imageName: string;
   result: ImageEvaluationResult | undefined;
   failReason: string | undefined;
+  /** The image was edited outside the app after it was evaluated */
+  stale?: boolean;
+  /** QR codes and barcodes found in the image */
+  barcodes?: DecodedBarcode[];
+  /** How many times the evaluation was retried after a rate limit or server error */
+  retries?: number;
+}
+
+export interface DecodedBarcode {
+  /** e.g. "qrcode" or "code 128" */
+  format: string;
+  payload: string;
 }
 
 export interface ImageEvaluationResult {
@@ -22,20 +169,99 @@ export interface ProjectInfoModel {
   projectName: string;
 }
 
-/** Image preview with thumbnail for display in lists/grids */
+/** A folder in a project along with the folders nested in it */
+export interface FolderNode {
+  /** The folder's own name, e.g. "Floor 2" */
+  name: string;
+  /** Relative to the project's images, e.g. "Building A/Floor 2" */
+  path: string;
+  children: FolderNode[];
+}
+
+/** Image info for display in lists/grids. The thumbnail is loaded with `getThumbnailUrl`. */
 export interface ImagePreviewModel {
   imageName: string;
-  base64Preview: string;
   imageSizeBytes: number;
+  /** As displayed, i.e. after applying the EXIF orientation */
   width: number;
   height: number;
+  /** Hex encoded BLAKE3 hash of the file contents */
+  contentHash?: string;
+  /** Hex encoded 64 bit difference hash, used to find near-duplicate shots */
+  perceptualHash?: string;
+  metadata?: ImageMetadataModel;
+}
+
+/** Capture details read from EXIF/XMP. Every field is optional. */
+export interface ImageMetadataModel {
+  /** ISO 8601 local time, with the UTC offset if the camera recorded one */
+  capturedAt?: string;
+  cameraMake?: string;
+  cameraModel?: string;
+  gps?: GpsCoordinates;
+  /** File name before the image was imported (and possibly renamed) */
+  originalFileName?: string;
+}
+
+export interface GpsCoordinates {
+  /** Decimal degrees, negative south of the equator */
+  latitude: number;
+  /** Decimal degrees, negative west of Greenwich */
+  longitude: number;
+  /** Meters above sea level */
+  altitude?: number;
+}
+
+/** Order of paginated previews. "name" is case insensitive, "modified" is the file's modification time. */
+export type PreviewSortKey = "name" | "size" | "modified";
+
+export interface RequestPreviewPage {
+  offset: number;
+  limit: number;
+  sortKey?: PreviewSortKey;
+  descending?: boolean;
+}
+
+/** One page of previews plus the number of images in the whole project */
+export interface PreviewPage {
+  previews: ImagePreviewModel[];
+  total: number;
+}
+
+/** Images that look nearly identical, e.g. several frames of the same subject */
+/** Payload of PROJECT_FILES_CHANGED_EVENT */
+export interface ProjectFilesChanged {
+  projectName: string;
+  changes: ReconcileReport;
+}
+
+/** What reconciling a project's image index with its files on disk found, by image name */
+export interface ReconcileReport {
+  added: string[];
+  removed: string[];
+  /** Images whose contents were edited since they were indexed */
+  changed: string[];
+}
+
+export interface NearDuplicateGroup {
+  imageNames: string[];
+}
+
+/** Diagnostics for the in-memory full image cache */
+export interface ImageCacheStats {
+  capacityBytes: number;
+  usedBytes: number;
+  entryCount: number;
+  hits: number;
+  misses: number;
+  evictions: number;
 }
 
-/** Full resolution image data */
+/** Full resolution image info. The image is loaded with `getImageUrl`. */
 export interface FullImageModel {
   imageName: string;
-  base64Image: string;
   imageSizeBytes: number;
+  /** As displayed, i.e. after applying the EXIF orientation */
   width: number;
   height: number;
 }
@@ -4,10 +4,14 @@ import { useSettingsStore } from "@/lib/stores/settingsStore";
 
 const CUSTOM_PROMPT_KEY = "customPrompt";
 const CUSTOM_TEMPERATURE_KEY = "customTemperature";
+const PREFER_BARCODE_SERIAL_KEY = "preferBarcodeSerial";
 
 export default function useCustomPrompt() {
   const setCustomPrompt = useSettingsStore((s) => s.setCustomPrompt);
   const setCustomTemperature = useSettingsStore((s) => s.setCustomTemperature);
+  const setPreferBarcodeSerial = useSettingsStore(
+    (s) => s.setPreferBarcodeSerial
+  );
   const { getValue, setValue } = useTauriStore();
 
   useEffect(() => {
@@ -17,7 +21,10 @@ export default function useCustomPrompt() {
     getValue<number>(CUSTOM_TEMPERATURE_KEY).then((saved) => {
       setCustomTemperature(saved ?? null);
     });
-  }, [setCustomPrompt, setCustomTemperature]);
+    getValue<boolean>(PREFER_BARCODE_SERIAL_KEY).then((saved) => {
+      setPreferBarcodeSerial(saved ?? false);
+    });
+  }, [setCustomPrompt, setCustomTemperature, setPreferBarcodeSerial]);
 
   const saveCustomPrompt = useCallback(
     async (prompt: string | null) => {
@@ -35,5 +42,13 @@ export default function useCustomPrompt() {
     [setCustomTemperature, setValue]
   );
 
-  return { saveCustomPrompt, saveCustomTemperature };
+  const savePreferBarcodeSerial = useCallback(
+    async (prefer: boolean) => {
+      setPreferBarcodeSerial(prefer);
+      await setValue(PREFER_BARCODE_SERIAL_KEY, prefer);
+    },
+    [setPreferBarcodeSerial, setValue]
+  );
+
+  return { saveCustomPrompt, saveCustomTemperature, savePreferBarcodeSerial };
 }
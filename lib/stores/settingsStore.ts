@@ -3,13 +3,17 @@ import { create } from "zustand";
 interface SettingsState {
   customPrompt: string | null;
   customTemperature: number | null;
+  preferBarcodeSerial: boolean;
   setCustomPrompt: (prompt: string | null) => void;
   setCustomTemperature: (temperature: number | null) => void;
+  setPreferBarcodeSerial: (prefer: boolean) => void;
 }
 
 export const useSettingsStore = create<SettingsState>((set) => ({
   customPrompt: null,
   customTemperature: null,
+  preferBarcodeSerial: false,
   setCustomPrompt: (prompt) => set({ customPrompt: prompt }),
   setCustomTemperature: (temperature) => set({ customTemperature: temperature }),
+  setPreferBarcodeSerial: (prefer) => set({ preferBarcodeSerial: prefer }),
 }));
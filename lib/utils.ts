@@ -1,6 +1,43 @@
+import { convertFileSrc } from "@tauri-apps/api/core"
 import { clsx, type ClassValue } from "clsx"
 import { twMerge } from "tailwind-merge"
+import type { AppError } from "@/lib/hooks/models"
+
+/** Scheme the backend serves project images on, see `image_protocol.rs` */
+const IMAGE_PROTOCOL_SCHEME = "imgreader"
 
 export function cn(...inputs: ClassValue[]) {
   return twMerge(clsx(inputs))
 }
+
+export function isAppError(error: unknown): error is AppError {
+  return (
+    typeof error === "object" &&
+    error !== null &&
+    "code" in error &&
+    "message" in error
+  )
+}
+
+/** Human readable message for anything a Tauri command rejected with */
+export function getErrorMessage(error: unknown): string {
+  if (isAppError(error)) return error.message
+  if (error instanceof Error) return error.message
+  return String(error)
+}
+
+function imageProtocolUrl(path: string, version?: string) {
+  const url = convertFileSrc(path, IMAGE_PROTOCOL_SCHEME)
+  // The URL of a replaced image doesn't change, so a version forces the webview to refetch it
+  return version ? `${url}?v=${encodeURIComponent(version)}` : url
+}
+
+/** URL of a full resolution project image */
+export function getImageUrl(projectName: string, imageName: string, version?: string) {
+  return imageProtocolUrl(`project/${projectName}/image/${imageName}`, version)
+}
+
+/** URL of the JPEG thumbnail of a project image */
+export function getThumbnailUrl(projectName: string, imageName: string, version?: string) {
+  return imageProtocolUrl(`project/${projectName}/thumb/${imageName}`, version)
+}
@@ -0,0 +1,71 @@
+use std::fmt::Display;
+
+use serde::Serialize;
+
+pub type AppResult<T> = Result<T, AppError>;
+
+/// Error returned by the services and every Tauri command.
+/// Serialized as `{ "code": "NotFound", "message": "..." }` so the frontend can react to each case.
+/// The codes are part of the frontend contract, don't rename variants.
+#[derive(Debug, Clone, thiserror::Error, Serialize)]
+#[serde(tag = "code", content = "message")]
+pub enum AppError {
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Already exists: {0}")]
+    AlreadyExists(String),
+    #[error("Invalid name: {0}")]
+    InvalidName(String),
+    #[error("I/O error: {0}")]
+    Io(String),
+    #[error("Could not decode: {0}")]
+    Decode(String),
+    #[error("Evaluation failed: {0}")]
+    Evaluation(String),
+    #[error("Authentication failed: {0}")]
+    Auth(String),
+    #[error("Rate limited: {0}")]
+    RateLimited(String),
+    #[error("Internal error: {0}")]
+    Internal(String),
+}
+
+impl AppError {
+    /// Wraps an I/O error, keeping its kind but prefixing the path or action it relates to
+    pub fn io(context: impl Display, err: std::io::Error) -> Self {
+        let message = format!("{context}: {err}");
+        match err.kind() {
+            std::io::ErrorKind::NotFound => Self::NotFound(message),
+            std::io::ErrorKind::AlreadyExists => Self::AlreadyExists(message),
+            _ => Self::Io(message),
+        }
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(err: std::io::Error) -> Self {
+        match err.kind() {
+            std::io::ErrorKind::NotFound => Self::NotFound(err.to_string()),
+            std::io::ErrorKind::AlreadyExists => Self::AlreadyExists(err.to_string()),
+            _ => Self::Io(err.to_string()),
+        }
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(err: serde_json::Error) -> Self {
+        Self::Decode(err.to_string())
+    }
+}
+
+impl From<image::ImageError> for AppError {
+    fn from(err: image::ImageError) -> Self {
+        Self::Decode(err.to_string())
+    }
+}
+
+impl From<tokio::task::JoinError> for AppError {
+    fn from(err: tokio::task::JoinError) -> Self {
+        Self::Internal(err.to_string())
+    }
+}
@@ -1,6 +1,8 @@
+mod app_error;
 mod app_service_container;
 mod constants;
 mod services;
+use services::projects_service::image_protocol::{self, IMAGE_PROTOCOL_SCHEME};
 pub use services::projects_service::tauri_exports::*;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -8,6 +10,10 @@ pub fn run() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_store::Builder::new().build())
+        .register_asynchronous_uri_scheme_protocol(
+            IMAGE_PROTOCOL_SCHEME,
+            image_protocol::handle_request,
+        )
         .setup(|app| {
             if cfg!(debug_assertions) {
                 app.handle().plugin(
@@ -24,11 +30,30 @@ pub fn run() {
             get_project,
             get_project_names,
             get_image_previews_in_project,
+            get_image_preview_page,
+            stream_image_previews_in_project,
+            cancel_preview_stream,
+            reconcile_project_images,
+            watch_project,
+            unwatch_project,
+            find_near_duplicates_in_project,
             load_image_from_project,
+            get_image_metadata,
+            get_image_cache_stats,
+            set_image_cache_capacity,
             import_images_to_project,
+            import_directory_to_project,
+            import_zip_to_project,
+            cancel_import,
             delete_images_from_project,
             evaluate_images,
+            cancel_evaluation,
             get_image_evaluations,
+            get_evaluation_provider,
+            set_evaluation_provider,
+            get_ocr_models_dir,
+            get_evaluation_retry_policy,
+            set_evaluation_retry_policy,
             export_evaluated_images,
             open_path_in_file_manager,
             open_image_in_default_app,
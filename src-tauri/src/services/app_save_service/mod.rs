@@ -1,2 +1,2 @@
 pub mod service;
-pub use service::AppSaveService;
\ No newline at end of file
+pub use service::{AppSaveService, JsonRead};
\ No newline at end of file
@@ -1,13 +1,35 @@
 use std::{
+    ffi::OsString,
     fs,
     io::BufWriter,
     path::{Path, PathBuf},
+    sync::atomic::{AtomicU64, Ordering},
 };
 
 use dirs::data_dir;
 use serde::{Deserialize, Serialize};
 
-use crate::constants;
+use crate::{
+    app_error::{AppError, AppResult},
+    constants,
+};
+
+/// Extension appended to a JSON file while its new contents are being written
+const TMP_EXTENSION: &str = "tmp";
+/// Extension appended to the last known good copy of a JSON file
+const BACKUP_EXTENSION: &str = "bak";
+/// Makes temporary file names unique when the same JSON file is saved twice at once
+static TMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Result of a JSON read that may have fallen back to the backup copy.
+/// Sent as is to the frontend so it can tell the user their data was restored.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct JsonRead<T> {
+    pub value: T,
+    /// True when the primary file could not be read or parsed and the `.bak` copy was used instead
+    pub recovered_from_backup: bool,
+}
 
 pub struct AppSaveService {
     pub save_dir: PathBuf,
@@ -15,17 +37,19 @@ pub struct AppSaveService {
 
 impl Default for AppSaveService {
     fn default() -> Self {
-        let save_path = AppSaveService::get_save_path_internal();
-        if !save_path.exists() {
-            fs::create_dir_all(save_path.clone()).expect("could not create App directory");
-        }
-        Self {
-            save_dir: save_path,
-        }
+        Self::new(AppSaveService::get_save_path_internal())
     }
 }
 
 impl AppSaveService {
+    /// Saves everything under `save_dir` instead of the app data directory, e.g. a temp dir
+    pub fn new(save_dir: PathBuf) -> Self {
+        if !save_dir.exists() {
+            fs::create_dir_all(save_dir.clone()).expect("could not create App directory");
+        }
+        Self { save_dir }
+    }
+
     /// Example: `projects\project1`
     pub fn ensure_folder_created(&self, relative_path: &str) {
         let initial_path = self.save_dir.clone();
@@ -36,10 +60,10 @@ impl AppSaveService {
     }
 
     /// Returns the full paths of all the items in the folder
-    pub fn get_items_in_folder(&self, relative_path: &str) -> Result<Vec<PathBuf>, String> {
+    pub fn get_items_in_folder(&self, relative_path: &str) -> AppResult<Vec<PathBuf>> {
         let mut items = Vec::new();
         let full_path = self.save_dir.join(Path::new(relative_path));
-        let read_dir = fs::read_dir(full_path).map_err(|e| e.to_string())?;
+        let read_dir = fs::read_dir(full_path).map_err(|e| AppError::io(relative_path, e))?;
         for item in read_dir.into_iter().flatten() {
             items.push(item.path())
         }
@@ -52,59 +76,158 @@ impl AppSaveService {
     }
 
     /// Example: `projects\project1\thing.json`
-    pub fn save_json<T: Serialize>(&self, relative_path: &str, json: &T) -> Result<(), String> {
-        let initial_path = self.save_dir.clone();
-        let full_path = initial_path.join(Path::new(relative_path));
-        let file = fs::File::create(full_path).map_err(|e| e.to_string())?;
-        let writer = BufWriter::new(file);
+    ///
+    /// The JSON is written to a uniquely named `.tmp` sibling, fsynced and then renamed over the
+    /// target, so a crash mid-write never leaves a truncated file behind and two saves of the same
+    /// file never write into each other's temporary file.
+    /// The previous contents are kept as `.bak`.
+    pub fn save_json<T: Serialize>(&self, relative_path: &str, json: &T) -> AppResult<()> {
+        let full_path = self.get_full_path(relative_path);
+        let counter = TMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let tmp_path = Self::sibling_path(
+            &full_path,
+            &format!("{}.{counter}.{TMP_EXTENSION}", std::process::id()),
+        );
+        let bak_path = Self::sibling_path(&full_path, BACKUP_EXTENSION);
+
+        if let Err(err) = Self::write_synced(&tmp_path, relative_path, json) {
+            let _ = fs::remove_file(&tmp_path);
+            return Err(err);
+        }
 
-        serde_json::to_writer_pretty(writer, json).map_err(|e| e.to_string())
+        // Only roll the backup forward if the current file is intact, otherwise we would
+        // replace the last good copy with a corrupt one
+        if Self::is_valid_json_file(&full_path) {
+            fs::copy(&full_path, &bak_path).map_err(|e| AppError::io(relative_path, e))?;
+            Self::sync_file(&bak_path)?;
+        }
+
+        fs::rename(&tmp_path, &full_path).map_err(|e| AppError::io(relative_path, e))?;
+        Self::sync_parent_dir(&full_path);
+        Ok(())
     }
 
-    pub fn read_file(&self, relative_path: &str) -> Result<String, String> {
+    /// Writes `json` to `path` and fsyncs it
+    fn write_synced<T: Serialize>(path: &Path, relative_path: &str, json: &T) -> AppResult<()> {
+        let file = fs::File::create(path).map_err(|e| AppError::io(relative_path, e))?;
+        let mut writer = BufWriter::new(file);
+        serde_json::to_writer_pretty(&mut writer, json)?;
+        let file = writer
+            .into_inner()
+            .map_err(|e| AppError::io(relative_path, e.into_error()))?;
+        file.sync_all().map_err(|e| AppError::io(relative_path, e))
+    }
+
+    pub fn read_file(&self, relative_path: &str) -> AppResult<String> {
         let initial_path = self.save_dir.clone();
         let full_path = initial_path.join(Path::new(relative_path));
-        let content = fs::read_to_string(full_path).map_err(|e| e.to_string())?;
+        let content = fs::read_to_string(full_path).map_err(|e| AppError::io(relative_path, e))?;
         Ok(content)
     }
 
+    /// Reads a JSON file, falling back to its `.bak` copy if the primary file is corrupt
     pub fn read_json<T: for<'de> Deserialize<'de>>(
         &self,
         relative_path: &str,
-    ) -> Result<T, String> {
-        let content = self.read_file(relative_path)?;
-        serde_json::from_str(&content).map_err(|e| e.to_string())
+    ) -> AppResult<T> {
+        self.read_json_with_recovery(relative_path)
+            .map(|read| read.value)
+    }
+
+    /// Reads a JSON file and reports whether the `.bak` copy had to be used.
+    /// Returns the primary file's error if the backup is missing or unreadable too.
+    pub fn read_json_with_recovery<T: for<'de> Deserialize<'de>>(
+        &self,
+        relative_path: &str,
+    ) -> AppResult<JsonRead<T>> {
+        let primary_err = match self.read_file(relative_path) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(value) => {
+                    return Ok(JsonRead {
+                        value,
+                        recovered_from_backup: false,
+                    })
+                }
+                Err(e) => AppError::Decode(format!("{relative_path}: {e}")),
+            },
+            Err(e) => e,
+        };
+
+        let bak_path = Self::sibling_path(&self.get_full_path(relative_path), BACKUP_EXTENSION);
+        let value = fs::read_to_string(&bak_path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .ok_or(primary_err.clone())?;
+
+        log::warn!("Recovered {relative_path} from backup after read failure: {primary_err}");
+        Ok(JsonRead {
+            value,
+            recovered_from_backup: true,
+        })
     }
 
     /// Copies a file from the source path to the destination path
     /// Example: `projects\project1\thing.json`
-    pub fn copy_file(&self, source_path: &str, relative_dest_path: &str) -> Result<(), String> {
+    pub fn copy_file(&self, source_path: &str, relative_dest_path: &str) -> AppResult<()> {
         let initial_path = self.save_dir.clone();
         let full_path = initial_path.join(Path::new(relative_dest_path));
-        fs::copy(source_path, full_path).map_err(|e| e.to_string())?;
+        fs::copy(source_path, full_path).map_err(|e| AppError::io(source_path, e))?;
         Ok(())
     }
 
     /// Deletes a file at the relative path
     /// Example: `projects\project1\images\photo.jpg`
-    pub fn delete_file(&self, relative_path: &str) -> Result<(), String> {
+    pub fn delete_file(&self, relative_path: &str) -> AppResult<()> {
         let full_path = self.save_dir.join(Path::new(relative_path));
-        fs::remove_file(full_path).map_err(|e| e.to_string())
+        fs::remove_file(full_path).map_err(|e| AppError::io(relative_path, e))
     }
 
     /// Deletes a folder and all its contents at the relative path
     /// Example: `projects\project1`
-    pub fn delete_folder(&self, relative_path: &str) -> Result<(), String> {
+    pub fn delete_folder(&self, relative_path: &str) -> AppResult<()> {
         let full_path = self.save_dir.join(Path::new(relative_path));
-        fs::remove_dir_all(full_path).map_err(|e| e.to_string())
+        fs::remove_dir_all(full_path).map_err(|e| AppError::io(relative_path, e))
     }
 
     /// Moves/renames a folder from one relative path to another
     /// Example: from `projects\project1` to `archived\project1`
-    pub fn rename_folder(&self, from: &str, to: &str) -> Result<(), String> {
+    pub fn rename_folder(&self, from: &str, to: &str) -> AppResult<()> {
         let from_path = self.save_dir.join(Path::new(from));
         let to_path = self.save_dir.join(Path::new(to));
-        fs::rename(from_path, to_path).map_err(|e| e.to_string())
+        fs::rename(from_path, to_path).map_err(|e| AppError::io(from, e))
+    }
+
+    /// Returns `path` with `extension` appended, e.g. `image_evals.json` -> `image_evals.json.bak`
+    fn sibling_path(path: &Path, extension: &str) -> PathBuf {
+        let mut name = path.file_name().map(OsString::from).unwrap_or_default();
+        name.push(".");
+        name.push(extension);
+        path.with_file_name(name)
+    }
+
+    fn is_valid_json_file(path: &Path) -> bool {
+        fs::read(path)
+            .ok()
+            .is_some_and(|bytes| serde_json::from_slice::<serde_json::Value>(&bytes).is_ok())
+    }
+
+    fn sync_file(path: &Path) -> AppResult<()> {
+        fs::File::open(path)
+            .and_then(|f| f.sync_all())
+            .map_err(|e| AppError::io(path.display(), e))
+    }
+
+    /// Makes a rename durable. Directories can't be opened for syncing on Windows, where
+    /// `MoveFileEx` already flushes the metadata change.
+    fn sync_parent_dir(path: &Path) {
+        #[cfg(unix)]
+        if let Some(parent) = path.parent() {
+            if let Err(err) = fs::File::open(parent).and_then(|dir| dir.sync_all()) {
+                log::warn!("Failed to sync directory {}: {err}", parent.display());
+            }
+        }
+        #[cfg(not(unix))]
+        let _ = path;
     }
 
     /// Get the save path for the app from the AppData directory
@@ -113,3 +236,69 @@ impl AppSaveService {
         save_path.join(constants::APP_NAME)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fails partway through serializing, like a write that gets interrupted
+    struct FailsMidway;
+
+    impl Serialize for FailsMidway {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            use serde::ser::{Error, SerializeSeq};
+            let mut seq = serializer.serialize_seq(Some(2))?;
+            seq.serialize_element("written")?;
+            Err(S::Error::custom("interrupted"))
+        }
+    }
+
+    fn test_service(name: &str) -> AppSaveService {
+        AppSaveService::new(std::env::temp_dir().join(format!(
+            "get-crap-done-save-{name}-{}-{}",
+            std::process::id(),
+            TMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        )))
+    }
+
+    #[test]
+    fn interrupted_save_keeps_previous_file() {
+        let service = test_service("interrupted");
+        service.save_json("data.json", &vec!["first"]).unwrap();
+
+        assert!(service.save_json("data.json", &FailsMidway).is_err());
+
+        let read = service
+            .read_json_with_recovery::<Vec<String>>("data.json")
+            .unwrap();
+        assert_eq!(read.value, vec!["first"]);
+        assert!(!read.recovered_from_backup);
+        let leftovers: Vec<_> = fs::read_dir(&service.save_dir)
+            .unwrap()
+            .flatten()
+            .filter(|entry| entry.file_name().to_string_lossy().ends_with(".tmp"))
+            .collect();
+        assert!(leftovers.is_empty());
+        let _ = fs::remove_dir_all(&service.save_dir);
+    }
+
+    #[test]
+    fn corrupt_file_is_read_from_backup() {
+        let service = test_service("recovery");
+        service.save_json("data.json", &vec!["first"]).unwrap();
+        service.save_json("data.json", &vec!["second"]).unwrap();
+        fs::write(service.get_full_path("data.json"), b"[\"trunc").unwrap();
+
+        let read = service
+            .read_json_with_recovery::<Vec<String>>("data.json")
+            .unwrap();
+        assert_eq!(read.value, vec!["first"]);
+        assert!(read.recovered_from_backup);
+
+        fs::remove_file(service.get_full_path("data.json.bak")).unwrap();
+        assert!(service
+            .read_json_with_recovery::<Vec<String>>("data.json")
+            .is_err());
+        let _ = fs::remove_dir_all(&service.save_dir);
+    }
+}
@@ -0,0 +1,476 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use tokio_util::sync::CancellationToken;
+use zip::ZipArchive;
+
+use crate::{
+    app_error::{AppError, AppResult},
+    services::projects_service::{components::image_decoders, models::SkippedImport},
+};
+
+/// How much an archive may hold. Images barely compress, so a big entry that claims to shrink
+/// more than `max_compression_ratio` is treated as a zip bomb.
+struct ExtractLimits {
+    /// Max number of entries in an archive, files and directories alike
+    max_entries: usize,
+    /// Max uncompressed size of a single extracted image
+    max_entry_bytes: u64,
+    /// Max combined uncompressed size of everything extracted from one archive
+    max_total_bytes: u64,
+    max_compression_ratio: u64,
+    /// Entries smaller than this are never treated as zip bombs, whatever their ratio
+    ratio_check_min_bytes: u64,
+}
+
+const LIMITS: ExtractLimits = ExtractLimits {
+    max_entries: 20_000,
+    max_entry_bytes: 512 * 1024 * 1024,
+    max_total_bytes: 16 * 1024 * 1024 * 1024,
+    max_compression_ratio: 100,
+    ratio_check_min_bytes: 1024 * 1024,
+};
+
+pub struct ExtractedImage {
+    /// Where the image was extracted to
+    pub path: PathBuf,
+    /// `archive.zip/inner/path.jpg`, shown in import reports
+    pub source_path: String,
+}
+
+#[derive(Default)]
+pub struct ExtractedArchive {
+    pub images: Vec<ExtractedImage>,
+    pub skipped: Vec<SkippedImport>,
+}
+
+/// Sent after each entry of an archive is handled, whether it was extracted or not
+pub struct ExtractionProgress {
+    /// Zero based position of the entry in the archive
+    pub index: usize,
+    pub total: usize,
+    /// Path of the entry inside the archive
+    pub entry_name: String,
+    /// Compressed size of the entry
+    pub bytes: u64,
+    /// Compressed size of the entries handled so far, this one included
+    pub bytes_done: u64,
+    /// Size of the archive file
+    pub total_bytes: u64,
+}
+
+/// Extracts the supported images in a zip archive into `dest_dir`, each in its own numbered
+/// subdirectory so entries with the same file name in different archive folders don't collide.
+/// Blocks until done, `cancel` is checked between entries and `None` returned if it was
+/// cancelled.
+///
+/// Stored paths are never trusted: only the file name of an entry is used, and entries whose path
+/// is absolute or climbs out with `..` are skipped. Entries that are too big or compress
+/// suspiciously well are skipped too, and the import fails if the archive as a whole expands to
+/// more than 16 GiB. Sizes are checked against the bytes actually written, since the sizes stored
+/// in the archive can lie.
+pub fn extract_images(
+    archive_path: &Path,
+    dest_dir: &Path,
+    cancel: &CancellationToken,
+    on_progress: impl FnMut(ExtractionProgress),
+) -> AppResult<Option<ExtractedArchive>> {
+    extract_images_within(archive_path, dest_dir, &LIMITS, cancel, on_progress)
+}
+
+fn extract_images_within(
+    archive_path: &Path,
+    dest_dir: &Path,
+    limits: &ExtractLimits,
+    cancel: &CancellationToken,
+    mut on_progress: impl FnMut(ExtractionProgress),
+) -> AppResult<Option<ExtractedArchive>> {
+    let archive_name = archive_path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let decode_error = |e: zip::result::ZipError| AppError::Decode(format!("{archive_name}: {e}"));
+    let too_big = || {
+        AppError::Decode(format!(
+            "{archive_name} expands to more than {}",
+            format_size(limits.max_total_bytes)
+        ))
+    };
+    let too_big_entry = || format!("Larger than {}", format_size(limits.max_entry_bytes));
+
+    let file = File::open(archive_path).map_err(|e| AppError::io(archive_path.display(), e))?;
+    let archive_bytes = file.metadata().map(|m| m.len()).unwrap_or_default();
+    let mut archive = ZipArchive::new(file).map_err(decode_error)?;
+    if archive.len() > limits.max_entries {
+        return Err(AppError::Decode(format!(
+            "{archive_name} has more than {} entries",
+            limits.max_entries
+        )));
+    }
+
+    let mut extracted = ExtractedArchive::default();
+    let mut total_bytes = 0u64;
+    let entry_count = archive.len();
+    let mut bytes_done = 0u64;
+    let mut report = |index, entry_name, bytes| {
+        bytes_done += bytes;
+        on_progress(ExtractionProgress {
+            index,
+            total: entry_count,
+            entry_name,
+            bytes,
+            bytes_done,
+            total_bytes: archive_bytes,
+        });
+    };
+    for i in 0..entry_count {
+        if cancel.is_cancelled() {
+            return Ok(None);
+        }
+        let mut entry = match archive.by_index(i) {
+            Ok(entry) => entry,
+            Err(e) => {
+                // e.g. encrypted entries or unsupported compression methods
+                let source_path = format!("{archive_name} (entry {i})");
+                extracted.skipped.push(SkippedImport {
+                    source_path: source_path.clone(),
+                    reason: e.to_string(),
+                });
+                report(i, source_path, 0);
+                continue;
+            }
+        };
+        let entry_name = entry.name().to_string();
+        let entry_bytes = entry.compressed_size();
+        'entry: {
+            if entry.is_dir() {
+                break 'entry;
+            }
+            let source_path = format!("{archive_name}/{entry_name}");
+            let mut skip = |reason: String| {
+                extracted.skipped.push(SkippedImport {
+                    source_path: source_path.clone(),
+                    reason,
+                })
+            };
+
+            let Some(inner_path) = entry.enclosed_name() else {
+                skip("Unsafe path in archive".to_string());
+                break 'entry;
+            };
+            let Some(file_name) = inner_path.file_name().map(|n| n.to_os_string()) else {
+                break 'entry;
+            };
+            // Skips macOS `__MACOSX/._name` resource forks and other hidden files
+            let is_hidden = inner_path.components().any(|c| {
+                let name = c.as_os_str().to_string_lossy();
+                name.starts_with('.') || name == "__MACOSX"
+            });
+            if is_hidden || !image_decoders::is_supported_image(&inner_path) {
+                break 'entry;
+            }
+            if entry.size() > limits.max_entry_bytes {
+                skip(too_big_entry());
+                break 'entry;
+            }
+            if entry.size() > limits.ratio_check_min_bytes
+                && entry.size() / entry.compressed_size().max(1) > limits.max_compression_ratio
+            {
+                skip("Compressed suspiciously well, possibly a zip bomb".to_string());
+                break 'entry;
+            }
+            let remaining_bytes = limits.max_total_bytes - total_bytes;
+            if entry.size() > remaining_bytes {
+                return Err(too_big());
+            }
+
+            let entry_dir = dest_dir.join(extracted.images.len().to_string());
+            fs::create_dir_all(&entry_dir).map_err(|e| AppError::io(entry_dir.display(), e))?;
+            let path = entry_dir.join(file_name);
+            let limit = limits.max_entry_bytes.min(remaining_bytes);
+            let written = match copy_limited(&mut entry, &path, limit) {
+                Ok(written) if written <= limit => written,
+                Ok(_) => {
+                    let _ = fs::remove_file(&path);
+                    if limit == remaining_bytes {
+                        return Err(too_big());
+                    }
+                    skip(too_big_entry());
+                    break 'entry;
+                }
+                Err(e) => {
+                    let _ = fs::remove_file(&path);
+                    skip(e.to_string());
+                    break 'entry;
+                }
+            };
+            total_bytes += written;
+            extracted.images.push(ExtractedImage { path, source_path });
+        }
+        report(i, entry_name, entry_bytes);
+    }
+    Ok(Some(extracted))
+}
+
+/// Copies at most `limit + 1` bytes, so a result over `limit` means the entry was cut short
+fn copy_limited(reader: &mut impl Read, path: &Path, limit: u64) -> io::Result<u64> {
+    let mut out = File::create(path)?;
+    io::copy(&mut reader.take(limit + 1), &mut out)
+}
+
+/// `512 MiB`, `16 GiB`
+fn format_size(bytes: u64) -> String {
+    const GIB: u64 = 1024 * 1024 * 1024;
+    const MIB: u64 = 1024 * 1024;
+    if bytes >= GIB {
+        format!("{} GiB", bytes / GIB)
+    } else if bytes >= MIB {
+        format!("{} MiB", bytes / MIB)
+    } else {
+        format!("{bytes} bytes")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::Write,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use zip::{write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+    use super::*;
+
+    static NEXT_TEST_DIR: AtomicUsize = AtomicUsize::new(0);
+
+    /// A zip archive written to its own temp dir, removed when dropped
+    struct TestArchive {
+        dir: PathBuf,
+        path: PathBuf,
+    }
+
+    impl TestArchive {
+        /// `entries` are `(name, contents)`, stored uncompressed unless `deflate` is set
+        fn new(test_name: &str, entries: &[(&str, &[u8])], deflate: bool) -> Self {
+            let dir = std::env::temp_dir().join(format!(
+                "get-crap-done-zip-{test_name}-{}-{}",
+                std::process::id(),
+                NEXT_TEST_DIR.fetch_add(1, Ordering::Relaxed)
+            ));
+            fs::create_dir_all(&dir).unwrap();
+            let path = dir.join("photos.zip");
+            let mut zip = ZipWriter::new(File::create(&path).unwrap());
+            let method = if deflate {
+                CompressionMethod::Deflated
+            } else {
+                CompressionMethod::Stored
+            };
+            let options = SimpleFileOptions::default()
+                .compression_method(method)
+                .large_file(true);
+            for (name, contents) in entries {
+                zip.start_file(*name, options).unwrap();
+                zip.write_all(contents).unwrap();
+            }
+            zip.finish().unwrap();
+            Self { dir, path }
+        }
+
+        fn extract(&self, limits: &ExtractLimits) -> AppResult<Option<ExtractedArchive>> {
+            extract_images_within(
+                &self.path,
+                &self.dir.join("extracted"),
+                limits,
+                &CancellationToken::new(),
+                |_| {},
+            )
+        }
+
+        fn extracted_names(extracted: &ExtractedArchive) -> Vec<&str> {
+            extracted
+                .images
+                .iter()
+                .map(|image| image.source_path.as_str())
+                .collect()
+        }
+
+        fn skipped(extracted: &ExtractedArchive) -> Vec<(&str, &str)> {
+            extracted
+                .skipped
+                .iter()
+                .map(|s| (s.source_path.as_str(), s.reason.as_str()))
+                .collect()
+        }
+    }
+
+    impl Drop for TestArchive {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    #[test]
+    fn rejects_entries_outside_the_archive() {
+        let archive = TestArchive::new(
+            "unsafe",
+            &[
+                ("../evil.jpg", b"evil"),
+                ("/absolute.jpg", b"absolute"),
+                ("photo.jpg", b"photo"),
+            ],
+            false,
+        );
+
+        let extracted = archive.extract(&LIMITS).unwrap().unwrap();
+
+        assert_eq!(
+            TestArchive::extracted_names(&extracted),
+            vec!["photos.zip/photo.jpg"]
+        );
+        assert_eq!(
+            TestArchive::skipped(&extracted),
+            vec![
+                ("photos.zip/../evil.jpg", "Unsafe path in archive"),
+                ("photos.zip//absolute.jpg", "Unsafe path in archive"),
+            ]
+        );
+        let written = &extracted.images[0].path;
+        assert!(written.starts_with(archive.dir.join("extracted")));
+        assert_eq!(fs::read(written).unwrap(), b"photo");
+    }
+
+    #[test]
+    fn skips_hidden_entries_and_other_files() {
+        let archive = TestArchive::new(
+            "hidden",
+            &[
+                (".hidden.jpg", b"hidden"),
+                ("__MACOSX/._photo.jpg", b"resource fork"),
+                ("trip/.thumbs/photo.jpg", b"thumbnail"),
+                ("trip/notes.txt", b"notes"),
+                ("trip/photo.jpg", b"photo"),
+            ],
+            false,
+        );
+
+        let extracted = archive.extract(&LIMITS).unwrap().unwrap();
+
+        assert_eq!(
+            TestArchive::extracted_names(&extracted),
+            vec!["photos.zip/trip/photo.jpg"]
+        );
+        assert!(extracted.skipped.is_empty());
+    }
+
+    #[test]
+    fn skips_entries_over_the_size_limit() {
+        let limits = ExtractLimits {
+            max_entry_bytes: 1_000,
+            ..LIMITS
+        };
+        let archive = TestArchive::new(
+            "entry-limit",
+            &[("big.jpg", &[7; 2_000]), ("small.jpg", &[7; 500])],
+            false,
+        );
+
+        let extracted = archive.extract(&limits).unwrap().unwrap();
+
+        assert_eq!(
+            TestArchive::extracted_names(&extracted),
+            vec!["photos.zip/small.jpg"]
+        );
+        assert_eq!(
+            TestArchive::skipped(&extracted),
+            vec![("photos.zip/big.jpg", "Larger than 1000 bytes")]
+        );
+    }
+
+    #[test]
+    fn skips_entries_that_compress_suspiciously_well() {
+        let zeros = vec![0; 2 * 1024 * 1024];
+        let archive = TestArchive::new(
+            "ratio",
+            &[("bomb.jpg", &zeros), ("small.jpg", &[0; 1_000])],
+            true,
+        );
+
+        let extracted = archive.extract(&LIMITS).unwrap().unwrap();
+
+        assert_eq!(
+            TestArchive::extracted_names(&extracted),
+            vec!["photos.zip/small.jpg"]
+        );
+        assert_eq!(
+            TestArchive::skipped(&extracted),
+            vec![(
+                "photos.zip/bomb.jpg",
+                "Compressed suspiciously well, possibly a zip bomb"
+            )]
+        );
+    }
+
+    #[test]
+    fn fails_when_the_archive_expands_too_far() {
+        let limits = ExtractLimits {
+            max_total_bytes: 2_500,
+            ..LIMITS
+        };
+        let archive = TestArchive::new(
+            "total-limit",
+            &[
+                ("a.jpg", &[1; 1_000]),
+                ("b.jpg", &[2; 1_000]),
+                ("c.jpg", &[3; 1_000]),
+            ],
+            false,
+        );
+
+        assert!(matches!(archive.extract(&limits), Err(AppError::Decode(_))));
+    }
+
+    #[test]
+    fn reports_every_entry_and_stops_when_cancelled() {
+        let archive = TestArchive::new(
+            "progress",
+            &[("a.jpg", b"a"), ("notes.txt", b"notes"), ("b.jpg", b"b")],
+            false,
+        );
+        let cancel = CancellationToken::new();
+        let mut reported = Vec::new();
+
+        let extracted = extract_images(
+            &archive.path,
+            &archive.dir.join("extracted"),
+            &cancel,
+            |progress| reported.push((progress.index, progress.total, progress.entry_name)),
+        )
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(extracted.images.len(), 2);
+        assert_eq!(
+            reported,
+            vec![
+                (0, 3, "a.jpg".to_string()),
+                (1, 3, "notes.txt".to_string()),
+                (2, 3, "b.jpg".to_string()),
+            ]
+        );
+
+        cancel.cancel();
+        let cancelled = extract_images(
+            &archive.path,
+            &archive.dir.join("cancelled"),
+            &cancel,
+            |_| {},
+        )
+        .unwrap();
+        assert!(cancelled.is_none());
+        assert!(!archive.dir.join("cancelled").exists());
+    }
+}
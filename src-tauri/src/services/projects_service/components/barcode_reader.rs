@@ -0,0 +1,161 @@
+use std::{collections::HashMap, path::Path, sync::OnceLock};
+
+use regex::Regex;
+
+use crate::{
+    app_error::AppResult,
+    services::projects_service::{
+        components::offline_ocr::serial_number_regex, models::DecodedBarcode,
+    },
+};
+
+/// Serial numbers passed as a query parameter, e.g. `https://example.com/unit?sn=AB1234`
+fn serial_query_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"(?i)[?&](?:sn|serial|serial_?number)=([A-Z0-9-]{4,})").unwrap())
+}
+
+/// A payload that is nothing but an identifier, the usual content of a 1D barcode
+fn bare_identifier_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"^[A-Z0-9-]*[0-9][A-Z0-9-]*$").unwrap())
+}
+
+/// Decodes the barcodes in every image, keyed by path. Images that fail to decode are logged and
+/// left out.
+pub async fn decode_barcodes_in_images(
+    image_paths: &[String],
+) -> HashMap<String, Vec<DecodedBarcode>> {
+    let image_paths = image_paths.to_vec();
+    let result = tokio::task::spawn_blocking(move || {
+        image_paths
+            .into_iter()
+            .filter_map(|path| match decode_barcodes(Path::new(&path)) {
+                Ok(barcodes) => Some((path, barcodes)),
+                Err(e) => {
+                    log::warn!("Failed to scan {path} for barcodes: {e}");
+                    None
+                }
+            })
+            .collect()
+    })
+    .await;
+    result.unwrap_or_else(|e| {
+        log::warn!("Failed to scan for barcodes: {e}");
+        HashMap::new()
+    })
+}
+
+/// The serial number encoded in the first barcode that holds one.
+/// Accepts labeled serials (`S/N: AB1234`), URL parameters (`?sn=AB1234`) and payloads that are
+/// a bare identifier, unless the barcode is a retail product code.
+pub fn serial_from_barcodes(barcodes: &[DecodedBarcode]) -> Option<String> {
+    barcodes.iter().find_map(|barcode| {
+        let payload = barcode.payload.trim();
+        if let Some(caps) = serial_query_regex().captures(payload) {
+            return Some(caps[1].to_uppercase());
+        }
+        let upper = payload.to_uppercase();
+        if let Some(caps) = serial_number_regex().captures(&upper) {
+            return Some(caps[1].to_string());
+        }
+        let is_identifier = upper.len() >= 4 && bare_identifier_regex().is_match(&upper);
+        (is_identifier && !is_product_code(&barcode.format)).then_some(upper)
+    })
+}
+
+/// EAN and UPC barcodes hold the product's retail number, which is the same for every unit
+fn is_product_code(format: &str) -> bool {
+    let format = format.to_lowercase();
+    format.starts_with("ean") || format.starts_with("upc")
+}
+
+/// Finds and decodes every QR code and 1D barcode in the image
+#[cfg(feature = "barcodes")]
+pub fn decode_barcodes(path: &Path) -> AppResult<Vec<DecodedBarcode>> {
+    use crate::app_error::AppError;
+
+    let image = image::open(path)
+        .map_err(|e| AppError::Decode(format!("Failed to decode {}: {e}", path.display())))?
+        .into_luma8();
+    let (width, height) = image.dimensions();
+    match rxing::helpers::detect_multiple_in_luma(image.into_raw(), width, height) {
+        Ok(results) => Ok(results
+            .into_iter()
+            .map(|result| DecodedBarcode {
+                format: result.getBarcodeFormat().to_string(),
+                payload: result.getText().to_string(),
+            })
+            .collect()),
+        // Raised when the image simply has no barcode in it
+        Err(rxing::Exceptions::NotFoundException(_)) => Ok(Vec::new()),
+        Err(e) => Err(AppError::Decode(format!(
+            "Failed to read barcodes in {}: {e}",
+            path.display()
+        ))),
+    }
+}
+
+#[cfg(not(feature = "barcodes"))]
+pub fn decode_barcodes(_path: &Path) -> AppResult<Vec<DecodedBarcode>> {
+    Ok(Vec::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn barcode(format: &str, payload: &str) -> DecodedBarcode {
+        DecodedBarcode {
+            format: format.to_string(),
+            payload: payload.to_string(),
+        }
+    }
+
+    #[test]
+    fn reads_labeled_and_url_serials() {
+        assert_eq!(
+            serial_from_barcodes(&[barcode("qrcode", "S/N: ab1234")]).as_deref(),
+            Some("AB1234")
+        );
+        assert_eq!(
+            serial_from_barcodes(&[barcode("qrcode", "https://example.com/unit?sn=ab1234")])
+                .as_deref(),
+            Some("AB1234")
+        );
+    }
+
+    #[test]
+    fn reads_bare_identifiers_except_product_codes() {
+        assert_eq!(
+            serial_from_barcodes(&[barcode("code 128", "2419G20345")]).as_deref(),
+            Some("2419G20345")
+        );
+        for format in ["ean 13", "ean 8", "upc a", "upc e"] {
+            assert_eq!(
+                serial_from_barcodes(&[barcode(format, "4006381333931")]),
+                None,
+                "{format} should be skipped"
+            );
+        }
+        // A product code doesn't hide a serial in a later barcode
+        assert_eq!(
+            serial_from_barcodes(&[
+                barcode("ean 13", "4006381333931"),
+                barcode("code 39", "SN-99812"),
+            ])
+            .as_deref(),
+            Some("SN-99812")
+        );
+    }
+
+    #[test]
+    fn ignores_payloads_without_an_identifier() {
+        assert_eq!(
+            serial_from_barcodes(&[barcode("qrcode", "https://example.com/manual")]),
+            None
+        );
+        assert_eq!(serial_from_barcodes(&[barcode("code 128", "ABCDEF")]), None);
+        assert_eq!(serial_from_barcodes(&[barcode("code 128", "123")]), None);
+    }
+}
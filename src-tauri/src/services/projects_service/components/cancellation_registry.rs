@@ -0,0 +1,65 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use tokio_util::sync::CancellationToken;
+
+/// A token handed out by `CancellationRegistryComponent::register`
+pub struct RegisteredToken {
+    pub token: CancellationToken,
+    generation: u64,
+}
+
+#[derive(Default)]
+struct Registry {
+    tokens: HashMap<String, (u64, CancellationToken)>,
+    next_generation: u64,
+}
+
+/// Tracks long running operations the frontend can cancel, keyed by an id the frontend picks
+#[derive(Default)]
+pub struct CancellationRegistryComponent {
+    registry: Mutex<Registry>,
+}
+
+impl CancellationRegistryComponent {
+    /// Returns a fresh token for `id`, cancelling any operation still registered under it
+    pub fn register(&self, id: &str) -> RegisteredToken {
+        let token = CancellationToken::new();
+        let mut registry = self.lock_registry();
+        let generation = registry.next_generation;
+        registry.next_generation += 1;
+        if let Some((_, old)) = registry
+            .tokens
+            .insert(id.to_string(), (generation, token.clone()))
+        {
+            old.cancel();
+        }
+        RegisteredToken { token, generation }
+    }
+
+    /// Returns false if nothing is registered under `id`, e.g. because it already finished
+    pub fn cancel(&self, id: &str) -> bool {
+        match self.lock_registry().tokens.get(id) {
+            Some((_, token)) => {
+                token.cancel();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Forgets `id` once its operation is done, unless a newer one took its place
+    pub fn finish(&self, id: &str, registered: &RegisteredToken) {
+        let mut registry = self.lock_registry();
+        if registry
+            .tokens
+            .get(id)
+            .is_some_and(|(generation, _)| *generation == registered.generation)
+        {
+            registry.tokens.remove(id);
+        }
+    }
+
+    fn lock_registry(&self) -> std::sync::MutexGuard<'_, Registry> {
+        self.registry.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,531 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+    time::Duration,
+};
+
+use base64::Engine;
+use futures::{future::BoxFuture, StreamExt};
+use ocr_image_thing::{ImageEvalClient, ImageEvalOutcome, ImageEvaluationResult};
+use regex::Regex;
+use tokio::sync::OnceCell;
+
+use crate::{
+    app_error::{AppError, AppResult},
+    services::projects_service::{
+        components::offline_ocr::OfflineOcrProvider, models::EvaluationProviderConfig,
+    },
+};
+
+/// Local models on a laptop can take minutes for a single image
+const COMPATIBLE_REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+const DEFAULT_PROMPT: &str = "Analyze this image of mechanical equipment.\n\n\
+1. Identify any unit tags, serial numbers, model numbers, or identifying labels visible in the image.\n\
+2. Determine the equipment type (e.g. HVAC unit, pump, compressor, boiler, electrical panel).\n\
+3. Note any other important identifying information such as manufacturer, capacity ratings, or installation details.\n\n\
+For the filepath_suffix, use the most specific identifier you find (e.g. \"_UNIT_123\", \"_SERIAL_ABC456\", \"_MODEL_XYZ789\"). Prefer unit tags over serial numbers, and serial numbers over model numbers.\n\
+For the brief_description, state the equipment type and mention the identifier you used for the suffix.";
+
+/// Sent as the system message so any model answers in a shape we can parse
+const RESPONSE_FORMAT_INSTRUCTIONS: &str = "Respond with only a JSON object of the form \
+{\"brief_description\": string, \"filepath_suffix\": string or null}. \
+Use null for the filepath_suffix if no identifier is visible.";
+
+/// Why a provider couldn't evaluate an image, along with what the server's response said about
+/// it, so failures can be told apart without reading the message
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct EvaluationFailure {
+    /// Shown to the user
+    pub reason: String,
+    /// HTTP status of the failed response
+    pub status: Option<u16>,
+    /// Error code from the response body, e.g. `insufficient_quota`
+    pub code: Option<String>,
+    /// The wait the server asked for with `Retry-After`
+    pub retry_after: Option<Duration>,
+    /// The request was sent but no response came back, e.g. the connection was refused or timed out
+    pub no_response: bool,
+}
+
+impl EvaluationFailure {
+    /// A failure that has nothing to do with a server response, e.g. an unreadable image
+    pub fn other(reason: impl Into<String>) -> Self {
+        Self {
+            reason: reason.into(),
+            ..Default::default()
+        }
+    }
+
+    /// A rejected key or an exhausted quota, which fails every request made with the account
+    pub fn is_account_failure(&self) -> bool {
+        matches!(self.status, Some(401 | 403)) || self.code.as_deref() == Some("insufficient_quota")
+    }
+}
+
+/// Classifies a failure by the status and code the provider reported. The message isn't read,
+/// image names and other details in it could look like a status.
+impl From<&EvaluationFailure> for AppError {
+    fn from(failure: &EvaluationFailure) -> Self {
+        let reason = failure.reason.clone();
+        match (failure.status, failure.code.as_deref()) {
+            (Some(401 | 403), _) | (_, Some("invalid_api_key")) => Self::Auth(reason),
+            (Some(429), _) | (_, Some("insufficient_quota" | "rate_limit_exceeded")) => {
+                Self::RateLimited(reason)
+            }
+            _ => Self::Evaluation(reason),
+        }
+    }
+}
+
+/// What a provider made of one image
+pub type ProviderResult = Result<ImageEvaluationResult, EvaluationFailure>;
+
+/// Evaluates images with some model. Each request picks its provider, so implementations are
+/// used as trait objects.
+pub trait EvaluationProvider: Send + Sync {
+    /// Returns one result per image path, in the same order
+    fn evaluate_images(
+        &self,
+        image_paths: Vec<String>,
+        custom_prompt: Option<String>,
+        temperature: Option<f32>,
+    ) -> BoxFuture<'_, Vec<ProviderResult>>;
+}
+
+/// `try again in 1.5s` or `in 250ms`, as OpenAI words the wait in rate limit errors
+fn openai_retry_after_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| Regex::new(r"try again in (\d+(?:\.\d+)?)(ms|s)\b").unwrap())
+}
+
+/// Builds the provider described by `config`. `openai_api_key` is only used by OpenAI and
+/// `ocr_models_dir` only by offline OCR.
+pub fn provider_from_config(
+    config: EvaluationProviderConfig,
+    openai_api_key: String,
+    ocr_models_dir: PathBuf,
+) -> AppResult<Box<dyn EvaluationProvider>> {
+    Ok(match config {
+        EvaluationProviderConfig::OpenAi => Box::new(OpenAiProvider::new(openai_api_key)),
+        EvaluationProviderConfig::OpenAiCompatible {
+            base_url,
+            model,
+            api_key,
+        } => Box::new(OpenAiCompatibleProvider::new(base_url, model, api_key)),
+        EvaluationProviderConfig::OfflineOcr => Box::new(OfflineOcrProvider::new(ocr_models_dir)?),
+        EvaluationProviderConfig::Mock => Box::new(MockProvider),
+    })
+}
+
+/// OpenAI through `ocr_image_thing`
+pub struct OpenAiProvider {
+    client: ImageEvalClient,
+    api_key: String,
+    /// Set once the key is handed to the client, which happens before the first request
+    key_set: OnceCell<()>,
+}
+
+impl OpenAiProvider {
+    pub fn new(api_key: String) -> Self {
+        Self {
+            client: ImageEvalClient::new(),
+            api_key,
+            key_set: OnceCell::new(),
+        }
+    }
+
+    /// `ocr_image_thing` only passes on OpenAI's error message, so the status is recovered from
+    /// the codes OpenAI puts in it for a bad key, a rate limit and an exhausted quota
+    fn failure(reason: String) -> EvaluationFailure {
+        let lower = reason.to_lowercase();
+        let (status, code) = if lower.contains("insufficient_quota") {
+            (Some(429), "insufficient_quota")
+        } else if lower.contains("rate_limit_exceeded") || lower.contains("rate limit reached") {
+            (Some(429), "rate_limit_exceeded")
+        } else if lower.contains("invalid_api_key") || lower.contains("incorrect api key") {
+            (Some(401), "invalid_api_key")
+        } else {
+            return EvaluationFailure::other(reason);
+        };
+        let retry_after = openai_retry_after_regex()
+            .captures(&lower)
+            .and_then(|caps| {
+                let value: f64 = caps[1].parse().ok()?;
+                let seconds = if &caps[2] == "ms" {
+                    value / 1000.0
+                } else {
+                    value
+                };
+                Duration::try_from_secs_f64(seconds).ok()
+            });
+        EvaluationFailure {
+            reason,
+            status,
+            code: Some(code.to_string()),
+            retry_after,
+            no_response: false,
+        }
+    }
+}
+
+impl EvaluationProvider for OpenAiProvider {
+    fn evaluate_images(
+        &self,
+        image_paths: Vec<String>,
+        custom_prompt: Option<String>,
+        temperature: Option<f32>,
+    ) -> BoxFuture<'_, Vec<ProviderResult>> {
+        Box::pin(async move {
+            self.key_set
+                .get_or_init(|| self.client.set_api_key(&self.api_key))
+                .await;
+            let mut outcomes: HashMap<String, ImageEvalOutcome> = self
+                .client
+                .evaluate_images(image_paths.clone(), custom_prompt, temperature)
+                .await
+                .into_iter()
+                .map(|outcome| (outcome.full_image_path.clone(), outcome))
+                .collect();
+            image_paths
+                .iter()
+                .map(|path| match outcomes.remove(path) {
+                    Some(ImageEvalOutcome {
+                        success_result: Some(result),
+                        ..
+                    }) => Ok(result),
+                    Some(outcome) => Err(Self::failure(outcome.failure_result.unwrap_or_default())),
+                    None => Err(EvaluationFailure::other("The evaluator returned no result")),
+                })
+                .collect()
+        })
+    }
+}
+
+/// Any OpenAI compatible chat completions endpoint that accepts images.
+/// Talks HTTP directly so failures keep their status code and `Retry-After` header, which
+/// [`EvaluationRetryComponent`](super::evaluation_retry::EvaluationRetryComponent) goes by.
+pub struct OpenAiCompatibleProvider {
+    http: reqwest::Client,
+    completions_url: String,
+    model: String,
+    api_key: Option<String>,
+}
+
+impl OpenAiCompatibleProvider {
+    pub fn new(base_url: String, model: String, api_key: Option<String>) -> Self {
+        let http = reqwest::Client::builder()
+            .timeout(COMPATIBLE_REQUEST_TIMEOUT)
+            .build()
+            .unwrap_or_default();
+        Self {
+            http,
+            completions_url: format!("{}/chat/completions", base_url.trim_end_matches('/')),
+            model,
+            api_key: api_key.filter(|key| !key.is_empty()),
+        }
+    }
+
+    async fn evaluate_image(
+        &self,
+        image_path: &str,
+        prompt: &str,
+        temperature: Option<f32>,
+    ) -> ProviderResult {
+        #[derive(serde::Deserialize)]
+        struct ChatCompletion {
+            choices: Vec<Choice>,
+        }
+        #[derive(serde::Deserialize)]
+        struct Choice {
+            message: Message,
+        }
+        #[derive(serde::Deserialize)]
+        struct Message {
+            content: Option<String>,
+        }
+
+        let image_url = Self::data_url(Path::new(image_path))
+            .await
+            .map_err(|e| EvaluationFailure::other(format!("Failed to read {image_path}: {e}")))?;
+        let mut body = serde_json::json!({
+            "model": self.model,
+            "messages": [
+                { "role": "system", "content": RESPONSE_FORMAT_INSTRUCTIONS },
+                {
+                    "role": "user",
+                    "content": [
+                        { "type": "text", "text": prompt },
+                        { "type": "image_url", "image_url": { "url": image_url } },
+                    ],
+                },
+            ],
+        });
+        if let Some(temperature) = temperature {
+            body["temperature"] = temperature.into();
+        }
+
+        let mut request = self.http.post(&self.completions_url).json(&body);
+        if let Some(api_key) = &self.api_key {
+            request = request.bearer_auth(api_key);
+        }
+        let response = request.send().await.map_err(|e| EvaluationFailure {
+            reason: format!("Request to {} failed: {e}", self.completions_url),
+            no_response: true,
+            ..Default::default()
+        })?;
+        if !response.status().is_success() {
+            return Err(Self::failure(response).await);
+        }
+        let completion: ChatCompletion = response.json().await.map_err(|e| {
+            EvaluationFailure::other(format!("Failed to read the model response: {e}"))
+        })?;
+        let output = completion
+            .choices
+            .into_iter()
+            .next()
+            .and_then(|choice| choice.message.content)
+            .ok_or_else(|| EvaluationFailure::other("The model returned an empty response"))?;
+        Self::parse_output(output, image_path).map_err(EvaluationFailure::other)
+    }
+
+    /// Describes a failed response as `HTTP <status>: <message>`, keeping its status, error code
+    /// and `Retry-After` wait
+    async fn failure(response: reqwest::Response) -> EvaluationFailure {
+        let status = response.status();
+        let retry_after = Self::retry_after(response.headers());
+        let body = response.text().await.unwrap_or_default();
+        // OpenAI style servers wrap the error as `{"error": {"message": "...", "code": "..."}}`
+        let error = serde_json::from_str::<serde_json::Value>(&body)
+            .ok()
+            .map(|mut json| json["error"].take());
+        let field = |name: &str| {
+            error
+                .as_ref()
+                .and_then(|error| error[name].as_str())
+                .map(str::to_string)
+        };
+        let message = field("message").unwrap_or(body);
+        EvaluationFailure {
+            reason: format!("HTTP {status}: {message}"),
+            status: Some(status.as_u16()),
+            code: field("code").or_else(|| field("type")),
+            retry_after,
+            no_response: false,
+        }
+    }
+
+    /// Reads `Retry-After` in seconds, or OpenAI's `retry-after-ms`. A wait given as an HTTP date
+    /// is ignored and backoff is used instead.
+    fn retry_after(headers: &reqwest::header::HeaderMap) -> Option<Duration> {
+        let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+        if let Some(ms) = header("retry-after-ms").and_then(|ms| ms.trim().parse::<f64>().ok()) {
+            return Duration::try_from_secs_f64(ms / 1000.0).ok();
+        }
+        let seconds = header(reqwest::header::RETRY_AFTER.as_str())?
+            .trim()
+            .parse::<f64>()
+            .ok()?;
+        Duration::try_from_secs_f64(seconds).ok()
+    }
+
+    /// Encodes the image inline, since a local server can't fetch files by path
+    async fn data_url(path: &Path) -> std::io::Result<String> {
+        let bytes = tokio::fs::read(path).await?;
+        let mime = image::ImageFormat::from_path(path)
+            .map(|format| format.to_mime_type())
+            .unwrap_or("image/jpeg");
+        let encoded = base64::engine::general_purpose::STANDARD.encode(bytes);
+        Ok(format!("data:{mime};base64,{encoded}"))
+    }
+
+    /// Reads the JSON object out of the model output. Smaller models often wrap it in a code
+    /// fence or add a sentence around it.
+    fn parse_output(output: String, image_path: &str) -> Result<ImageEvaluationResult, String> {
+        #[derive(serde::Deserialize)]
+        struct ModelReply {
+            brief_description: String,
+            #[serde(default)]
+            filepath_suffix: Option<String>,
+        }
+
+        let json = match (output.find('{'), output.rfind('}')) {
+            (Some(start), Some(end)) if start < end => &output[start..=end],
+            _ => return Err(format!("The model did not return JSON: {output}")),
+        };
+        let reply: ModelReply = serde_json::from_str(json)
+            .map_err(|e| format!("Failed to parse the model response: {e}"))?;
+        let suffix = reply
+            .filepath_suffix
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+        Ok(ImageEvaluationResult {
+            full_model_output: output,
+            new_suggested_filepath_suffix: suffix,
+            brief_description: reply.brief_description,
+            original_image_path: image_path.to_string(),
+        })
+    }
+}
+
+impl EvaluationProvider for OpenAiCompatibleProvider {
+    fn evaluate_images(
+        &self,
+        image_paths: Vec<String>,
+        custom_prompt: Option<String>,
+        temperature: Option<f32>,
+    ) -> BoxFuture<'_, Vec<ProviderResult>> {
+        Box::pin(async move {
+            let prompt = custom_prompt
+                .filter(|p| !p.trim().is_empty())
+                .unwrap_or_else(|| DEFAULT_PROMPT.to_string());
+            let prompt = prompt.as_str();
+            // One request at a time, the caller decides how many images are evaluated at once
+            futures::stream::iter(&image_paths)
+                .then(|path| self.evaluate_image(path, prompt, temperature))
+                .collect()
+                .await
+        })
+    }
+}
+
+/// Makes up a result from each file name, so evaluations can be exercised without a model
+pub struct MockProvider;
+
+impl MockProvider {
+    fn evaluate_image(image_path: String) -> ProviderResult {
+        let stem = Path::new(&image_path)
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let identifier = stem
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .collect::<String>()
+            .to_uppercase();
+        let suffix = (!identifier.is_empty()).then(|| format!("_MOCK_{identifier}"));
+        let brief_description = format!("Mock evaluation of {stem}");
+        Ok(ImageEvaluationResult {
+            full_model_output: brief_description.clone(),
+            new_suggested_filepath_suffix: suffix,
+            brief_description,
+            original_image_path: image_path,
+        })
+    }
+}
+
+impl EvaluationProvider for MockProvider {
+    fn evaluate_images(
+        &self,
+        image_paths: Vec<String>,
+        _custom_prompt: Option<String>,
+        _temperature: Option<f32>,
+    ) -> BoxFuture<'_, Vec<ProviderResult>> {
+        Box::pin(async move { image_paths.into_iter().map(Self::evaluate_image).collect() })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use reqwest::header::{HeaderMap, HeaderValue};
+
+    use super::*;
+
+    #[test]
+    fn reads_openai_rate_limits_from_their_code() {
+        let failure = OpenAiProvider::failure(
+            "Rate limit reached for gpt-4o (rate_limit_exceeded). Please try again in 1.5s."
+                .to_string(),
+        );
+        assert_eq!(failure.status, Some(429));
+        assert_eq!(failure.code.as_deref(), Some("rate_limit_exceeded"));
+        assert_eq!(failure.retry_after, Some(Duration::from_millis(1_500)));
+
+        let failure = OpenAiProvider::failure(
+            "You exceeded your current quota (insufficient_quota)".to_string(),
+        );
+        assert_eq!(failure.status, Some(429));
+        assert_eq!(failure.code.as_deref(), Some("insufficient_quota"));
+
+        let failure = OpenAiProvider::failure("Incorrect API key provided".to_string());
+        assert_eq!(failure.status, Some(401));
+    }
+
+    #[test]
+    fn leaves_other_openai_failures_without_a_status() {
+        let reason = "Failed to decode image 429.jpg, try again in 5s".to_string();
+        assert_eq!(
+            OpenAiProvider::failure(reason.clone()),
+            EvaluationFailure::other(reason)
+        );
+    }
+
+    #[test]
+    fn classifies_failures_by_status_and_code() {
+        let failure = |status, code: Option<&str>| EvaluationFailure {
+            reason: "failed".to_string(),
+            status,
+            code: code.map(str::to_string),
+            ..Default::default()
+        };
+        assert!(matches!(
+            AppError::from(&failure(Some(401), None)),
+            AppError::Auth(_)
+        ));
+        assert!(matches!(
+            AppError::from(&failure(Some(403), None)),
+            AppError::Auth(_)
+        ));
+        assert!(matches!(
+            AppError::from(&failure(Some(429), Some("rate_limit_exceeded"))),
+            AppError::RateLimited(_)
+        ));
+        assert!(matches!(
+            AppError::from(&failure(Some(429), Some("insufficient_quota"))),
+            AppError::RateLimited(_)
+        ));
+        assert!(matches!(
+            AppError::from(&failure(Some(500), None)),
+            AppError::Evaluation(_)
+        ));
+    }
+
+    #[test]
+    fn ignores_status_like_numbers_in_the_message() {
+        for reason in [
+            "Failed to decode IMG_1429.jpg",
+            "Failed to decode IMG_1401.jpg",
+        ] {
+            assert!(matches!(
+                AppError::from(&EvaluationFailure::other(reason)),
+                AppError::Evaluation(_)
+            ));
+        }
+    }
+
+    #[test]
+    fn reads_retry_after_headers() {
+        let headers = |pairs: &[(&'static str, &'static str)]| {
+            let mut headers = HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(*name, HeaderValue::from_static(value));
+            }
+            headers
+        };
+        let retry_after = |pairs| OpenAiCompatibleProvider::retry_after(&headers(pairs));
+        assert_eq!(
+            retry_after(&[("retry-after", "20")]),
+            Some(Duration::from_secs(20))
+        );
+        assert_eq!(
+            retry_after(&[("retry-after", "20"), ("retry-after-ms", "250")]),
+            Some(Duration::from_millis(250))
+        );
+        assert_eq!(
+            retry_after(&[("retry-after", "Wed, 21 Oct 2015 07:28:00 GMT")]),
+            None
+        );
+        assert_eq!(retry_after(&[]), None);
+    }
+}
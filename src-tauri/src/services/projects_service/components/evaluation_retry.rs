@@ -0,0 +1,458 @@
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    sync::{Arc, Mutex, MutexGuard},
+    time::Duration,
+};
+
+use tokio::sync::{Semaphore, SemaphorePermit};
+use tokio_util::sync::CancellationToken;
+
+use crate::services::projects_service::{
+    components::evaluation_providers::{EvaluationFailure, EvaluationProvider, ProviderResult},
+    models::RetryPolicy,
+};
+
+/// Whether waiting and trying again could fix a failed evaluation
+#[derive(Debug, PartialEq)]
+enum FailureKind {
+    Permanent,
+    /// Rate limits and server errors. Carries the wait the provider asked for, if it did.
+    Transient {
+        retry_after: Option<Duration>,
+    },
+}
+
+/// HTTP statuses worth retrying: request timeouts, rate limits and server errors
+const TRANSIENT_STATUSES: [u16; 6] = [408, 429, 500, 502, 503, 504];
+
+impl FailureKind {
+    /// Goes by the status of the failed response, or retries if none came back
+    fn of(failure: &EvaluationFailure) -> Self {
+        // Running out of credit is reported as a 429 too, but waiting won't fix it
+        if failure.code.as_deref() == Some("insufficient_quota") {
+            return Self::Permanent;
+        }
+        let transient = failure.no_response
+            || failure
+                .status
+                .is_some_and(|status| TRANSIENT_STATUSES.contains(&status));
+        if transient {
+            Self::Transient {
+                retry_after: failure.retry_after,
+            }
+        } else {
+            Self::Permanent
+        }
+    }
+}
+
+struct RetryState {
+    policy: RetryPolicy,
+    /// Shared by every evaluation so the limit holds across projects
+    in_flight: Arc<Semaphore>,
+}
+
+/// Retries evaluations that failed because of a rate limit or a server error, and caps how many
+/// images are being evaluated at once
+pub struct EvaluationRetryComponent {
+    state: Mutex<RetryState>,
+}
+
+impl EvaluationRetryComponent {
+    pub fn new(policy: RetryPolicy) -> Self {
+        Self {
+            state: Mutex::new(Self::state_for(policy)),
+        }
+    }
+
+    fn state_for(policy: RetryPolicy) -> RetryState {
+        RetryState {
+            in_flight: Arc::new(Semaphore::new(policy.max_concurrent_requests.max(1))),
+            policy,
+        }
+    }
+
+    fn lock_state(&self) -> MutexGuard<'_, RetryState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn policy(&self) -> RetryPolicy {
+        self.lock_state().policy.clone()
+    }
+
+    /// Evaluations already running keep the policy they started with
+    pub fn set_policy(&self, policy: RetryPolicy) {
+        *self.lock_state() = Self::state_for(policy);
+    }
+
+    /// Takes the policy and request limit for an evaluation run
+    pub fn session(&self) -> RetrySession {
+        let state = self.lock_state();
+        RetrySession {
+            policy: state.policy.clone(),
+            in_flight: state.in_flight.clone(),
+        }
+    }
+}
+
+/// The retry policy and request limit in effect when an evaluation run started
+pub struct RetrySession {
+    policy: RetryPolicy,
+    in_flight: Arc<Semaphore>,
+}
+
+impl RetrySession {
+    /// Waits for one of the request slots, for work that should be throttled like requests
+    pub async fn request_slot(&self) -> Option<SemaphorePermit<'_>> {
+        self.in_flight.acquire().await.ok()
+    }
+
+    /// Evaluates one image, retrying it on its own. `on_started` is called once the first request
+    /// gets through the limit. Cancelling stops waiting for a retry and keeps the last failure.
+    /// Returns the result, with the status and code of the last failure, and how often the image
+    /// was retried.
+    pub async fn evaluate_image(
+        &self,
+        provider: &dyn EvaluationProvider,
+        image_path: String,
+        custom_prompt: Option<&str>,
+        temperature: Option<f32>,
+        cancel: &CancellationToken,
+        on_started: impl FnOnce(),
+    ) -> (ProviderResult, u32) {
+        let mut on_started = Some(on_started);
+        let mut retries = 0;
+        loop {
+            // The permit is only held during the request, not while backing off
+            let result = {
+                let _permit = self.in_flight.acquire().await;
+                if let Some(on_started) = on_started.take() {
+                    on_started();
+                }
+                provider
+                    .evaluate_images(
+                        vec![image_path.clone()],
+                        custom_prompt.map(str::to_string),
+                        temperature,
+                    )
+                    .await
+                    .into_iter()
+                    .next()
+                    .unwrap_or_else(|| {
+                        Err(EvaluationFailure::other("The evaluator returned no result"))
+                    })
+            };
+            let failure = match &result {
+                Ok(_) => return (result, retries),
+                Err(failure) => failure,
+            };
+            let FailureKind::Transient { retry_after } = FailureKind::of(failure) else {
+                return (result, retries);
+            };
+            if retries >= self.policy.max_retries {
+                return (result, retries);
+            }
+            let delay = Self::backoff_delay(&self.policy, retries, retry_after);
+            log::info!(
+                "Retrying {image_path} in {delay:?} after: {}",
+                failure.reason
+            );
+            tokio::select! {
+                _ = cancel.cancelled() => return (result, retries),
+                _ = tokio::time::sleep(delay) => {}
+            }
+            retries += 1;
+        }
+    }
+
+    /// The wait the provider asked for, or exponential backoff with jitter otherwise.
+    /// Jitter keeps images that failed together from retrying together.
+    fn backoff_delay(
+        policy: &RetryPolicy,
+        retries: u32,
+        retry_after: Option<Duration>,
+    ) -> Duration {
+        let max_backoff = Duration::from_millis(policy.max_backoff_ms);
+        if let Some(retry_after) = retry_after {
+            return retry_after.min(max_backoff);
+        }
+        let backoff_ms = policy
+            .initial_backoff_ms
+            .saturating_mul(1 << retries.min(20))
+            .min(policy.max_backoff_ms);
+        let jitter_ms = random_u64() % (backoff_ms / 2 + 1);
+        Duration::from_millis(backoff_ms / 2 + jitter_ms)
+    }
+}
+
+/// Good enough randomness for jitter without pulling in a dependency
+fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        path::PathBuf,
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Instant,
+    };
+
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::*;
+    use crate::services::projects_service::components::evaluation_providers::OpenAiCompatibleProvider;
+
+    fn failure(status: Option<u16>) -> EvaluationFailure {
+        EvaluationFailure {
+            reason: "failed".to_string(),
+            status,
+            ..Default::default()
+        }
+    }
+
+    fn policy(initial_backoff_ms: u64, max_backoff_ms: u64) -> RetryPolicy {
+        RetryPolicy {
+            max_retries: 3,
+            initial_backoff_ms,
+            max_backoff_ms,
+            max_concurrent_requests: 1,
+        }
+    }
+
+    #[test]
+    fn retries_rate_limits_server_errors_and_lost_requests() {
+        for status in TRANSIENT_STATUSES {
+            assert_eq!(
+                FailureKind::of(&failure(Some(status))),
+                FailureKind::Transient { retry_after: None }
+            );
+        }
+        let retry_after = Some(Duration::from_secs(20));
+        assert_eq!(
+            FailureKind::of(&EvaluationFailure {
+                retry_after,
+                ..failure(Some(429))
+            }),
+            FailureKind::Transient { retry_after }
+        );
+        assert_eq!(
+            FailureKind::of(&EvaluationFailure {
+                no_response: true,
+                ..failure(None)
+            }),
+            FailureKind::Transient { retry_after: None }
+        );
+    }
+
+    #[test]
+    fn keeps_other_failures() {
+        for status in [400, 401, 403, 404, 413, 422] {
+            assert_eq!(
+                FailureKind::of(&failure(Some(status))),
+                FailureKind::Permanent
+            );
+        }
+        // The message isn't read, whatever numbers it happens to contain
+        assert_eq!(
+            FailureKind::of(&EvaluationFailure::other(
+                "Failed to decode IMG_0500.jpg: connection to the decoder timed out"
+            )),
+            FailureKind::Permanent
+        );
+        assert_eq!(
+            FailureKind::of(&EvaluationFailure {
+                code: Some("insufficient_quota".to_string()),
+                ..failure(Some(429))
+            }),
+            FailureKind::Permanent
+        );
+    }
+
+    #[test]
+    fn waits_as_long_as_the_server_asks_up_to_the_limit() {
+        let policy = policy(1_000, 10_000);
+        let asked = Some(Duration::from_millis(2_500));
+        assert_eq!(
+            RetrySession::backoff_delay(&policy, 0, asked),
+            Duration::from_millis(2_500)
+        );
+        let asked = Some(Duration::from_secs(3_600));
+        assert_eq!(
+            RetrySession::backoff_delay(&policy, 0, asked),
+            Duration::from_secs(10)
+        );
+    }
+
+    #[test]
+    fn backs_off_exponentially_with_jitter() {
+        let policy = policy(1_000, 10_000);
+        for (retries, full_ms) in [(0, 1_000), (1, 2_000), (2, 4_000), (3, 8_000), (4, 10_000)] {
+            for _ in 0..20 {
+                let delay = RetrySession::backoff_delay(&policy, retries, None);
+                assert!(
+                    delay >= Duration::from_millis(full_ms / 2)
+                        && delay <= Duration::from_millis(full_ms),
+                    "retry {retries} waited {delay:?}"
+                );
+            }
+        }
+        // Doesn't overflow however often an image is retried
+        assert!(RetrySession::backoff_delay(&policy, u32::MAX, None) <= Duration::from_secs(10));
+    }
+
+    /// Reads one HTTP request, headers and body
+    async fn read_request(stream: &mut TcpStream) {
+        let mut request = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            let read = stream.read(&mut buf).await.unwrap();
+            assert!(read > 0, "connection closed before the request was read");
+            request.extend_from_slice(&buf[..read]);
+            let text = String::from_utf8_lossy(&request);
+            let Some(header_end) = text.find("\r\n\r\n") else {
+                continue;
+            };
+            let content_length = text[..header_end]
+                .lines()
+                .find_map(|line| {
+                    let (name, value) = line.split_once(':')?;
+                    name.eq_ignore_ascii_case("content-length")
+                        .then(|| value.trim().parse::<usize>().ok())?
+                })
+                .unwrap_or(0);
+            if request.len() >= header_end + 4 + content_length {
+                return;
+            }
+        }
+    }
+
+    /// Answers one request per connection with the next of `responses`. Returns the base URL
+    /// and the number of requests answered.
+    async fn serve(responses: Vec<String>) -> (String, Arc<AtomicUsize>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+        let answered = Arc::new(AtomicUsize::new(0));
+        let counter = answered.clone();
+        tokio::spawn(async move {
+            for response in responses {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                read_request(&mut stream).await;
+                counter.fetch_add(1, Ordering::SeqCst);
+                stream.write_all(response.as_bytes()).await.unwrap();
+                let _ = stream.shutdown().await;
+            }
+        });
+        (base_url, answered)
+    }
+
+    fn response(status: &str, extra_headers: &str, body: &str) -> String {
+        format!(
+            "HTTP/1.1 {status}\r\n{extra_headers}Content-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+    }
+
+    fn test_image(name: &str) -> PathBuf {
+        let path =
+            std::env::temp_dir().join(format!("get-crap-done-{name}-{}.png", std::process::id()));
+        std::fs::write(&path, b"not really a png").unwrap();
+        path
+    }
+
+    #[tokio::test]
+    async fn retries_after_the_wait_the_server_asks_for() {
+        let completion = serde_json::json!({
+            "choices": [{
+                "message": {
+                    "content": r#"{"brief_description": "Pump", "filepath_suffix": "_UNIT_P-1"}"#
+                }
+            }]
+        })
+        .to_string();
+        let (base_url, answered) = serve(vec![
+            response(
+                "429 Too Many Requests",
+                "Retry-After: 1\r\n",
+                r#"{"error": {"message": "Slow down", "code": "rate_limit_exceeded"}}"#,
+            ),
+            response("200 OK", "", &completion),
+        ])
+        .await;
+        let provider = OpenAiCompatibleProvider::new(base_url, "test-model".to_string(), None);
+        // Backoff alone would wait at least 15 seconds, so a quick retry means Retry-After was used
+        let retry = EvaluationRetryComponent::new(policy(30_000, 60_000)).session();
+        let image = test_image("retry-after");
+
+        let started = Instant::now();
+        let (result, retries) = retry
+            .evaluate_image(
+                &provider,
+                image.to_string_lossy().to_string(),
+                None,
+                None,
+                &CancellationToken::new(),
+                || {},
+            )
+            .await;
+        let waited = started.elapsed();
+        let _ = std::fs::remove_file(&image);
+
+        assert_eq!(retries, 1);
+        assert_eq!(answered.load(Ordering::SeqCst), 2);
+        assert!(
+            waited >= Duration::from_secs(1) && waited < Duration::from_secs(5),
+            "waited {waited:?}"
+        );
+        let success = result.expect("the retry should succeed");
+        assert_eq!(
+            success.new_suggested_filepath_suffix.as_deref(),
+            Some("_UNIT_P-1")
+        );
+    }
+
+    #[tokio::test]
+    async fn cancelling_stops_the_backoff() {
+        let (base_url, answered) = serve(vec![response(
+            "503 Service Unavailable",
+            "",
+            r#"{"error": {"message": "Overloaded"}}"#,
+        )])
+        .await;
+        let provider = OpenAiCompatibleProvider::new(base_url, "test-model".to_string(), None);
+        let retry = EvaluationRetryComponent::new(policy(30_000, 60_000)).session();
+        let image = test_image("cancel-backoff");
+        let cancel = CancellationToken::new();
+        let cancel_soon = cancel.clone();
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            cancel_soon.cancel();
+        });
+
+        let started = Instant::now();
+        let (result, retries) = retry
+            .evaluate_image(
+                &provider,
+                image.to_string_lossy().to_string(),
+                None,
+                None,
+                &cancel,
+                || {},
+            )
+            .await;
+        let waited = started.elapsed();
+        let _ = std::fs::remove_file(&image);
+
+        assert_eq!(retries, 0);
+        assert_eq!(answered.load(Ordering::SeqCst), 1);
+        assert!(waited < Duration::from_secs(5), "waited {waited:?}");
+        let failure = result.expect_err("the request should have failed");
+        assert_eq!(failure.status, Some(503));
+        assert!(failure.reason.starts_with("HTTP 503"));
+    }
+}
@@ -0,0 +1,241 @@
+use std::sync::{Arc, Mutex};
+
+use lru::LruCache;
+
+use crate::services::projects_service::{
+    components::image_loader::ImageCacheKey, models::ImageCacheStats,
+};
+
+/// Default memory budget for cached full resolution images
+pub const DEFAULT_FULL_IMAGE_CACHE_BYTES: u64 = 512 * 1024 * 1024;
+
+/// The raw bytes of an image file as served to the webview
+#[derive(Clone)]
+pub struct ImageBytes {
+    pub bytes: Arc<Vec<u8>>,
+    /// Quoted HTTP entity tag that changes whenever the file does
+    pub etag: String,
+    pub content_type: &'static str,
+}
+
+struct CacheState {
+    entries: LruCache<ImageCacheKey, ImageBytes>,
+    used_bytes: u64,
+    capacity_bytes: u64,
+    hits: u64,
+    misses: u64,
+    evictions: u64,
+}
+
+/// Keeps recently opened full resolution images in memory, evicting the least recently used ones
+/// once their combined size goes over the byte budget.
+/// Shared by all projects, keys are `project_name/image_name`.
+pub struct FullImageCacheComponent {
+    state: Mutex<CacheState>,
+}
+
+impl Default for FullImageCacheComponent {
+    fn default() -> Self {
+        Self::new(DEFAULT_FULL_IMAGE_CACHE_BYTES)
+    }
+}
+
+impl FullImageCacheComponent {
+    pub fn new(capacity_bytes: u64) -> Self {
+        Self {
+            state: Mutex::new(CacheState {
+                entries: LruCache::unbounded(),
+                used_bytes: 0,
+                capacity_bytes,
+                hits: 0,
+                misses: 0,
+                evictions: 0,
+            }),
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<ImageBytes> {
+        let mut state = self.lock_state();
+        match state.entries.get(key).cloned() {
+            Some(image) => {
+                state.hits += 1;
+                Some(image)
+            }
+            None => {
+                state.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Caches an image, evicting older ones as needed.
+    /// Images bigger than the whole budget are not cached at all.
+    pub fn insert(&self, key: ImageCacheKey, image: ImageBytes) {
+        let size = Self::entry_size(&image);
+        let mut state = self.lock_state();
+        if size > state.capacity_bytes {
+            return;
+        }
+        if let Some(old) = state.entries.put(key, image) {
+            state.used_bytes -= Self::entry_size(&old);
+        }
+        state.used_bytes += size;
+        Self::evict_to_capacity(&mut state);
+    }
+
+    pub fn remove(&self, key: &str) {
+        let mut state = self.lock_state();
+        if let Some(old) = state.entries.pop(key) {
+            state.used_bytes -= Self::entry_size(&old);
+        }
+    }
+
+    /// Removes every cached image whose key starts with `prefix`
+    pub fn remove_with_prefix(&self, prefix: &str) {
+        let mut state = self.lock_state();
+        let keys: Vec<ImageCacheKey> = state
+            .entries
+            .iter()
+            .filter(|(key, _)| key.starts_with(prefix))
+            .map(|(key, _)| key.clone())
+            .collect();
+        for key in keys {
+            if let Some(old) = state.entries.pop(&key) {
+                state.used_bytes -= Self::entry_size(&old);
+            }
+        }
+    }
+
+    /// Changes the byte budget, evicting images right away if the cache is now over it
+    pub fn set_capacity(&self, capacity_bytes: u64) {
+        let mut state = self.lock_state();
+        state.capacity_bytes = capacity_bytes;
+        Self::evict_to_capacity(&mut state);
+    }
+
+    pub fn stats(&self) -> ImageCacheStats {
+        let state = self.lock_state();
+        ImageCacheStats {
+            capacity_bytes: state.capacity_bytes,
+            used_bytes: state.used_bytes,
+            entry_count: state.entries.len(),
+            hits: state.hits,
+            misses: state.misses,
+            evictions: state.evictions,
+        }
+    }
+
+    fn evict_to_capacity(state: &mut CacheState) {
+        while state.used_bytes > state.capacity_bytes {
+            let Some((_, old)) = state.entries.pop_lru() else {
+                break;
+            };
+            state.used_bytes -= Self::entry_size(&old);
+            state.evictions += 1;
+        }
+    }
+
+    /// The file data dominates, the tag is counted so tiny images aren't free
+    fn entry_size(image: &ImageBytes) -> u64 {
+        (image.bytes.len() + image.etag.len()) as u64
+    }
+
+    fn lock_state(&self) -> std::sync::MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An image whose cache entry is exactly `size` bytes
+    fn image(size: usize) -> ImageBytes {
+        ImageBytes {
+            bytes: Arc::new(vec![0; size]),
+            etag: String::new(),
+            content_type: "image/jpeg",
+        }
+    }
+
+    fn cached_keys(cache: &FullImageCacheComponent) -> Vec<String> {
+        let state = cache.lock_state();
+        // Most recently used first
+        state.entries.iter().map(|(key, _)| key.clone()).collect()
+    }
+
+    #[test]
+    fn evicts_least_recently_used_images_over_the_budget() {
+        let cache = FullImageCacheComponent::new(300);
+        cache.insert("p/a.jpg".into(), image(100));
+        cache.insert("p/b.jpg".into(), image(100));
+        cache.insert("p/c.jpg".into(), image(100));
+        assert!(cache.get("p/a.jpg").is_some());
+
+        cache.insert("p/d.jpg".into(), image(150));
+
+        assert_eq!(cached_keys(&cache), vec!["p/d.jpg", "p/a.jpg"]);
+        let stats = cache.stats();
+        assert_eq!(stats.used_bytes, 250);
+        assert_eq!(stats.entry_count, 2);
+        assert_eq!(stats.evictions, 2);
+    }
+
+    #[test]
+    fn replacing_an_image_counts_its_size_once() {
+        let cache = FullImageCacheComponent::new(300);
+        cache.insert("p/a.jpg".into(), image(100));
+        cache.insert("p/a.jpg".into(), image(200));
+
+        let stats = cache.stats();
+        assert_eq!(stats.used_bytes, 200);
+        assert_eq!(stats.entry_count, 1);
+        assert_eq!(stats.evictions, 0);
+    }
+
+    #[test]
+    fn images_larger_than_the_budget_are_not_cached() {
+        let cache = FullImageCacheComponent::new(300);
+        cache.insert("p/a.jpg".into(), image(100));
+
+        cache.insert("p/huge.jpg".into(), image(301));
+
+        assert_eq!(cached_keys(&cache), vec!["p/a.jpg"]);
+        let stats = cache.stats();
+        assert_eq!(stats.used_bytes, 100);
+        assert_eq!(stats.evictions, 0);
+    }
+
+    #[test]
+    fn counts_hits_and_misses() {
+        let cache = FullImageCacheComponent::new(300);
+        assert!(cache.get("p/a.jpg").is_none());
+        cache.insert("p/a.jpg".into(), image(100));
+        assert!(cache.get("p/a.jpg").is_some());
+        assert!(cache.get("p/a.jpg").is_some());
+        cache.remove("p/a.jpg");
+        assert!(cache.get("p/a.jpg").is_none());
+
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses), (2, 2));
+        assert_eq!(stats.used_bytes, 0);
+        // Removed images don't count as evicted
+        assert_eq!(stats.evictions, 0);
+    }
+
+    #[test]
+    fn shrinking_the_budget_evicts_right_away() {
+        let cache = FullImageCacheComponent::new(300);
+        cache.insert("p/a.jpg".into(), image(100));
+        cache.insert("p/b.jpg".into(), image(100));
+        cache.insert("p/c.jpg".into(), image(100));
+
+        cache.set_capacity(150);
+
+        assert_eq!(cached_keys(&cache), vec!["p/c.jpg"]);
+        let stats = cache.stats();
+        assert_eq!(stats.capacity_bytes, 150);
+        assert_eq!(stats.used_bytes, 100);
+        assert_eq!(stats.evictions, 2);
+    }
+}
@@ -0,0 +1,115 @@
+use std::{fs, io::Cursor, path::Path};
+
+use image::{DynamicImage, ImageFormat, ImageReader};
+
+use crate::app_error::{AppError, AppResult};
+
+/// Formats the image crate decodes directly
+const STANDARD_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "gif", "webp", "bmp", "tif", "tiff"];
+/// HEIC/HEIF photos, the iPhone default. Decoded with libheif when the `heic` feature is on.
+const HEIF_EXTENSIONS: &[&str] = &["heic", "heif", "hif"];
+/// Camera RAW formats. Only the JPEG preview the camera embeds in the file is decoded.
+const RAW_EXTENSIONS: &[&str] = &[
+    "dng", "cr2", "cr3", "nef", "arw", "orf", "rw2", "raf", "pef", "srw",
+];
+/// Max number of JPEG start markers tried when looking for a RAW file's embedded preview
+const MAX_EMBEDDED_JPEG_CANDIDATES: usize = 64;
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.contains(&ext.to_lowercase().as_str()))
+}
+
+pub fn is_heif(path: &Path) -> bool {
+    has_extension(path, HEIF_EXTENSIONS)
+}
+
+pub fn is_raw(path: &Path) -> bool {
+    has_extension(path, RAW_EXTENSIONS)
+}
+
+/// True for files that can be imported as images, judged by their extension
+pub fn is_supported_image(path: &Path) -> bool {
+    has_extension(path, STANDARD_EXTENSIONS) || needs_transcoding(path)
+}
+
+/// True for formats the webview, the evaluator and the exporter's users can't open directly,
+/// which are shown and evaluated as JPEG instead
+pub fn needs_transcoding(path: &Path) -> bool {
+    is_heif(path) || is_raw(path)
+}
+
+/// Decodes the primary image of a HEIC/HEIF file.
+/// libheif applies the rotation and mirroring stored in the container, so the result is upright.
+#[cfg(feature = "heic")]
+pub fn decode_heif(path: &Path) -> AppResult<DynamicImage> {
+    use libheif_rs::{ColorSpace, HeifContext, LibHeif, RgbChroma};
+
+    let path_str = path
+        .to_str()
+        .ok_or_else(|| AppError::InvalidName(format!("Invalid path: {}", path.display())))?;
+    let decode_error = |e: libheif_rs::HeifError| {
+        AppError::Decode(format!("Failed to decode {}: {e}", path.display()))
+    };
+
+    let lib_heif = LibHeif::new();
+    let context = HeifContext::read_from_file(path_str).map_err(decode_error)?;
+    let handle = context.primary_image_handle().map_err(decode_error)?;
+    let image = lib_heif
+        .decode(&handle, ColorSpace::Rgb(RgbChroma::Rgba), None)
+        .map_err(decode_error)?;
+    let plane = image
+        .planes()
+        .interleaved
+        .ok_or_else(|| AppError::Decode(format!("No pixel data in {}", path.display())))?;
+
+    // Rows can be padded, copy them without the padding
+    let row_len = plane.width as usize * 4;
+    let mut pixels = Vec::with_capacity(row_len * plane.height as usize);
+    for row in plane.data.chunks(plane.stride).take(plane.height as usize) {
+        pixels.extend_from_slice(&row[..row_len]);
+    }
+    image::RgbaImage::from_raw(plane.width, plane.height, pixels)
+        .map(DynamicImage::ImageRgba8)
+        .ok_or_else(|| AppError::Decode(format!("Invalid pixel data in {}", path.display())))
+}
+
+#[cfg(not(feature = "heic"))]
+pub fn decode_heif(path: &Path) -> AppResult<DynamicImage> {
+    Err(AppError::Decode(format!(
+        "{} is HEIC/HEIF, which this build can't decode",
+        path.display()
+    )))
+}
+
+/// Decodes the largest JPEG preview embedded in a camera RAW file.
+/// RAW data itself isn't developed, the camera's own preview is good enough for reading text.
+pub fn decode_raw_preview(path: &Path) -> AppResult<DynamicImage> {
+    let bytes = fs::read(path).map_err(|e| AppError::io(path.display(), e))?;
+
+    // Every JPEG starts with an SOI marker followed by another marker
+    let mut candidates: Vec<(usize, u64)> = bytes
+        .windows(3)
+        .enumerate()
+        .filter(|(_, window)| *window == [0xFF, 0xD8, 0xFF])
+        .take(MAX_EMBEDDED_JPEG_CANDIDATES)
+        .filter_map(|(offset, _)| {
+            let (width, height) = jpeg_reader(&bytes[offset..]).into_dimensions().ok()?;
+            Some((offset, u64::from(width) * u64::from(height)))
+        })
+        .collect();
+    candidates.sort_by_key(|(_, pixels)| std::cmp::Reverse(*pixels));
+
+    // Lossless JPEG tiles of the RAW data have valid headers too but can't be decoded
+    candidates
+        .into_iter()
+        .find_map(|(offset, _)| jpeg_reader(&bytes[offset..]).decode().ok())
+        .ok_or_else(|| AppError::Decode(format!("No embedded preview in {}", path.display())))
+}
+
+fn jpeg_reader(bytes: &[u8]) -> ImageReader<Cursor<&[u8]>> {
+    let mut reader = ImageReader::new(Cursor::new(bytes));
+    reader.set_format(ImageFormat::Jpeg);
+    reader
+}
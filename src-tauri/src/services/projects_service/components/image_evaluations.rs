@@ -1,156 +1,485 @@
-use std::{fs, path::Path, path::PathBuf, sync::Arc};
+use std::{
+    collections::HashMap,
+    fs,
+    path::Path,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+};
 
-use ocr_image_thing::ImageEvalClient;
+use futures::{stream::FuturesUnordered, StreamExt};
+use ocr_image_thing::ImageEvaluationResult;
+use tokio_util::sync::CancellationToken;
 
-use crate::services::{
-    app_save_service::AppSaveService,
-    projects_service::{models::ImageEvaluation, requests::RequestImageEvaluation},
+use crate::{
+    app_error::{AppError, AppResult},
+    services::{
+        app_save_service::{AppSaveService, JsonRead},
+        projects_service::{
+            components::{
+                barcode_reader::{decode_barcodes_in_images, serial_from_barcodes},
+                evaluation_providers::{provider_from_config, EvaluationProvider, ProviderResult},
+                evaluation_retry::EvaluationRetryComponent,
+                image_index::ImageIndexComponent,
+                image_loader::ImageLoaderComponent,
+                offline_ocr::OCR_MODELS_DIR,
+                project_locks::ProjectLocksComponent,
+            },
+            models::{
+                DecodedBarcode, EvaluationImageStatus, EvaluationProgress,
+                EvaluationProviderConfig, ImageEvaluation, RetryPolicy,
+            },
+            requests::RequestImageEvaluation,
+        },
+    },
 };
 
+/// Lets the caller follow an evaluation and stop it halfway
+pub struct EvaluationSession<'a> {
+    pub evaluation_id: &'a str,
+    /// Cancelling stops the images that aren't done yet and keeps the results saved so far
+    pub cancel: &'a CancellationToken,
+    /// Called when an image starts and when it's done
+    pub on_progress: &'a (dyn Fn(EvaluationProgress) + Send + Sync),
+}
+
 pub struct ImageEvaluationsComponent {
     app_save: Arc<AppSaveService>,
-    client: ImageEvalClient,
+    image_index: Arc<ImageIndexComponent>,
+    /// Taken around each save of an evaluation result, not for the whole run
+    locks: Arc<ProjectLocksComponent>,
+    retry: EvaluationRetryComponent,
+}
+
+const RETRY_POLICY_PATH: &str = "evaluation_retry.json";
+
+/// Numbers the upright copy directories of evaluations running at the same time
+static NEXT_RUN_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// An image the provider is done with
+struct EvaluatedImage {
+    image_name: String,
+    content_hash: String,
+    /// Where the image was when its evaluation started
+    full_path: String,
+    result: ProviderResult,
+    retries: u32,
+    barcodes: Vec<DecodedBarcode>,
 }
 
 impl ImageEvaluationsComponent {
-    pub fn new(app_save: Arc<AppSaveService>) -> Self {
-        let client = ocr_image_thing::ImageEvalClient::new();
-        Self { app_save, client }
-    }
-
-    /// Collects all image files from `images/` root and one level of subdirectories.
-    /// Returns `(full_path, relative_name)` pairs.
-    fn collect_all_images(images_base: &Path) -> Vec<(PathBuf, String)> {
-        let mut result = Vec::new();
-        let entries = match fs::read_dir(images_base) {
-            Ok(e) => e,
-            Err(_) => return result,
+    pub fn new(
+        app_save: Arc<AppSaveService>,
+        image_index: Arc<ImageIndexComponent>,
+        locks: Arc<ProjectLocksComponent>,
+    ) -> Self {
+        let retry_policy = if app_save.get_full_path(RETRY_POLICY_PATH).exists() {
+            app_save.read_json(RETRY_POLICY_PATH).unwrap_or_else(|e| {
+                log::warn!("Failed to read the evaluation retry policy: {e}");
+                RetryPolicy::default()
+            })
+        } else {
+            RetryPolicy::default()
         };
-        for entry in entries.flatten() {
-            let path = entry.path();
-            if path.is_file() {
-                let name = path
-                    .file_name()
-                    .map(|n| n.to_string_lossy().to_string())
-                    .unwrap_or_default();
-                result.push((path, name));
-            } else if path.is_dir() {
-                let folder_name = match path.file_name() {
-                    Some(n) => n.to_string_lossy().to_string(),
-                    None => continue,
-                };
-                if let Ok(sub_entries) = fs::read_dir(&path) {
-                    for sub_entry in sub_entries.flatten() {
-                        let sub_path = sub_entry.path();
-                        if sub_path.is_file() {
-                            let file_name = sub_path
-                                .file_name()
-                                .map(|n| n.to_string_lossy().to_string())
-                                .unwrap_or_default();
-                            let rel_name = format!("{folder_name}/{file_name}");
-                            result.push((sub_path, rel_name));
-                        }
-                    }
-                }
-            }
+        Self {
+            app_save,
+            image_index,
+            locks,
+            retry: EvaluationRetryComponent::new(retry_policy),
         }
-        result
     }
 
+    /// Evaluates with the provider chosen in the request, or the project's provider otherwise
     pub async fn evaluate_images(
         &self,
         project_name: &str,
         request: RequestImageEvaluation,
         custom_prompt: Option<String>,
         temperature: Option<f32>,
-    ) -> Result<Vec<ImageEvaluation>, String> {
-        self.client.set_api_key(&request.openai_api_key).await;
+        session: &EvaluationSession<'_>,
+    ) -> AppResult<Vec<ImageEvaluation>> {
+        let config = match request.provider {
+            Some(config) => config,
+            None => self.read_provider_config(project_name)?,
+        };
+        let provider = provider_from_config(config, request.openai_api_key, self.ocr_models_dir())?;
+        self.evaluate_images_with(
+            project_name,
+            &request.image_names,
+            provider.as_ref(),
+            custom_prompt,
+            temperature,
+            request.prefer_barcode_serial,
+            session,
+        )
+        .await
+    }
 
-        // Collect all images from root and subdirectories
+    /// Evaluates images with `provider`, saving each result as soon as its image is done.
+    /// The project is only locked while a result is saved, so images can be moved or deleted
+    /// during the run. Cancelling the session keeps the results saved so far.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn evaluate_images_with(
+        &self,
+        project_name: &str,
+        image_names: &[String],
+        provider: &dyn EvaluationProvider,
+        custom_prompt: Option<String>,
+        temperature: Option<f32>,
+        prefer_barcode_serial: bool,
+        session: &EvaluationSession<'_>,
+    ) -> AppResult<Vec<ImageEvaluation>> {
+        // Every indexed image, in the project root and its folders
         let images_base = self
             .app_save
             .get_full_path(&format!("projects/{project_name}/images"));
-        let all_images = Self::collect_all_images(&images_base);
+        let index = self.image_index.read_index(project_name).await?;
 
-        // Build a map from relative name -> full path, then filter by requested names
+        // Full path -> (relative name, content hash) of the requested images. The hash finds
+        // the image again if it's moved while being evaluated.
         let requested: std::collections::HashSet<&str> =
-            request.image_names.iter().map(String::as_str).collect();
-        let selected_images: Vec<(String, String)> = all_images
-            .into_iter()
-            .filter(|(_, rel_name)| requested.contains(rel_name.as_str()))
-            .map(|(full_path, rel_name)| (full_path.to_string_lossy().to_string(), rel_name))
+            image_names.iter().map(String::as_str).collect();
+        let selected_images: HashMap<String, (String, String)> = index
+            .images
+            .iter()
+            .filter(|(rel_name, _)| requested.contains(rel_name.as_str()))
+            .map(|(rel_name, entry)| {
+                let full_path = images_base.join(rel_name).to_string_lossy().to_string();
+                (full_path, (rel_name.clone(), entry.content_hash.clone()))
+            })
             .collect();
 
         if selected_images.is_empty() {
-            return Err(format!(
+            return Err(AppError::NotFound(format!(
                 "No matching images found in project. Requested: {:?}",
-                request.image_names
-            ));
+                image_names
+            )));
         }
 
-        // Build a reverse map: full_path -> relative_name (for result mapping)
-        let path_to_rel: std::collections::HashMap<String, String> = selected_images
-            .iter()
-            .map(|(full_path, rel_name)| (full_path.clone(), rel_name.clone()))
+        // OCR does poorly on sideways text, so images stored rotated are sent as upright copies.
+        // HEIF and RAW images are sent as JPEG copies the same way.
+        // One directory per run, evaluations of the same project can overlap
+        let run_id = NEXT_RUN_ID.fetch_add(1, Ordering::Relaxed);
+        let upright_dir = self
+            .app_save
+            .get_full_path(&format!("projects/{project_name}/.upright/{run_id}"));
+        // Cancelled when the run ends, so copies still being written don't outlive its directory
+        let run_over = session.cancel.child_token();
+        let total = selected_images.len();
+        let done = AtomicUsize::new(0);
+        let progress = |image_name: &str, status, evaluation| {
+            (session.on_progress)(EvaluationProgress {
+                evaluation_id: session.evaluation_id.to_string(),
+                project_name: project_name.to_string(),
+                image_name: image_name.to_string(),
+                status,
+                done: done.load(Ordering::Relaxed),
+                total,
+                evaluation,
+            })
+        };
+
+        let retry = self.retry.session();
+        let mut jobs: FuturesUnordered<_> = selected_images
+            .into_iter()
+            .enumerate()
+            .map(|(i, (full_path, (rel_name, content_hash)))| {
+                let (retry, progress) = (&retry, &progress);
+                let custom_prompt = custom_prompt.as_deref();
+                let copy_path = upright_dir.join(format!("{i}.jpg"));
+                let run_over = run_over.clone();
+                async move {
+                    // Copies are written as their images come up, throttled like requests, so the
+                    // first image is sent right away and a cancelled run stops writing them
+                    let sent_path = {
+                        let _slot = retry.request_slot().await;
+                        if run_over.is_cancelled() {
+                            return None;
+                        }
+                        Self::upright_copy(full_path.clone(), copy_path, run_over).await
+                    };
+                    let (mut result, retries) = retry
+                        .evaluate_image(
+                            provider,
+                            sent_path.clone(),
+                            custom_prompt,
+                            temperature,
+                            session.cancel,
+                            || progress(&rel_name, EvaluationImageStatus::Started, None),
+                        )
+                        .await;
+                    // Barcodes are read locally once the provider is done with the image
+                    let barcodes = decode_barcodes_in_images(std::slice::from_ref(&sent_path))
+                        .await
+                        .remove(&sent_path)
+                        .unwrap_or_default();
+                    // Point results for upright copies back at the original image
+                    if let Ok(success) = &mut result {
+                        success.original_image_path = full_path.clone();
+                    }
+                    Some(EvaluatedImage {
+                        image_name: rel_name,
+                        content_hash,
+                        full_path,
+                        result,
+                        retries,
+                        barcodes,
+                    })
+                }
+            })
             .collect();
 
-        let full_paths: Vec<String> = selected_images.into_iter().map(|(fp, _)| fp).collect();
-        let eval_results = self.client.evaluate_images(full_paths, custom_prompt, temperature).await;
-
-        let current_evals = self.read_images_eval_json(project_name)?;
-        let mut new_evals = Vec::new();
-
-        for result in eval_results {
-            // Resolve the relative name from the full path
-            let rel_name = path_to_rel
-                .get(&result.full_image_path)
-                .cloned()
-                .unwrap_or_else(|| {
-                    // Fallback: extract filename only
-                    Path::new(&result.full_image_path)
-                        .file_name()
-                        .unwrap()
-                        .to_string_lossy()
-                        .to_string()
-                });
+        let mut finished = 0;
+        let mut batch_error = None;
+        // A rate limit or rejected key every image so far ran into may hit the whole batch. Those
+        // failures are held back until another kind of result comes in, and the run fails with
+        // their error instead if none does.
+        let mut held_back: Vec<(String, ImageEvaluation)> = Vec::new();
+        let mut held_back_error: Option<AppError> = None;
+        'run: loop {
+            let EvaluatedImage {
+                image_name,
+                content_hash,
+                full_path,
+                mut result,
+                retries,
+                barcodes,
+            } = tokio::select! {
+                biased;
+                _ = session.cancel.cancelled() => break,
+                job = jobs.next() => match job {
+                    Some(Some(job)) => job,
+                    // Jobs only stop early once the run is cancelled
+                    Some(None) | None => break,
+                },
+            };
+            finished += 1;
 
+            let failure_error = result.as_ref().err().map(AppError::from);
+            // A rejected key or an exhausted quota fails every request the same way, so there's
+            // no point in waiting for the other images
+            if let Err(failure) = &result {
+                if failure.is_account_failure() {
+                    batch_error = failure_error;
+                    break;
+                }
+            }
+
+            if prefer_barcode_serial {
+                if let Some(serial) = serial_from_barcodes(&barcodes) {
+                    let suffix = Some(format!("_SERIAL_{serial}"));
+                    result = Ok(match result {
+                        Ok(success) => ImageEvaluationResult {
+                            new_suggested_filepath_suffix: suffix,
+                            ..success
+                        },
+                        // The serial is enough to name the image even if the model failed on it
+                        Err(failure) => ImageEvaluationResult {
+                            full_model_output: format!("The evaluator failed: {}", failure.reason),
+                            new_suggested_filepath_suffix: suffix,
+                            brief_description: format!(
+                                "Serial number {serial} read from a barcode"
+                            ),
+                            original_image_path: full_path,
+                        },
+                    });
+                }
+            }
+            let (result, fail_reason) = match result {
+                Ok(success) => (Some(success), None),
+                Err(failure) => (None, Some(failure.reason)),
+            };
             let new_eval = ImageEvaluation {
-                image_name: rel_name,
-                result: result.success_result,
-                fail_reason: result.failure_result,
+                image_name,
+                result,
+                fail_reason,
+                stale: false,
+                barcodes,
+                retries,
+            };
+
+            let batch_wide = match failure_error {
+                Some(err @ (AppError::Auth(_) | AppError::RateLimited(_)))
+                    if new_eval.result.is_none() =>
+                {
+                    Some(err)
+                }
+                _ => None,
+            };
+            let same_as_held_back = |err: &AppError| {
+                held_back.len() == finished - 1
+                    && match &held_back_error {
+                        Some(held) => std::mem::discriminant(held) == std::mem::discriminant(err),
+                        None => true,
+                    }
             };
-            new_evals.push(new_eval);
+            if let Some(err) = batch_wide.filter(same_as_held_back) {
+                held_back.push((content_hash, new_eval));
+                held_back_error = Some(err);
+                continue;
+            }
+            let ready = held_back
+                .drain(..)
+                .chain(std::iter::once((content_hash, new_eval)));
+            for (content_hash, evaluation) in ready {
+                if let Err(e) = self
+                    .save_and_report(project_name, &content_hash, evaluation, &done, &progress)
+                    .await
+                {
+                    batch_error = Some(e);
+                    break 'run;
+                }
+            }
+        }
+        if batch_error.is_none() && !held_back.is_empty() {
+            batch_error = held_back_error;
+        }
+        // Stops the images still being evaluated before their upright copies are removed
+        run_over.cancel();
+        drop(jobs);
+        if upright_dir.exists() {
+            if let Err(e) = fs::remove_dir_all(&upright_dir) {
+                log::warn!(
+                    "Failed to remove upright copies in {}: {e}",
+                    upright_dir.display()
+                );
+            }
+            // Only succeeds once no other run of the project has copies left
+            if let Some(parent) = upright_dir.parent() {
+                let _ = fs::remove_dir(parent);
+            }
         }
-        // Overwrite or add to the current evals:
-        // Ensure new_evals with the same image_name as old ones overwrite the old evaluations
-        let mut eval_map: std::collections::HashMap<String, ImageEvaluation> = current_evals
-            .into_iter()
-            .map(|eval| (eval.image_name.clone(), eval))
-            .collect();
 
-        for eval in new_evals {
-            eval_map.insert(eval.image_name.clone(), eval); // Overwrite or insert
+        match batch_error {
+            Some(err) => Err(err),
+            None => self.read_images_eval_json(project_name),
         }
+    }
 
-        let all_new_evals: Vec<ImageEvaluation> = eval_map.into_values().collect();
+    /// Saves a finished image's evaluation and reports it as done
+    async fn save_and_report(
+        &self,
+        project_name: &str,
+        content_hash: &str,
+        evaluation: ImageEvaluation,
+        done: &AtomicUsize,
+        progress: &impl Fn(&str, EvaluationImageStatus, Option<ImageEvaluation>),
+    ) -> AppResult<()> {
+        let image_name = evaluation.image_name.clone();
+        let status = if evaluation.result.is_some() {
+            EvaluationImageStatus::Completed
+        } else {
+            EvaluationImageStatus::Failed
+        };
+        let saved = self
+            .save_evaluation(project_name, content_hash, evaluation)
+            .await?;
+        done.fetch_add(1, Ordering::Relaxed);
+        match saved {
+            Some(saved) => progress(&saved.image_name.clone(), status, Some(saved)),
+            // Deleted or edited while it was being evaluated
+            None => progress(&image_name, EvaluationImageStatus::Failed, None),
+        }
+        Ok(())
+    }
+
+    /// Saves one image's evaluation under the project lock. The image may have been moved,
+    /// edited or deleted since its evaluation started, so it's looked up again by content hash.
+    /// Returns the evaluation as saved, or `None` if the evaluated content is no longer in the
+    /// project.
+    async fn save_evaluation(
+        &self,
+        project_name: &str,
+        content_hash: &str,
+        mut evaluation: ImageEvaluation,
+    ) -> AppResult<Option<ImageEvaluation>> {
+        let _guard = self.locks.lock(project_name).await;
+        let index = self.image_index.read_index(project_name).await?;
+        let unchanged = index
+            .images
+            .get(&evaluation.image_name)
+            .is_some_and(|entry| entry.content_hash == content_hash);
+        if !unchanged {
+            match index.find_by_hash(content_hash) {
+                Some(current_name) => evaluation.image_name = current_name.to_string(),
+                None => return Ok(None),
+            }
+        }
 
-        self.write_images_eval_json(project_name, &all_new_evals)?;
+        // Overwrite the image's previous evaluation, or add it
+        let mut evals = self.read_images_eval_json(project_name)?;
+        match evals
+            .iter_mut()
+            .find(|eval| eval.image_name == evaluation.image_name)
+        {
+            Some(existing) => *existing = evaluation.clone(),
+            None => evals.push(evaluation.clone()),
+        }
+        self.write_images_eval_json(project_name, &evals)?;
+        Ok(Some(evaluation))
+    }
 
-        Ok(all_new_evals)
+    /// Writes an upright JPEG copy of an image to `copy_path` if it's stored rotated or flipped,
+    /// or in a format the evaluator can't read. Returns the path to send: the copy, or the image
+    /// itself if it needs no copy, fails to convert (which is logged) or the run is over.
+    async fn upright_copy(
+        full_path: String,
+        copy_path: PathBuf,
+        run_over: CancellationToken,
+    ) -> String {
+        let original = full_path.clone();
+        let result = tokio::task::spawn_blocking(move || {
+            let source = Path::new(&full_path);
+            if run_over.is_cancelled() || !ImageLoaderComponent::needs_upright_copy(source) {
+                return full_path;
+            }
+            if let Some(dir) = copy_path.parent() {
+                if let Err(e) = fs::create_dir_all(dir) {
+                    log::warn!("Failed to create {}: {e}", dir.display());
+                    return full_path;
+                }
+            }
+            match ImageLoaderComponent::write_upright_copy(source, &copy_path) {
+                Ok(true) => copy_path.to_string_lossy().to_string(),
+                Ok(false) => full_path,
+                Err(e) => {
+                    log::warn!("Failed to write upright copy of {full_path}: {e}");
+                    full_path
+                }
+            }
+        })
+        .await;
+        result.unwrap_or_else(|e| {
+            log::warn!("Failed to write upright copy of {original}: {e}");
+            original
+        })
     }
 
     /// Read the existing evaluated images for the project.
     /// Returns an empty vec if the file does not exist (e.g. first evaluation for the project).
-    pub fn read_images_eval_json(
+    pub fn read_images_eval_json(&self, project_name: &str) -> AppResult<Vec<ImageEvaluation>> {
+        self.read_images_eval_json_with_recovery(project_name)
+            .map(|read| read.value)
+    }
+
+    /// Same as `read_images_eval_json`, also reporting whether the backup had to be used
+    pub fn read_images_eval_json_with_recovery(
         &self,
         project_name: &str,
-    ) -> Result<Vec<ImageEvaluation>, String> {
+    ) -> AppResult<JsonRead<Vec<ImageEvaluation>>> {
         let evals_path = format!("projects/{project_name}/image_evals.json");
         let full_path = self.app_save.get_full_path(&evals_path);
-        if full_path.exists() {
-            self.app_save.read_json(&evals_path)
+        // A damaged file is still recovered from its backup
+        let bak_path = self.app_save.get_full_path(&format!("{evals_path}.bak"));
+        if full_path.exists() || bak_path.exists() {
+            self.app_save.read_json_with_recovery(&evals_path)
         } else {
-            Ok(Vec::new())
+            Ok(JsonRead {
+                value: Vec::new(),
+                recovered_from_backup: false,
+            })
         }
     }
 
@@ -158,17 +487,51 @@ impl ImageEvaluationsComponent {
         &self,
         project_name: &str,
         evals: &Vec<ImageEvaluation>,
-    ) -> Result<(), String> {
+    ) -> AppResult<()> {
         let evals_path = format!("projects/{project_name}/image_evals.json");
         self.app_save.save_json(&evals_path, evals)
     }
 
+    pub fn retry_policy(&self) -> RetryPolicy {
+        self.retry.policy()
+    }
+
+    pub fn set_retry_policy(&self, policy: RetryPolicy) -> AppResult<()> {
+        self.app_save.save_json(RETRY_POLICY_PATH, &policy)?;
+        self.retry.set_policy(policy);
+        Ok(())
+    }
+
+    /// Where offline OCR reads its models from
+    pub fn ocr_models_dir(&self) -> PathBuf {
+        self.app_save.get_full_path(OCR_MODELS_DIR)
+    }
+
+    /// The provider the project's images are evaluated with. OpenAI unless changed.
+    pub fn read_provider_config(&self, project_name: &str) -> AppResult<EvaluationProviderConfig> {
+        let config_path = format!("projects/{project_name}/evaluation_provider.json");
+        if self.app_save.get_full_path(&config_path).exists() {
+            self.app_save.read_json(&config_path)
+        } else {
+            Ok(EvaluationProviderConfig::default())
+        }
+    }
+
+    pub fn write_provider_config(
+        &self,
+        project_name: &str,
+        config: &EvaluationProviderConfig,
+    ) -> AppResult<()> {
+        let config_path = format!("projects/{project_name}/evaluation_provider.json");
+        self.app_save.save_json(&config_path, config)
+    }
+
     /// Renames evaluation entries when images are moved between folders.
     pub fn rename_evaluations(
         &self,
         project_name: &str,
         renames: &[(String, String)],
-    ) -> Result<(), String> {
+    ) -> AppResult<()> {
         let mut evals = self.read_images_eval_json(project_name)?;
         for eval in &mut evals {
             for (old, new) in renames {
@@ -181,12 +544,33 @@ impl ImageEvaluationsComponent {
         self.write_images_eval_json(project_name, &evals)
     }
 
+    /// Flags the evaluations of images that were edited since, until they are evaluated again
+    pub fn mark_stale(&self, project_name: &str, image_names: &[String]) -> AppResult<()> {
+        if image_names.is_empty() {
+            return Ok(());
+        }
+        let mut evals = self.read_images_eval_json(project_name)?;
+        let names_set: std::collections::HashSet<_> =
+            image_names.iter().map(String::as_str).collect();
+        let mut changed = false;
+        for eval in &mut evals {
+            if !eval.stale && names_set.contains(eval.image_name.as_str()) {
+                eval.stale = true;
+                changed = true;
+            }
+        }
+        if changed {
+            self.write_images_eval_json(project_name, &evals)?;
+        }
+        Ok(())
+    }
+
     /// Removes saved evaluations for the given image names (e.g. when those images are deleted).
     pub fn remove_evaluations_for_images(
         &self,
         project_name: &str,
         image_names: &[String],
-    ) -> Result<(), String> {
+    ) -> AppResult<()> {
         if image_names.is_empty() {
             return Ok(());
         }
@@ -75,6 +75,7 @@ impl ImageExporterComponent {
         Ok(errors)
     }
 
+    /// Mirrors the image's folder path, however deeply nested, under `out_dir`
     fn destination_dir_for_eval(out_dir: &Path, image_name: &str) -> PathBuf {
         let image_path = Path::new(image_name);
         let Some(parent) = image_path.parent() else {
@@ -0,0 +1,774 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsStr,
+    fs::{self, File},
+    io::BufReader,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::UNIX_EPOCH,
+};
+
+use walkdir::WalkDir;
+
+use crate::{
+    app_error::{AppError, AppResult},
+    services::{
+        app_save_service::AppSaveService,
+        projects_service::models::{ImageMetadataModel, ImagePreviewModel, ReconcileReport},
+    },
+};
+
+/// Max number of differing perceptual hash bits for two images to count as near-duplicates
+pub const DEFAULT_NEAR_DUPLICATE_DISTANCE: u32 = 10;
+
+/// Every image in a project with the facts about it that are expensive to recompute, keyed by
+/// relative image name. Saved as `projects/{name}/image_index.json`.
+/// Commands list images from here instead of walking `images/` again.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageIndex {
+    pub images: BTreeMap<String, ImageIndexEntry>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageIndexEntry {
+    /// Hex encoded BLAKE3 hash of the file contents
+    pub content_hash: String,
+    pub size_bytes: u64,
+    /// Milliseconds since the Unix epoch, together with the size used to notice edits made
+    /// outside the app
+    #[serde(default)]
+    pub modified_at: Option<u64>,
+    /// As displayed, filled in when its preview is generated
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+    /// Hex encoded 64 bit difference hash of the image, filled in when its preview is generated
+    #[serde(default)]
+    pub perceptual_hash: Option<String>,
+    /// EXIF/XMP capture details, read on import or the first time the image is listed
+    #[serde(default)]
+    pub metadata: Option<ImageMetadataModel>,
+    /// Hex encoded BLAKE3 hash of the original file, for images transcoded to JPEG on import
+    #[serde(default)]
+    pub source_hash: Option<String>,
+}
+
+impl ImageIndexEntry {
+    /// An entry for freshly hashed content, with nothing derived from it yet
+    pub fn new(content_hash: String, size_bytes: u64, modified_at: Option<u64>) -> Self {
+        Self {
+            content_hash,
+            size_bytes,
+            modified_at,
+            width: None,
+            height: None,
+            perceptual_hash: None,
+            metadata: None,
+            source_hash: None,
+        }
+    }
+}
+
+impl ImageIndex {
+    /// The folder an image is directly in, `None` for the project root
+    pub fn folder_of(image_name: &str) -> Option<&str> {
+        image_name.rsplit_once('/').map(|(folder, _)| folder)
+    }
+
+    /// Names of the images in `folder` and its subfolders
+    pub fn image_names_in_folder(&self, folder: &str) -> Vec<String> {
+        let prefix = format!("{folder}/");
+        self.images
+            .range(prefix.clone()..)
+            .take_while(|(name, _)| name.starts_with(&prefix))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// `(full_path, relative_name)` of every indexed image, given the project's `images/` path
+    pub fn image_files(&self, images_dir: &Path) -> Vec<(PathBuf, String)> {
+        self.images
+            .keys()
+            .map(|name| (images_dir.join(name), name.clone()))
+            .collect()
+    }
+
+    /// Returns the name of an indexed image with exactly this content, or transcoded from a file
+    /// with exactly this content, if any
+    pub fn find_by_hash(&self, content_hash: &str) -> Option<&str> {
+        self.images
+            .iter()
+            .find(|(_, entry)| {
+                entry.content_hash == content_hash
+                    || entry.source_hash.as_deref() == Some(content_hash)
+            })
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Groups images whose perceptual hashes differ by at most `max_distance` bits.
+    /// Grouping is transitive, so A and C share a group if both are close to B.
+    /// Only groups with more than one image are returned, each sorted by name.
+    pub fn near_duplicate_groups(&self, max_distance: u32) -> Vec<Vec<String>> {
+        let hashed: Vec<(&str, u64)> = self
+            .images
+            .iter()
+            .filter_map(|(name, entry)| {
+                let hash = entry.perceptual_hash.as_deref()?;
+                let hash = u64::from_str_radix(hash, 16).ok()?;
+                Some((name.as_str(), hash))
+            })
+            .collect();
+
+        // Union-find over every pair that is close enough
+        let mut parents: Vec<usize> = (0..hashed.len()).collect();
+        fn root(parents: &mut [usize], mut i: usize) -> usize {
+            while parents[i] != i {
+                parents[i] = parents[parents[i]];
+                i = parents[i];
+            }
+            i
+        }
+        for i in 0..hashed.len() {
+            for j in (i + 1)..hashed.len() {
+                if (hashed[i].1 ^ hashed[j].1).count_ones() <= max_distance {
+                    let (a, b) = (root(&mut parents, i), root(&mut parents, j));
+                    parents[b] = a;
+                }
+            }
+        }
+
+        let mut groups: BTreeMap<usize, Vec<String>> = BTreeMap::new();
+        for (i, (name, _)) in hashed.iter().enumerate() {
+            let group_root = root(&mut parents, i);
+            groups.entry(group_root).or_default().push(name.to_string());
+        }
+        groups
+            .into_values()
+            .filter(|group| group.len() > 1)
+            .collect()
+    }
+}
+
+/// What a walk of a project's `images/` directory found for one file
+struct DiskImage {
+    name: String,
+    size_bytes: u64,
+    modified_at: Option<u64>,
+    /// Only hashed when the file's size or modification time differ from the index it was
+    /// compared to
+    content_hash: Option<String>,
+}
+
+/// Reads and updates the per-project image index.
+/// Every change goes through `update_index`, which changes it in place and can be used without
+/// the project lock.
+///
+/// Indexes are kept in memory once loaded. The first load of a session reconciles the index with
+/// the files on disk, after that only `reconcile` walks `images/` again, so changes made outside
+/// the app are picked up by calling it.
+pub struct ImageIndexComponent {
+    app_save: Arc<AppSaveService>,
+    /// Keyed by project name
+    loaded: Mutex<HashMap<String, ImageIndex>>,
+    /// Held while an index is changed and saved, so saves land in the order changes were made
+    writes: Mutex<()>,
+}
+
+impl ImageIndexComponent {
+    pub fn new(app_save: Arc<AppSaveService>) -> Self {
+        Self {
+            app_save,
+            loaded: Mutex::new(HashMap::new()),
+            writes: Mutex::new(()),
+        }
+    }
+
+    /// Returns the project's index, loading and reconciling it first if this is the first time
+    pub async fn read_index(&self, project_name: &str) -> AppResult<ImageIndex> {
+        if let Some(index) = self.lock_loaded().get(project_name) {
+            return Ok(index.clone());
+        }
+        let (index, _) = self.reconcile_with_disk(project_name).await?;
+        Ok(index)
+    }
+
+    /// Changes the project's index in place and saves it if `update` reports a change.
+    /// Changes made at the same time, e.g. by a preview stream and a move, don't overwrite each
+    /// other. Returns the index after the change.
+    pub async fn update_index(
+        &self,
+        project_name: &str,
+        update: impl FnOnce(&mut ImageIndex) -> bool,
+    ) -> AppResult<ImageIndex> {
+        let index = self.read_index(project_name).await?;
+        let _writes = self.lock_writes();
+        let mut index = self
+            .lock_loaded()
+            .get(project_name)
+            .cloned()
+            .unwrap_or(index);
+        if update(&mut index) {
+            self.save_index(project_name, &index)?;
+        }
+        Ok(index)
+    }
+
+    fn save_index(&self, project_name: &str, index: &ImageIndex) -> AppResult<()> {
+        let index_path = format!("projects/{project_name}/image_index.json");
+        self.app_save.save_json(&index_path, index)?;
+        self.lock_loaded()
+            .insert(project_name.to_string(), index.clone());
+        Ok(())
+    }
+
+    /// Brings the index in line with the files on disk, e.g. after images were added, removed or
+    /// edited outside the app, and reports what changed
+    pub async fn reconcile(&self, project_name: &str) -> AppResult<ReconcileReport> {
+        let (_, report) = self.reconcile_with_disk(project_name).await?;
+        Ok(report)
+    }
+
+    /// Drops the in-memory index of a project that was deleted or moved away
+    pub fn forget(&self, project_name: &str) {
+        self.lock_loaded().remove(project_name);
+    }
+
+    /// Syncs the loaded index, or the saved one if it isn't loaded yet, with the files on disk and
+    /// saves the result if anything changed
+    async fn reconcile_with_disk(
+        &self,
+        project_name: &str,
+    ) -> AppResult<(ImageIndex, ReconcileReport)> {
+        let (saved, disk_images) = self.walk_disk(project_name).await?;
+        self.apply_disk_images(project_name, saved, disk_images)
+    }
+
+    /// Walks and hashes the project's images against the loaded or saved index, on a blocking
+    /// thread and without holding any lock, so the index stays readable and changeable meanwhile.
+    /// Returns the index that was compared to along with what was found.
+    async fn walk_disk(&self, project_name: &str) -> AppResult<(ImageIndex, Vec<DiskImage>)> {
+        let loaded = self.lock_loaded().get(project_name).cloned();
+        let app_save = self.app_save.clone();
+        let project_name = project_name.to_string();
+        tokio::task::spawn_blocking(move || {
+            let index_path = format!("projects/{project_name}/image_index.json");
+            let index = match loaded {
+                Some(index) => index,
+                None if app_save.get_full_path(&index_path).exists() => {
+                    app_save.read_json(&index_path)?
+                }
+                None => ImageIndex::default(),
+            };
+            let images_dir = app_save.get_full_path(&format!("projects/{project_name}/images"));
+            let files = Self::scan_images_dir(&images_dir)?;
+            let disk_images = Self::read_disk_images(&index, &files);
+            Ok((index, disk_images))
+        })
+        .await
+        .map_err(|e| AppError::Internal(format!("Image index task failed: {e}")))?
+    }
+
+    /// Merges what `walk_disk` found into the current index, which may have changed during the
+    /// walk, and saves it while still holding `writes`. `saved` is used if no index was loaded
+    /// in the meantime.
+    fn apply_disk_images(
+        &self,
+        project_name: &str,
+        saved: ImageIndex,
+        disk_images: Vec<DiskImage>,
+    ) -> AppResult<(ImageIndex, ReconcileReport)> {
+        let images_dir = self
+            .app_save
+            .get_full_path(&format!("projects/{project_name}/images"));
+        let _writes = self.lock_writes();
+        let mut index = self
+            .lock_loaded()
+            .get(project_name)
+            .cloned()
+            .unwrap_or(saved);
+        let report = Self::sync_with_disk_images(&mut index, disk_images, &images_dir);
+        if report.is_empty() {
+            self.lock_loaded()
+                .insert(project_name.to_string(), index.clone());
+        } else {
+            log::info!(
+                "Reconciled image index of {project_name}: {} added, {} removed, {} changed",
+                report.added.len(),
+                report.removed.len(),
+                report.changed.len()
+            );
+            self.save_index(project_name, &index)?;
+        }
+        Ok((index, report))
+    }
+
+    fn lock_loaded(&self) -> MutexGuard<'_, HashMap<String, ImageIndex>> {
+        self.loaded.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn lock_writes(&self) -> MutexGuard<'_, ()> {
+        self.writes.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Walks a project's `images/` directory and its folders at any depth, skipping hidden ones.
+    /// Returns `(full_path, relative_name)` where relative_name is e.g. `"file.jpg"` or
+    /// `"Building A/Floor 2/file.jpg"`.
+    pub fn scan_images_dir(images_dir: &Path) -> AppResult<Vec<(PathBuf, String)>> {
+        let mut result = Vec::new();
+        let walker = WalkDir::new(images_dir)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|entry| !Self::is_hidden(entry.file_name()));
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) if e.depth() == 0 => {
+                    return Err(AppError::io(images_dir.display(), e.into()));
+                }
+                Err(e) => {
+                    log::warn!("Skipping unreadable item in {}: {e}", images_dir.display());
+                    continue;
+                }
+            };
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let Ok(rel_path) = entry.path().strip_prefix(images_dir) else {
+                continue;
+            };
+            let rel_name = rel_path
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            result.push((entry.into_path(), rel_name));
+        }
+        Ok(result)
+    }
+
+    fn is_hidden(file_name: &OsStr) -> bool {
+        file_name.to_string_lossy().starts_with('.')
+    }
+
+    /// Stats `files`, the `(full_path, relative_name)` pairs on disk, and hashes the ones that
+    /// aren't in `index` yet or whose size or modification time changed
+    fn read_disk_images(index: &ImageIndex, files: &[(PathBuf, String)]) -> Vec<DiskImage> {
+        let mut disk_images = Vec::new();
+        for (path, name) in files {
+            let Ok(metadata) = path.metadata() else {
+                continue;
+            };
+            let size_bytes = metadata.len();
+            let modified_at = Self::modified_millis(&metadata);
+            let content_hash = match index.images.get(name) {
+                Some(entry) if Self::is_unchanged(entry, size_bytes, modified_at) => None,
+                _ => match Self::hash_file(path) {
+                    Ok(content_hash) => Some(content_hash),
+                    Err(e) => {
+                        log::warn!("Failed to hash {name}: {e}");
+                        continue;
+                    }
+                },
+            };
+            disk_images.push(DiskImage {
+                name: name.clone(),
+                size_bytes,
+                modified_at,
+                content_hash,
+            });
+        }
+        disk_images
+    }
+
+    /// Updates `index` to match `disk_images`: adds new images, replaces edited ones and drops
+    /// entries whose files are gone. Changed images lose what was derived from their old content.
+    /// Entries that changed since the walk, e.g. images imported meanwhile, keep what the app
+    /// recorded for them.
+    fn sync_with_disk_images(
+        index: &mut ImageIndex,
+        disk_images: Vec<DiskImage>,
+        images_dir: &Path,
+    ) -> ReconcileReport {
+        let mut report = ReconcileReport::default();
+        let present: HashSet<&str> = disk_images.iter().map(|d| d.name.as_str()).collect();
+        index.images.retain(|name, _| {
+            let keep = present.contains(name.as_str()) || images_dir.join(name).is_file();
+            if !keep {
+                report.removed.push(name.clone());
+            }
+            keep
+        });
+
+        for disk_image in disk_images {
+            let DiskImage {
+                name,
+                size_bytes,
+                modified_at,
+                content_hash,
+            } = disk_image;
+            let existing = index.images.get_mut(&name);
+            match (existing, content_hash) {
+                (Some(entry), _) if Self::is_unchanged(entry, size_bytes, modified_at) => {
+                    // Entries from before modification times were indexed just gain one
+                    entry.modified_at = modified_at;
+                }
+                // Touched but not edited
+                (Some(entry), Some(content_hash)) if entry.content_hash == content_hash => {
+                    entry.size_bytes = size_bytes;
+                    entry.modified_at = modified_at;
+                }
+                (Some(_), Some(content_hash)) => {
+                    report.changed.push(name.clone());
+                    index.images.insert(
+                        name,
+                        ImageIndexEntry::new(content_hash, size_bytes, modified_at),
+                    );
+                }
+                (None, Some(content_hash)) => {
+                    report.added.push(name.clone());
+                    index.images.insert(
+                        name,
+                        ImageIndexEntry::new(content_hash, size_bytes, modified_at),
+                    );
+                }
+                // Changed or removed by the app since the walk compared it
+                (_, None) => {}
+            }
+        }
+        report
+    }
+
+    /// Whether a file with this size and modification time still has the entry's content
+    fn is_unchanged(entry: &ImageIndexEntry, size_bytes: u64, modified_at: Option<u64>) -> bool {
+        entry.size_bytes == size_bytes
+            && (entry.modified_at.is_none() || entry.modified_at == modified_at)
+    }
+
+    /// Modification time in milliseconds since the Unix epoch, if the platform has one
+    pub fn modified_millis(metadata: &fs::Metadata) -> Option<u64> {
+        let modified = metadata.modified().ok()?;
+        let since_epoch = modified.duration_since(UNIX_EPOCH).ok()?;
+        Some(since_epoch.as_millis() as u64)
+    }
+
+    /// Stores the hashes and metadata read while generating previews.
+    /// Entries whose content hash no longer matches the preview are replaced.
+    pub async fn record_previews(
+        &self,
+        project_name: &str,
+        previews: &[ImagePreviewModel],
+    ) -> AppResult<()> {
+        let images_dir = self
+            .app_save
+            .get_full_path(&format!("projects/{project_name}/images"));
+        self.update_index(project_name, |index| {
+            let mut changed = false;
+            for preview in previews {
+                let (Some(content_hash), Some(perceptual_hash)) =
+                    (&preview.content_hash, &preview.perceptual_hash)
+                else {
+                    continue;
+                };
+                match index.images.get_mut(&preview.image_name) {
+                    Some(entry) if entry.content_hash == *content_hash => {
+                        if entry.perceptual_hash.as_ref() != Some(perceptual_hash) {
+                            entry.perceptual_hash = Some(perceptual_hash.clone());
+                            changed = true;
+                        }
+                        if entry.width != Some(preview.width)
+                            || entry.height != Some(preview.height)
+                        {
+                            entry.width = Some(preview.width);
+                            entry.height = Some(preview.height);
+                            changed = true;
+                        }
+                        if entry.metadata.is_none() && preview.metadata.is_some() {
+                            entry.metadata = preview.metadata.clone();
+                            changed = true;
+                        }
+                    }
+                    _ => {
+                        // The image may have been deleted while its preview was generated
+                        let Ok(metadata) = images_dir.join(&preview.image_name).metadata() else {
+                            continue;
+                        };
+                        let modified_at = Self::modified_millis(&metadata);
+                        index.images.insert(
+                            preview.image_name.clone(),
+                            ImageIndexEntry {
+                                perceptual_hash: Some(perceptual_hash.clone()),
+                                width: Some(preview.width),
+                                height: Some(preview.height),
+                                metadata: preview.metadata.clone(),
+                                ..ImageIndexEntry::new(
+                                    content_hash.clone(),
+                                    preview.image_size_bytes,
+                                    modified_at,
+                                )
+                            },
+                        );
+                        changed = true;
+                    }
+                }
+            }
+            changed
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Renames index entries when images are moved between folders
+    pub async fn rename_images(
+        &self,
+        project_name: &str,
+        renames: &[(String, String)],
+    ) -> AppResult<()> {
+        self.update_index(project_name, |index| {
+            for (old, new) in renames {
+                if let Some(entry) = index.images.remove(old) {
+                    index.images.insert(new.clone(), entry);
+                }
+            }
+            true
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Adds the entries of imported images, replacing those of images they overwrote. An entry
+    /// recorded for the same content meanwhile, e.g. by a preview, is kept and only gains what
+    /// the import read.
+    pub async fn add_images(
+        &self,
+        project_name: &str,
+        images: Vec<(String, ImageIndexEntry)>,
+    ) -> AppResult<()> {
+        if images.is_empty() {
+            return Ok(());
+        }
+        self.update_index(project_name, |index| {
+            for (name, entry) in images {
+                match index.images.get_mut(&name) {
+                    Some(existing) if existing.content_hash == entry.content_hash => {
+                        existing.metadata = existing.metadata.take().or(entry.metadata);
+                        existing.source_hash = existing.source_hash.take().or(entry.source_hash);
+                    }
+                    _ => {
+                        index.images.insert(name, entry);
+                    }
+                }
+            }
+            true
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Removes index entries for deleted images
+    pub async fn remove_images(&self, project_name: &str, image_names: &[String]) -> AppResult<()> {
+        if image_names.is_empty() {
+            return Ok(());
+        }
+        self.update_index(project_name, |index| {
+            for name in image_names {
+                index.images.remove(name);
+            }
+            true
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// Returns the hex encoded BLAKE3 hash of a file's contents
+    pub fn hash_file(path: &Path) -> AppResult<String> {
+        let file = File::open(path).map_err(|e| AppError::io(path.display(), e))?;
+        let mut hasher = blake3::Hasher::new();
+        hasher
+            .update_reader(BufReader::new(file))
+            .map_err(|e| AppError::io(path.display(), e))?;
+        Ok(hasher.finalize().to_hex().to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    static NEXT_TEST_DIR: AtomicUsize = AtomicUsize::new(0);
+
+    /// An index component saving into its own temp dir, removed when dropped
+    struct TestIndex {
+        save_dir: PathBuf,
+        images_dir: PathBuf,
+        component: ImageIndexComponent,
+    }
+
+    impl TestIndex {
+        fn new(test_name: &str) -> Self {
+            let save_dir = std::env::temp_dir().join(format!(
+                "get-crap-done-index-{test_name}-{}-{}",
+                std::process::id(),
+                NEXT_TEST_DIR.fetch_add(1, Ordering::Relaxed)
+            ));
+            let images_dir = save_dir.join("projects/p/images");
+            fs::create_dir_all(&images_dir).unwrap();
+            let component =
+                ImageIndexComponent::new(Arc::new(AppSaveService::new(save_dir.clone())));
+            Self {
+                save_dir,
+                images_dir,
+                component,
+            }
+        }
+
+        fn write_image(&self, name: &str, contents: &[u8]) {
+            let path = self.images_dir.join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, contents).unwrap();
+        }
+
+        fn saved_index(&self) -> ImageIndex {
+            self.component
+                .app_save
+                .read_json("projects/p/image_index.json")
+                .unwrap()
+        }
+    }
+
+    impl Drop for TestIndex {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.save_dir);
+        }
+    }
+
+    #[tokio::test]
+    async fn reconcile_picks_up_added_removed_and_renamed_files() {
+        let test = TestIndex::new("reconcile");
+        test.write_image("kept.jpg", b"kept");
+        test.write_image("removed.jpg", b"removed");
+        test.write_image("old name.jpg", b"renamed");
+        let index = test.component.read_index("p").await.unwrap();
+        assert_eq!(index.images.len(), 3);
+
+        test.write_image("Floor 2/added.jpg", b"added");
+        fs::remove_file(test.images_dir.join("removed.jpg")).unwrap();
+        fs::rename(
+            test.images_dir.join("old name.jpg"),
+            test.images_dir.join("new name.jpg"),
+        )
+        .unwrap();
+        let mut report = test.component.reconcile("p").await.unwrap();
+        report.added.sort();
+        report.removed.sort();
+
+        assert_eq!(report.added, vec!["Floor 2/added.jpg", "new name.jpg"]);
+        assert_eq!(report.removed, vec!["old name.jpg", "removed.jpg"]);
+        assert!(report.changed.is_empty());
+        let names: Vec<String> = test.saved_index().images.into_keys().collect();
+        assert_eq!(names, vec!["Floor 2/added.jpg", "kept.jpg", "new name.jpg"]);
+        let renamed = &test.component.read_index("p").await.unwrap().images["new name.jpg"];
+        assert_eq!(
+            renamed.content_hash,
+            blake3::hash(b"renamed").to_hex().to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn reconcile_keeps_changes_made_during_the_walk() {
+        let test = TestIndex::new("concurrent");
+        test.write_image("photo.jpg", b"photo");
+        test.component.read_index("p").await.unwrap();
+        test.write_image("added.jpg", b"added");
+
+        let (saved, disk_images) = test.component.walk_disk("p").await.unwrap();
+        // Recorded while the walk was hashing, like a preview stream would
+        test.component
+            .update_index("p", |index| {
+                index.images.get_mut("photo.jpg").unwrap().perceptual_hash =
+                    Some("00ff00ff00ff00ff".to_string());
+                true
+            })
+            .await
+            .unwrap();
+        // Imported after the walk passed it
+        test.write_image("imported.jpg", b"imported");
+        test.component
+            .update_index("p", |index| {
+                index.images.insert(
+                    "imported.jpg".to_string(),
+                    ImageIndexEntry::new(blake3::hash(b"imported").to_hex().to_string(), 8, None),
+                );
+                true
+            })
+            .await
+            .unwrap();
+        let (_, report) = test
+            .component
+            .apply_disk_images("p", saved, disk_images)
+            .unwrap();
+
+        assert_eq!(report.added, vec!["added.jpg"]);
+        assert!(report.removed.is_empty());
+        for index in [
+            test.saved_index(),
+            test.component.read_index("p").await.unwrap(),
+        ] {
+            assert_eq!(
+                index.images["photo.jpg"].perceptual_hash.as_deref(),
+                Some("00ff00ff00ff00ff")
+            );
+            assert!(index.images.contains_key("imported.jpg"));
+            assert!(index.images.contains_key("added.jpg"));
+        }
+    }
+
+    #[tokio::test]
+    async fn imported_images_keep_what_was_recorded_meanwhile() {
+        let test = TestIndex::new("add");
+        test.write_image("photo.jpg", b"photo");
+        test.component.read_index("p").await.unwrap();
+        test.write_image("new.jpg", b"new");
+        let new_entry = ImageIndexEntry::new(blake3::hash(b"new").to_hex().to_string(), 3, None);
+        // Previews generated while the import was running
+        test.component
+            .update_index("p", |index| {
+                index.images.get_mut("photo.jpg").unwrap().perceptual_hash =
+                    Some("00ff00ff00ff00ff".to_string());
+                index.images.insert(
+                    "new.jpg".to_string(),
+                    ImageIndexEntry {
+                        width: Some(4),
+                        ..new_entry.clone()
+                    },
+                );
+                true
+            })
+            .await
+            .unwrap();
+
+        let imported = ImageIndexEntry {
+            source_hash: Some("source".to_string()),
+            ..new_entry
+        };
+        test.component
+            .add_images("p", vec![("new.jpg".to_string(), imported)])
+            .await
+            .unwrap();
+
+        let index = test.saved_index();
+        assert_eq!(
+            index.images["photo.jpg"].perceptual_hash.as_deref(),
+            Some("00ff00ff00ff00ff")
+        );
+        assert_eq!(index.images["new.jpg"].width, Some(4));
+        assert_eq!(
+            index.images["new.jpg"].source_hash.as_deref(),
+            Some("source")
+        );
+    }
+}
@@ -1,24 +1,110 @@
-use std::{collections::HashMap, fs, fs::File, io::BufReader, io::Cursor, path::PathBuf, sync::Arc};
+use std::{
+    collections::{BTreeSet, HashMap},
+    ffi::OsStr,
+    fs,
+    fs::File,
+    future::Future,
+    io::BufReader,
+    io::Cursor,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::UNIX_EPOCH,
+};
 
-use base64::{engine::general_purpose::STANDARD, Engine};
 use fast_image_resize::{images::Image, ResizeAlg, ResizeOptions, Resizer};
-use futures::future::join_all;
-use image::{codecs::jpeg::JpegEncoder, DynamicImage, GenericImageView, ImageFormat, ImageReader};
+use futures::{future::join_all, stream::FuturesUnordered, FutureExt, StreamExt};
+use image::{
+    codecs::jpeg::JpegEncoder, imageops::FilterType, metadata::Orientation, DynamicImage,
+    GenericImageView, ImageFormat, ImageReader,
+};
 use tokio::sync::{RwLock, Semaphore};
+use tokio_util::sync::CancellationToken;
 use turbojpeg::Decompressor;
-
-use crate::services::{app_save_service::AppSaveService, projects_service::models::*};
+use walkdir::WalkDir;
+
+use crate::{
+    app_error::{AppError, AppResult},
+    services::{
+        app_save_service::AppSaveService,
+        projects_service::{
+            components::{
+                archive_extractor::{self, ExtractedArchive},
+                full_image_cache::{
+                    FullImageCacheComponent, ImageBytes, DEFAULT_FULL_IMAGE_CACHE_BYTES,
+                },
+                image_decoders,
+                image_index::{ImageIndex, ImageIndexComponent, ImageIndexEntry},
+                image_metadata::read_image_metadata,
+                import_journal::ImportJournal,
+                thumbnail_cache::ThumbnailCacheComponent,
+            },
+            models::*,
+            names::{FolderName, ImageRelPath},
+            requests::{ImportConflictPolicy, PreviewSortKey, RequestPreviewPage},
+        },
+    },
+};
 
 /// Max dimension for preview thumbnails
 const PREVIEW_MAX_SIZE: u32 = 200;
 /// Max number of images decoded concurrently to avoid OOM
 const MAX_CONCURRENT_PREVIEWS: usize = 4;
+/// Number of hash characters appended by `ImportConflictPolicy::RenameByHash`
+const HASH_SUFFIX_LEN: usize = 8;
+/// Grayscale grid a difference hash is computed from. One extra column gives 8 comparisons per row.
+const DHASH_WIDTH: u32 = 9;
+const DHASH_HEIGHT: u32 = 8;
+/// JPEG quality of transcoded images and of the upright copies sent to the evaluator,
+/// high enough to keep small text legible
+const TRANSCODE_JPEG_QUALITY: u8 = 95;
+/// Memory budget of the full image cache, saved once it was changed
+const CACHE_CAPACITY_PATH: &str = "image_cache_capacity.json";
 /// Cache key format: "project_name/image_name"
 pub type ImageCacheKey = String;
+
+/// What happened to a single file during an import
+enum ImportOutcome {
+    Imported(String),
+    Overwritten(String),
+    Renamed(String),
+    /// Holds the name of the existing image with the same content
+    Duplicate(String),
+    Skipped(String),
+}
+
+/// How an import handles name conflicts and formats the app can't show directly
+#[derive(Clone, Copy, Default)]
+pub struct ImportOptions {
+    pub policy: ImportConflictPolicy,
+    /// Store HEIF and RAW files as upright JPEGs
+    pub transcode_to_jpeg: bool,
+}
+
+/// Lets the caller follow an import and stop it halfway
+pub struct ImportSession<'a> {
+    pub import_id: &'a str,
+    /// Cancelling rolls back everything the import copied so far
+    pub cancel: &'a CancellationToken,
+    /// Called after each source file is handled
+    pub on_progress: &'a (dyn Fn(ImportProgress) + Send + Sync),
+}
+
+/// A freshly generated thumbnail along with facts about the source image
+struct GeneratedPreview {
+    /// JPEG encoded thumbnail
+    jpeg: Vec<u8>,
+    width: u32,
+    height: u32,
+    perceptual_hash: String,
+}
+
 pub struct ImageLoaderComponent {
     app_save: Arc<AppSaveService>,
-    /// Cache for full resolution images
-    full_image_cache: RwLock<HashMap<ImageCacheKey, FullImageModel>>,
+    image_index: Arc<ImageIndexComponent>,
+    /// Previews saved to disk, checked before generating a new one
+    thumbnails: Arc<ThumbnailCacheComponent>,
+    /// Byte-budgeted LRU cache for full resolution images
+    full_image_cache: FullImageCacheComponent,
     /// Cache for image previews
     preview_cache: RwLock<HashMap<ImageCacheKey, ImagePreviewModel>>,
     /// Limits how many images are decoded in parallel
@@ -26,10 +112,13 @@ pub struct ImageLoaderComponent {
 }
 
 impl ImageLoaderComponent {
-    pub fn new(app_save: Arc<AppSaveService>) -> Self {
+    pub fn new(app_save: Arc<AppSaveService>, image_index: Arc<ImageIndexComponent>) -> Self {
+        let cache_capacity = Self::read_cache_capacity(&app_save);
         Self {
+            thumbnails: Arc::new(ThumbnailCacheComponent::new(app_save.clone())),
             app_save,
-            full_image_cache: RwLock::new(HashMap::new()),
+            image_index,
+            full_image_cache: FullImageCacheComponent::new(cache_capacity),
             preview_cache: RwLock::new(HashMap::new()),
             preview_semaphore: Arc::new(Semaphore::new(MAX_CONCURRENT_PREVIEWS)),
         }
@@ -44,7 +133,7 @@ impl ImageLoaderComponent {
         &self,
         project_name: &str,
         image_names: Vec<String>,
-    ) -> Result<(), String> {
+    ) -> AppResult<()> {
         for image_name in &image_names {
             let image_path = format!("projects/{project_name}/images/{image_name}");
             self.app_save.delete_file(&image_path)?;
@@ -55,42 +144,606 @@ impl ImageLoaderComponent {
                 let mut cache = self.preview_cache.write().await;
                 cache.remove(&key);
             }
-            {
-                let mut cache = self.full_image_cache.write().await;
-                cache.remove(&key);
-            }
+            self.full_image_cache.remove(&key);
         }
 
         Ok(())
     }
 
+    /// Copies the files at `image_paths` into the project root or into `folder`.
+    /// Files whose exact content is already somewhere in the project are reported as duplicates,
+    /// name conflicts with other images are resolved according to `options.policy`, and files
+    /// that can't be imported are reported as skipped instead of failing the whole import.
+    /// With `options.transcode_to_jpeg`, HEIF and RAW files are stored as upright JPEGs instead.
+    /// Thumbnails of the imported images are generated as part of the import.
     pub async fn import_images_to_project(
         &self,
         project_name: &str,
         image_paths: Vec<String>,
-        folder: Option<String>,
-    ) -> Result<(), String> {
-        let folder_prefix = folder
-            .as_deref()
-            .filter(|f| !f.is_empty())
-            .map(|f| format!("{f}/"))
-            .unwrap_or_default();
+        folder: Option<&str>,
+        options: ImportOptions,
+        session: &ImportSession<'_>,
+    ) -> AppResult<ImportReport> {
+        let folder_prefix = folder.map(|f| format!("{f}/")).unwrap_or_default();
+        let files = image_paths
+            .into_iter()
+            .map(|image_path| (image_path, folder_prefix.clone()))
+            .collect();
+        self.import_files(
+            project_name,
+            files,
+            options,
+            ImportReport::default(),
+            session,
+        )
+        .await
+    }
 
-        for image_path in image_paths {
-            let source_path = std::path::Path::new(&image_path);
-            let file_name = source_path
-                .file_name()
-                .ok_or_else(|| format!("Invalid file path: {image_path}"))?
-                .to_string_lossy();
-            let new_image_path =
-                format!("projects/{project_name}/images/{folder_prefix}{file_name}");
-            self.app_save.copy_file(&image_path, &new_image_path)?;
+    /// Walks `directory` recursively and imports every supported image in it into the project
+    /// root or `folder`. Hidden files and folders are ignored.
+    ///
+    /// With `preserve_structure`, images in subdirectories go into nested project folders with
+    /// the same path, e.g. `DCIM/100APPLE`, inside `folder` if given. Folders are only created
+    /// for subdirectories that contain images.
+    pub async fn import_directory_to_project(
+        &self,
+        project_name: &str,
+        directory: &Path,
+        folder: Option<&str>,
+        preserve_structure: bool,
+        options: ImportOptions,
+        session: &ImportSession<'_>,
+    ) -> AppResult<ImportReport> {
+        if !directory.is_dir() {
+            return Err(AppError::NotFound(format!(
+                "{} is not a directory",
+                directory.display()
+            )));
+        }
+        let mut report = ImportReport::default();
+        let mut files = Vec::new();
+        let mut target_folders = BTreeSet::new();
+
+        let walker = WalkDir::new(directory)
+            .sort_by_file_name()
+            .into_iter()
+            .filter_entry(|entry| entry.depth() == 0 || !Self::is_hidden(entry.file_name()));
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    let source_path = e.path().unwrap_or(directory).display().to_string();
+                    report.skipped.push(SkippedImport {
+                        source_path,
+                        reason: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            if !entry.file_type().is_file() || !image_decoders::is_supported_image(entry.path()) {
+                continue;
+            }
+            let source_path = entry.path().display().to_string();
+            let target_folder = if preserve_structure {
+                let subdirectory = entry
+                    .path()
+                    .parent()
+                    .and_then(|parent| parent.strip_prefix(directory).ok())
+                    .unwrap_or(Path::new(""));
+                match Self::folder_for_subdirectory(folder, subdirectory) {
+                    Ok(target_folder) => target_folder,
+                    Err(e) => {
+                        report.skipped.push(SkippedImport {
+                            source_path,
+                            reason: e.to_string(),
+                        });
+                        continue;
+                    }
+                }
+            } else {
+                folder.map(str::to_string)
+            };
+            let folder_prefix = match target_folder {
+                Some(target_folder) => {
+                    let prefix = format!("{target_folder}/");
+                    target_folders.insert(target_folder);
+                    prefix
+                }
+                None => String::new(),
+            };
+            files.push((source_path, folder_prefix));
+        }
+
+        report.folders = target_folders.into_iter().collect();
+        self.import_files(project_name, files, options, report, session)
+            .await
+    }
+
+    /// Imports the supported images in a zip archive into the project root or `folder`.
+    /// They are extracted to `.zip-import/` first, see `archive_extractor::extract_images` for
+    /// the safety limits. Progress is reported per archive entry while extracting, and
+    /// cancelling stops the extraction too. Reports list images by their path inside the archive.
+    pub async fn import_zip_to_project(
+        &self,
+        project_name: &str,
+        archive_path: &Path,
+        folder: Option<&str>,
+        options: ImportOptions,
+        session: &ImportSession<'_>,
+    ) -> AppResult<ImportReport> {
+        let extract_dir = self
+            .app_save
+            .get_full_path(&format!("projects/{project_name}/.zip-import"));
+
+        // Extracting can take minutes for a big archive, so it runs on a blocking thread that
+        // sends its progress back as it goes
+        let (progress_tx, mut progress_rx) = tokio::sync::mpsc::unbounded_channel();
+        let (archive, dest_dir, cancel) = (
+            archive_path.to_path_buf(),
+            extract_dir.clone(),
+            session.cancel.clone(),
+        );
+        let import_id = session.import_id.to_string();
+        let mut extraction = tokio::task::spawn_blocking(move || {
+            // Left behind if the app was closed during an earlier import
+            let _ = fs::remove_dir_all(&dest_dir);
+            archive_extractor::extract_images(&archive, &dest_dir, &cancel, |progress| {
+                let _ = progress_tx.send(ImportProgress {
+                    import_id: import_id.clone(),
+                    index: progress.index,
+                    total: progress.total,
+                    file_name: progress.entry_name,
+                    bytes: progress.bytes,
+                    bytes_done: progress.bytes_done,
+                    total_bytes: progress.total_bytes,
+                    extracting: true,
+                });
+            })
+        });
+        let extracted = loop {
+            tokio::select! {
+                extracted = &mut extraction => break extracted,
+                Some(progress) = progress_rx.recv() => (session.on_progress)(progress),
+            }
+        };
+        while let Ok(progress) = progress_rx.try_recv() {
+            (session.on_progress)(progress);
         }
 
+        let result = match extracted {
+            Ok(Ok(Some(extracted))) => {
+                self.import_extracted(project_name, extracted, folder, options, session)
+                    .await
+            }
+            Ok(Ok(None)) => Ok(ImportReport {
+                cancelled: true,
+                ..Default::default()
+            }),
+            Ok(Err(e)) => Err(e),
+            Err(e) => Err(AppError::Internal(format!(
+                "Archive extraction failed: {e}"
+            ))),
+        };
+        let removed = tokio::task::spawn_blocking(move || fs::remove_dir_all(&extract_dir)).await;
+        if let Ok(Err(e)) = removed {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                log::warn!("Failed to remove the extracted archive: {e}");
+            }
+        }
+        result
+    }
+
+    async fn import_extracted(
+        &self,
+        project_name: &str,
+        extracted: ExtractedArchive,
+        folder: Option<&str>,
+        options: ImportOptions,
+        session: &ImportSession<'_>,
+    ) -> AppResult<ImportReport> {
+        let folder_prefix = folder.map(|f| format!("{f}/")).unwrap_or_default();
+        let report = ImportReport {
+            skipped: extracted.skipped,
+            folders: folder.map(str::to_string).into_iter().collect(),
+            ..Default::default()
+        };
+        let mut source_paths = HashMap::new();
+        let files = extracted
+            .images
+            .into_iter()
+            .map(|image| {
+                let path = image.path.display().to_string();
+                source_paths.insert(path.clone(), image.source_path);
+                (path, folder_prefix.clone())
+            })
+            .collect();
+
+        let mut report = self
+            .import_files(project_name, files, options, report, session)
+            .await?;
+        let reported_paths = report
+            .renamed
+            .iter_mut()
+            .map(|r| &mut r.source_path)
+            .chain(report.skipped.iter_mut().map(|s| &mut s.source_path))
+            .chain(report.duplicates.iter_mut().map(|d| &mut d.source_path));
+        for source_path in reported_paths {
+            if let Some(original) = source_paths.remove(source_path.as_str()) {
+                *source_path = original;
+            }
+        }
+        Ok(report)
+    }
+
+    /// Project folder for images found in `subdirectory` of an imported directory: the same nested
+    /// path below `base_folder`, or `base_folder` itself at the top level
+    fn folder_for_subdirectory(
+        base_folder: Option<&str>,
+        subdirectory: &Path,
+    ) -> AppResult<Option<String>> {
+        let parts: Vec<String> = base_folder
+            .map(str::to_string)
+            .into_iter()
+            .chain(
+                subdirectory
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy().to_string()),
+            )
+            .collect();
+        if parts.is_empty() {
+            return Ok(None);
+        }
+        let folder = FolderName::parse(&parts.join("/"))?;
+        Ok(Some(folder.to_string()))
+    }
+
+    fn is_hidden(file_name: &OsStr) -> bool {
+        file_name.to_string_lossy().starts_with('.')
+    }
+
+    /// Imports `(source_path, folder_prefix)` pairs, adding their outcomes to `report`.
+    /// Missing target folders are created. Progress is reported after every file, and a cancelled
+    /// import is rolled back and returns an empty report marked as cancelled.
+    async fn import_files(
+        &self,
+        project_name: &str,
+        files: Vec<(String, String)>,
+        options: ImportOptions,
+        mut report: ImportReport,
+        session: &ImportSession<'_>,
+    ) -> AppResult<ImportReport> {
+        let images_path = format!("projects/{project_name}/images");
+        let mut index = self.image_index.read_index(project_name).await?;
+
+        let source_paths: Vec<String> = files.iter().map(|(path, _)| path.clone()).collect();
+        let sizes: Vec<u64> = tokio::task::spawn_blocking(move || {
+            source_paths
+                .iter()
+                .map(|image_path| {
+                    fs::metadata(image_path)
+                        .map(|m| m.len())
+                        .unwrap_or_default()
+                })
+                .collect()
+        })
+        .await
+        .map_err(|e| AppError::Internal(e.to_string()))?;
+        let total = files.len();
+        let total_bytes = sizes.iter().sum();
+        let mut bytes_done = 0;
+        let mut journal = ImportJournal::new(
+            self.app_save
+                .get_full_path(&format!("projects/{project_name}/.import")),
+        );
+        let mut thumbnail_jobs = FuturesUnordered::new();
+        let mut thumbnails = Vec::new();
+        // Only these are added to the project's index once the import is committed, it may have
+        // changed since `index` was read
+        let mut added_names = Vec::new();
+
+        for (i, ((image_path, folder_prefix), bytes)) in files.into_iter().zip(sizes).enumerate() {
+            if session.cancel.is_cancelled() {
+                return Ok(self.roll_back_import(project_name, journal).await);
+            }
+            let folder_path = self
+                .app_save
+                .get_full_path(&format!("{images_path}/{folder_prefix}"));
+            // Hashing, reading metadata and copying block, so each file is imported on a blocking
+            // thread that hands the index and journal back when it's done
+            let app_save = self.app_save.clone();
+            let project = project_name.to_string();
+            let (source_path, folder) = (image_path.clone(), folder_prefix.clone());
+            let mut import = tokio::task::spawn_blocking(move || {
+                let outcome = journal.ensure_dir(&folder_path).and_then(|_| {
+                    Self::import_image(
+                        &app_save,
+                        &project,
+                        &source_path,
+                        &folder,
+                        options,
+                        &mut index,
+                        &mut journal,
+                    )
+                });
+                (outcome, index, journal)
+            });
+            // Lets waiting thumbnails start while the file is copied
+            let imported = loop {
+                tokio::select! {
+                    imported = &mut import => break imported,
+                    Some(thumbnail) = thumbnail_jobs.next() => thumbnails.push(thumbnail),
+                }
+            };
+            let outcome;
+            (outcome, index, journal) =
+                imported.map_err(|e| AppError::Internal(format!("Import task failed: {e}")))?;
+            if let Ok(
+                ImportOutcome::Imported(image_name)
+                | ImportOutcome::Overwritten(image_name)
+                | ImportOutcome::Renamed(image_name),
+            ) = &outcome
+            {
+                let path = self
+                    .app_save
+                    .get_full_path(&format!("{images_path}/{image_name}"));
+                let entry = index.images.get(image_name);
+                thumbnail_jobs.push(self.preview_job(
+                    project_name,
+                    path,
+                    image_name.clone(),
+                    entry,
+                ));
+                added_names.push(image_name.clone());
+            }
+
+            bytes_done += bytes;
+            (session.on_progress)(ImportProgress {
+                import_id: session.import_id.to_string(),
+                index: i,
+                total,
+                file_name: Path::new(&image_path)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| image_path.clone()),
+                bytes,
+                bytes_done,
+                total_bytes,
+                extracting: false,
+            });
+
+            match outcome {
+                Ok(ImportOutcome::Imported(image_name)) => report.imported.push(image_name),
+                Ok(ImportOutcome::Overwritten(image_name)) => {
+                    report.imported.push(image_name.clone());
+                    report.overwritten.push(image_name);
+                }
+                Ok(ImportOutcome::Renamed(image_name)) => report.renamed.push(RenamedImport {
+                    source_path: image_path,
+                    image_name,
+                }),
+                Ok(ImportOutcome::Duplicate(existing_image_name)) => {
+                    report.duplicates.push(DuplicateImport {
+                        source_path: image_path,
+                        existing_image_name,
+                    })
+                }
+                Ok(ImportOutcome::Skipped(reason)) => report.skipped.push(SkippedImport {
+                    source_path: image_path,
+                    reason,
+                }),
+                Err(e) => report.skipped.push(SkippedImport {
+                    source_path: image_path,
+                    reason: e.to_string(),
+                }),
+            }
+        }
+
+        // The import isn't done until every thumbnail is, so it can still be cancelled here
+        loop {
+            tokio::select! {
+                biased;
+                _ = session.cancel.cancelled() => {
+                    return Ok(self.roll_back_import(project_name, journal).await);
+                }
+                thumbnail = thumbnail_jobs.next() => match thumbnail {
+                    Some(thumbnail) => thumbnails.push(thumbnail),
+                    None => break,
+                },
+            }
+        }
+        journal.commit();
+
+        for (preview, _) in thumbnails.iter().flatten() {
+            if let Some(entry) = index.images.get_mut(&preview.image_name) {
+                if preview.content_hash.as_ref() == Some(&entry.content_hash) {
+                    entry.perceptual_hash = preview.perceptual_hash.clone();
+                }
+            }
+        }
+        let added = added_names
+            .into_iter()
+            .filter_map(|name| {
+                let entry = index.images.remove(&name)?;
+                Some((name, entry))
+            })
+            .collect();
+        self.image_index.add_images(project_name, added).await?;
+
         // Clear preview cache for this project to ensure new images are loaded
         self.clear_project_cache(project_name).await;
+        self.cache_new_previews(thumbnails).await;
 
-        Ok(())
+        Ok(report)
+    }
+
+    /// Undoes a cancelled import along with the thumbnails generated for its images, and returns
+    /// the empty report of a cancelled import
+    async fn roll_back_import(&self, project_name: &str, journal: ImportJournal) -> ImportReport {
+        tokio::task::spawn_blocking(move || journal.roll_back())
+            .await
+            .unwrap_or_else(|e| log::warn!("Import rollback failed: {e}"));
+        // The index isn't saved until an import is committed, so it still lists only the images
+        // the project had before
+        match self.list_image_files(project_name).await {
+            Ok(files) => self.thumbnails.prune(project_name, &files),
+            Err(e) => log::warn!("Failed to remove thumbnails of a cancelled import: {e}"),
+        }
+        ImportReport {
+            cancelled: true,
+            ..Default::default()
+        }
+    }
+
+    /// Imports a single file, resolving a name conflict according to `options.policy`.
+    /// `index` is updated with every image copied into the project, `journal` with every file.
+    /// Blocks while the file is hashed and copied.
+    fn import_image(
+        app_save: &AppSaveService,
+        project_name: &str,
+        image_path: &str,
+        folder_prefix: &str,
+        options: ImportOptions,
+        index: &mut ImageIndex,
+        journal: &mut ImportJournal,
+    ) -> AppResult<ImportOutcome> {
+        let source_path = Path::new(image_path);
+        let source_file_name = source_path
+            .file_name()
+            .ok_or_else(|| AppError::InvalidName(format!("Invalid file path: {image_path}")))?
+            .to_string_lossy();
+        let transcode = options.transcode_to_jpeg && image_decoders::needs_transcoding(source_path);
+        let file_name = if transcode {
+            Path::new(&*source_file_name)
+                .with_extension("jpg")
+                .to_string_lossy()
+                .to_string()
+        } else {
+            source_file_name.to_string()
+        };
+        // The source file name becomes part of a project path, so it gets the same checks
+        let rel_name = ImageRelPath::parse(&format!("{folder_prefix}{file_name}"))?;
+
+        let content_hash = ImageIndexComponent::hash_file(source_path)?;
+        if let Some(existing) = index.find_by_hash(&content_hash) {
+            return Ok(ImportOutcome::Duplicate(existing.to_string()));
+        }
+        let size_bytes = source_path
+            .metadata()
+            .map_err(|e| AppError::io(image_path, e))?
+            .len();
+        // Read before a conflict can rename the file, so the original name is kept
+        let image_metadata = read_image_metadata(source_path, Some(&source_file_name));
+
+        // A transcoded image is indexed by its own content, the source hash still catches
+        // the same file being imported again
+        let transcoded = if transcode {
+            Some(Self::encode_upright_jpeg(source_path)?)
+        } else {
+            None
+        };
+        let (stored_hash, stored_size, source_hash) = match &transcoded {
+            Some(jpeg) => (
+                blake3::hash(jpeg).to_hex().to_string(),
+                jpeg.len() as u64,
+                Some(content_hash.clone()),
+            ),
+            None => (content_hash.clone(), size_bytes, None),
+        };
+
+        let images_base = format!("projects/{project_name}/images");
+        let exists = |name: &str| {
+            app_save
+                .get_full_path(&format!("{images_base}/{name}"))
+                .exists()
+        };
+        let mut copy_as = |name: &str| {
+            let dest_path = app_save.get_full_path(&format!("{images_base}/{name}"));
+            let temp_path = journal.temp_path()?;
+            let written = match &transcoded {
+                Some(jpeg) => fs::write(&temp_path, jpeg),
+                None => fs::copy(source_path, &temp_path).map(|_| ()),
+            };
+            if let Err(e) = written {
+                let _ = fs::remove_file(&temp_path);
+                return Err(AppError::io(image_path, e));
+            }
+            journal.place(&temp_path, &dest_path)?;
+            let modified_at = dest_path
+                .metadata()
+                .ok()
+                .and_then(|metadata| ImageIndexComponent::modified_millis(&metadata));
+            index.images.insert(
+                name.to_string(),
+                ImageIndexEntry {
+                    metadata: Some(image_metadata.clone()),
+                    source_hash: source_hash.clone(),
+                    ..ImageIndexEntry::new(stored_hash.clone(), stored_size, modified_at)
+                },
+            );
+            AppResult::Ok(())
+        };
+
+        if !exists(&rel_name) {
+            copy_as(&rel_name)?;
+            return Ok(ImportOutcome::Imported(rel_name.into()));
+        }
+
+        match options.policy {
+            ImportConflictPolicy::Skip => {
+                Ok(ImportOutcome::Skipped(format!("{rel_name} already exists")))
+            }
+            ImportConflictPolicy::Overwrite => {
+                let existing = app_save.get_full_path(&format!("{images_base}/{rel_name}"));
+                // Copying a file onto itself would truncate it
+                if fs::canonicalize(source_path).ok() == fs::canonicalize(&existing).ok() {
+                    return Ok(ImportOutcome::Skipped(format!(
+                        "{rel_name} is already in the project"
+                    )));
+                }
+                copy_as(&rel_name)?;
+                Ok(ImportOutcome::Overwritten(rel_name.into()))
+            }
+            // A suffix can push a name past the length limit, so renamed names are validated too
+            ImportConflictPolicy::AutoRename => {
+                let mut counter = 2u32;
+                let new_name = loop {
+                    let candidate = ImageRelPath::parse(&format!(
+                        "{folder_prefix}{}",
+                        Self::suffixed_file_name(&file_name, &counter.to_string())
+                    ))?;
+                    if !exists(&candidate) {
+                        break candidate;
+                    }
+                    counter += 1;
+                };
+                copy_as(&new_name)?;
+                Ok(ImportOutcome::Renamed(new_name.into()))
+            }
+            ImportConflictPolicy::RenameByHash => {
+                let new_name = ImageRelPath::parse(&format!(
+                    "{folder_prefix}{}",
+                    Self::suffixed_file_name(&file_name, &content_hash[..HASH_SUFFIX_LEN])
+                ))?;
+                if exists(&new_name) {
+                    return Ok(ImportOutcome::Skipped(format!("{new_name} already exists")));
+                }
+                copy_as(&new_name)?;
+                Ok(ImportOutcome::Renamed(new_name.into()))
+            }
+        }
+    }
+
+    /// `photo.jpg` + `2` -> `photo_2.jpg`
+    fn suffixed_file_name(file_name: &str, suffix: &str) -> String {
+        let path = Path::new(file_name);
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        match path.extension() {
+            Some(ext) => format!("{stem}_{suffix}.{}", ext.to_string_lossy()),
+            None => format!("{stem}_{suffix}"),
+        }
     }
 
     /// Clears cached image data for a specific project
@@ -101,59 +754,170 @@ impl ImageLoaderComponent {
             let mut cache = self.preview_cache.write().await;
             cache.retain(|k, _| !k.starts_with(&prefix));
         }
+        self.full_image_cache.remove_with_prefix(&prefix);
+    }
 
+    /// Drops the cached previews and full images of specific images, e.g. ones edited outside
+    /// the app
+    pub async fn invalidate_images(&self, project_name: &str, image_names: &[String]) {
+        let keys: Vec<ImageCacheKey> = image_names
+            .iter()
+            .map(|image_name| Self::cache_key(project_name, image_name))
+            .collect();
         {
-            let mut cache = self.full_image_cache.write().await;
-            cache.retain(|k, _| !k.starts_with(&prefix));
+            let mut cache = self.preview_cache.write().await;
+            for key in &keys {
+                cache.remove(key);
+            }
+        }
+        for key in &keys {
+            self.full_image_cache.remove(key);
         }
     }
 
-    /// Collects all image files from a directory and one level of subdirectories.
-    /// Returns `(full_path, relative_name)` where relative_name is e.g. `"file.jpg"` or `"folder/file.jpg"`.
-    fn collect_image_files(base_items: Vec<PathBuf>) -> Vec<(PathBuf, String)> {
-        let mut result = Vec::new();
-        for item in base_items {
-            if item.is_file() {
-                let name = item
-                    .file_name()
-                    .map(|n| n.to_string_lossy().to_string())
-                    .unwrap_or_default();
-                result.push((item, name));
-            } else if item.is_dir() {
-                let folder_name = match item.file_name() {
-                    Some(n) => n.to_string_lossy().to_string(),
-                    None => continue,
-                };
-                if let Ok(entries) = fs::read_dir(&item) {
-                    for entry in entries.flatten() {
-                        let path = entry.path();
-                        if path.is_file() {
-                            let file_name = path
-                                .file_name()
-                                .map(|n| n.to_string_lossy().to_string())
-                                .unwrap_or_default();
-                            let rel_name = format!("{folder_name}/{file_name}");
-                            result.push((path, rel_name));
-                        }
-                    }
+    /// Returns `(full_path, relative_name)` for every image in the project root and its folders,
+    /// as listed by the project's image index
+    pub async fn list_image_files(&self, project_name: &str) -> AppResult<Vec<(PathBuf, String)>> {
+        let index = self.image_index.read_index(project_name).await?;
+        Ok(index.image_files(&self.images_dir(project_name)))
+    }
+
+    fn images_dir(&self, project_name: &str) -> PathBuf {
+        self.app_save
+            .get_full_path(&format!("projects/{project_name}/images"))
+    }
+
+    /// Computes the perceptual hash of each image, skipping (and logging) images that can't be
+    /// decoded. Returns `(relative_name, hex_hash)` pairs.
+    pub async fn compute_perceptual_hashes(
+        &self,
+        files: Vec<(PathBuf, String)>,
+    ) -> Vec<(String, String)> {
+        let tasks: Vec<_> = files
+            .into_iter()
+            .map(|(path, image_name)| {
+                let sem = Arc::clone(&self.preview_semaphore);
+                async move {
+                    let _permit = sem
+                        .acquire()
+                        .await
+                        .map_err(|e| AppError::Internal(e.to_string()))?;
+                    let hash = tokio::task::spawn_blocking(move || {
+                        Self::load_image_upright(&path).map(|img| Self::difference_hash(&img))
+                    })
+                    .await??;
+                    AppResult::Ok((image_name, hash))
                 }
-            }
-        }
-        result
+            })
+            .collect();
+
+        join_all(tasks)
+            .await
+            .into_iter()
+            .filter_map(|result| {
+                result
+                    .map_err(|e| log::warn!("Failed to compute perceptual hash: {e}"))
+                    .ok()
+            })
+            .collect()
     }
 
     /// Returns image previews (thumbnails) for all images in a project
-    /// Uses caching and parallel processing for better performance
+    /// Uses caching (in memory and in `.thumbs/`) and parallel processing for better performance
     pub async fn get_image_previews_in_project(
         &self,
         project_name: &str,
-    ) -> Result<Vec<ImagePreviewModel>, String> {
-        let images_path = format!("projects/{project_name}/images");
-        let image_paths = self.app_save.get_items_in_folder(&images_path)?;
+    ) -> AppResult<Vec<ImagePreviewModel>> {
+        // Every indexed image, in the project root and its folders
+        let file_entries = self.list_image_files(project_name).await?;
+        self.thumbnails.prune(project_name, &file_entries);
+
+        let (mut previews, jobs) = self.preview_jobs(project_name, file_entries).await;
+        let results = join_all(jobs).await;
+        previews.extend(self.cache_new_previews(results).await);
+        Ok(previews)
+    }
 
-        // Collect files from root and one level of subdirectories
-        let file_entries = Self::collect_image_files(image_paths);
+    /// Returns one page of previews in the requested order, generating only the previews on it
+    pub async fn get_image_preview_page(
+        &self,
+        project_name: &str,
+        request: &RequestPreviewPage,
+    ) -> AppResult<PreviewPage> {
+        let index = self.image_index.read_index(project_name).await?;
+        let mut file_entries = index.image_files(&self.images_dir(project_name));
+        Self::sort_image_files(
+            &mut file_entries,
+            &index,
+            request.sort_key,
+            request.descending,
+        );
+        let total = file_entries.len();
+        let page: Vec<(PathBuf, String)> = file_entries
+            .into_iter()
+            .skip(request.offset)
+            .take(request.limit)
+            .collect();
+        let order: HashMap<String, usize> = page
+            .iter()
+            .enumerate()
+            .map(|(position, (_, name))| (name.clone(), position))
+            .collect();
+
+        let (mut previews, jobs) = self.preview_jobs(project_name, page).await;
+        let results = join_all(jobs).await;
+        previews.extend(self.cache_new_previews(results).await);
+        // Cached previews come back first, put everything back in the requested order
+        previews.sort_by_key(|preview| order.get(&preview.image_name).copied());
+
+        Ok(PreviewPage { previews, total })
+    }
 
+    /// Sends every preview in a project to `on_preview` as soon as it is available, cached ones
+    /// first. Stops early without an error once `cancel` is triggered.
+    /// Returns all previews that were sent.
+    pub async fn stream_image_previews_in_project(
+        &self,
+        project_name: &str,
+        cancel: CancellationToken,
+        on_preview: impl Fn(&ImagePreviewModel),
+    ) -> AppResult<Vec<ImagePreviewModel>> {
+        let file_entries = self.list_image_files(project_name).await?;
+        self.thumbnails.prune(project_name, &file_entries);
+
+        let (mut sent, jobs) = self.preview_jobs(project_name, file_entries).await;
+        for preview in &sent {
+            on_preview(preview);
+        }
+
+        let mut pending: FuturesUnordered<_> = jobs.into_iter().collect();
+        loop {
+            let result = tokio::select! {
+                _ = cancel.cancelled() => break,
+                result = pending.next() => match result {
+                    Some(result) => result,
+                    None => break,
+                },
+            };
+            for preview in self.cache_new_previews(vec![result]).await {
+                on_preview(&preview);
+                sent.push(preview);
+            }
+        }
+
+        Ok(sent)
+    }
+
+    /// Splits `file_entries` into previews already in memory and futures that generate the rest.
+    /// The futures are throttled by a semaphore to avoid OOM when importing many images at once.
+    async fn preview_jobs(
+        &self,
+        project_name: &str,
+        file_entries: Vec<(PathBuf, String)>,
+    ) -> (
+        Vec<ImagePreviewModel>,
+        Vec<impl Future<Output = AppResult<(ImagePreviewModel, ImageCacheKey)>> + Send + 'static>,
+    ) {
         // Check cache for existing previews
         let cache = self.preview_cache.read().await;
         let mut cached_previews = Vec::new();
@@ -169,24 +933,60 @@ impl ImageLoaderComponent {
         }
         drop(cache);
 
-        // Process uncached images in parallel, throttled by a semaphore
-        // to avoid OOM when importing many images at once
-        let project_name_owned = project_name.to_string();
-        let semaphore = Arc::clone(&self.preview_semaphore);
-        let tasks: Vec<_> = uncached_paths
+        // Images the index doesn't know yet get hashed while their preview is generated
+        let index = self
+            .image_index
+            .read_index(project_name)
+            .await
+            .unwrap_or_default();
+
+        let jobs = uncached_paths
             .into_iter()
             .map(|(path, image_name)| {
-                let project_name = project_name_owned.clone();
-                let sem = Arc::clone(&semaphore);
-                async move {
-                    let _permit = sem.acquire().await.map_err(|e| e.to_string())?;
-                    Self::generate_preview_async(path, image_name, project_name).await
-                }
+                let entry = index.images.get(&image_name);
+                self.preview_job(project_name, path, image_name, entry)
             })
             .collect();
 
-        let results = join_all(tasks).await;
+        (cached_previews, jobs)
+    }
 
+    /// Generates one preview once a decoding slot is free, reusing what the index already knows
+    fn preview_job(
+        &self,
+        project_name: &str,
+        path: PathBuf,
+        image_name: String,
+        entry: Option<&ImageIndexEntry>,
+    ) -> impl Future<Output = AppResult<(ImagePreviewModel, ImageCacheKey)>> + Send + 'static {
+        let project_name = project_name.to_string();
+        let sem = Arc::clone(&self.preview_semaphore);
+        let thumbnails = Arc::clone(&self.thumbnails);
+        let content_hash = entry.map(|entry| entry.content_hash.clone());
+        let image_metadata = entry.and_then(|entry| entry.metadata.clone());
+        async move {
+            let _permit = sem
+                .acquire()
+                .await
+                .map_err(|e| AppError::Internal(e.to_string()))?;
+            Self::generate_preview_async(
+                path,
+                image_name,
+                project_name,
+                content_hash,
+                image_metadata,
+                thumbnails,
+            )
+            .await
+        }
+    }
+
+    /// Stores successfully generated previews in the memory cache and returns them.
+    /// Failures are logged and left out.
+    async fn cache_new_previews(
+        &self,
+        results: Vec<AppResult<(ImagePreviewModel, ImageCacheKey)>>,
+    ) -> Vec<ImagePreviewModel> {
         // Collect new previews and update cache
         let mut new_previews = Vec::new();
         for result in results {
@@ -208,93 +1008,241 @@ impl ImageLoaderComponent {
             }
         }
 
-        // Combine cached and new previews
-        let mut all_previews = cached_previews;
-        all_previews.extend(new_previews.into_iter().map(|(p, _)| p));
+        new_previews.into_iter().map(|(p, _)| p).collect()
+    }
 
-        Ok(all_previews)
+    /// Sorts `(full_path, relative_name)` pairs by what the index knows about them. Images
+    /// without a known modification time sort as oldest.
+    fn sort_image_files(
+        file_entries: &mut [(PathBuf, String)],
+        index: &ImageIndex,
+        sort_key: PreviewSortKey,
+        descending: bool,
+    ) {
+        match sort_key {
+            PreviewSortKey::Name => {
+                file_entries.sort_by_cached_key(|(_, name)| name.to_lowercase());
+            }
+            PreviewSortKey::Size => {
+                file_entries
+                    .sort_by_key(|(_, name)| index.images.get(name).map(|entry| entry.size_bytes));
+            }
+            PreviewSortKey::Modified => {
+                file_entries.sort_by_key(|(_, name)| {
+                    index.images.get(name).and_then(|entry| entry.modified_at)
+                });
+            }
+        }
+        if descending {
+            file_entries.reverse();
+        }
     }
 
-    /// Generate a preview asynchronously (for parallel processing)
+    /// Generate a preview asynchronously (for parallel processing).
+    /// A thumbnail saved in `.thumbs/` is reused if the image hasn't changed since.
+    /// Metadata the index doesn't have yet is read from the file.
     async fn generate_preview_async(
         path: PathBuf,
         image_name: String,
         project_name: String,
-    ) -> Result<(ImagePreviewModel, ImageCacheKey), String> {
-        let path_clone = path.clone();
-        let preview = tokio::task::spawn_blocking(move || Self::generate_preview(&path_clone))
-            .await
-            .map_err(|e| e.to_string())?
-            .map_err(|e| format!("Failed to generate preview for {image_name}: {e}"))?;
-
-        let metadata = tokio::fs::metadata(&path)
-            .await
-            .map_err(|e| e.to_string())?;
-
+        content_hash: Option<String>,
+        image_metadata: Option<ImageMetadataModel>,
+        thumbnails: Arc<ThumbnailCacheComponent>,
+    ) -> AppResult<(ImagePreviewModel, ImageCacheKey)> {
         let key = Self::cache_key(&project_name, &image_name);
-        let model = ImagePreviewModel {
-            image_name,
-            base64_preview: preview.0,
-            image_size_bytes: metadata.len(),
-            width: preview.1,
-            height: preview.2,
-        };
+        let model = tokio::task::spawn_blocking(move || {
+            let image_metadata = image_metadata.unwrap_or_else(|| read_image_metadata(&path, None));
+            if let Some(mut model) = thumbnails.load(&project_name, &image_name, &path) {
+                model.metadata = Some(image_metadata);
+                return Ok(model);
+            }
+
+            let preview = Self::generate_preview(&path).map_err(|e| {
+                AppError::Decode(format!("Failed to generate preview for {image_name}: {e}"))
+            })?;
+            let content_hash = content_hash.or_else(|| ImageIndexComponent::hash_file(&path).ok());
+            let file_metadata = path.metadata().map_err(|e| AppError::io(&image_name, e))?;
+
+            let model = ImagePreviewModel {
+                image_name,
+                image_size_bytes: file_metadata.len(),
+                width: preview.width,
+                height: preview.height,
+                content_hash,
+                perceptual_hash: Some(preview.perceptual_hash),
+                metadata: Some(image_metadata),
+            };
+            // The preview is still usable if it can't be saved, it just gets regenerated next time
+            if let Err(e) = thumbnails.store(&project_name, &path, &model, &preview.jpeg) {
+                log::warn!("Failed to save thumbnail for {}: {e}", model.image_name);
+            }
+            AppResult::Ok(model)
+        })
+        .await??;
         Ok((model, key))
     }
 
-    /// Loads a full resolution image from a project (with caching)
+    /// Returns size and dimensions of a full resolution image, as displayed after applying its
+    /// EXIF orientation. The image data itself is read with `read_image_file`.
     pub async fn load_image_from_project(
         &self,
         project_name: &str,
         image_name: &str,
-    ) -> Result<FullImageModel, String> {
-        let key = Self::cache_key(project_name, image_name);
-
-        // Check cache first
-        {
-            let cache = self.full_image_cache.read().await;
-            if let Some(cached) = cache.get(&key) {
-                return Ok(cached.clone());
-            }
-        }
-
-        // Load from disk
+    ) -> AppResult<FullImageModel> {
         let image_path = format!("projects/{project_name}/images/{image_name}");
         let full_path = self.app_save.get_full_path(&image_path);
 
-        let image_bytes = tokio::fs::read(&full_path)
-            .await
-            .map_err(|e| format!("Failed to read image: {e}"))?;
-
         let metadata = tokio::fs::metadata(&full_path)
             .await
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| AppError::io(image_name, e))?;
 
         // Get dimensions from header only (much faster than full decode)
-        let path_for_dims = full_path.clone();
-        let (width, height) = tokio::task::spawn_blocking(move || {
-            image::image_dimensions(&path_for_dims).unwrap_or((0, 0))
-        })
-        .await
-        .map_err(|e| e.to_string())?;
-
-        let base64_image = STANDARD.encode(&image_bytes);
+        let (width, height) =
+            tokio::task::spawn_blocking(move || Self::upright_dimensions(&full_path)).await?;
 
-        let model = FullImageModel {
+        Ok(FullImageModel {
             image_name: image_name.to_string(),
-            base64_image,
             image_size_bytes: metadata.len(),
             width,
             height,
+        })
+    }
+
+    /// Reads the EXIF/XMP capture details of an image straight from the file
+    pub async fn load_image_metadata(
+        &self,
+        project_name: &str,
+        image_name: &str,
+    ) -> AppResult<ImageMetadataModel> {
+        let image_path = format!("projects/{project_name}/images/{image_name}");
+        let full_path = self.app_save.get_full_path(&image_path);
+        tokio::fs::metadata(&full_path)
+            .await
+            .map_err(|e| AppError::io(image_name, e))?;
+        Ok(tokio::task::spawn_blocking(move || read_image_metadata(&full_path, None)).await?)
+    }
+
+    /// Reads the raw bytes of a full resolution image (with caching).
+    /// HEIF and RAW files, which the webview can't display, are transcoded to JPEG.
+    /// A cached copy is only used if the file hasn't changed since it was read.
+    pub async fn read_image_file(
+        &self,
+        project_name: &str,
+        image_name: &str,
+    ) -> AppResult<ImageBytes> {
+        let key = Self::cache_key(project_name, image_name);
+        let image_path = format!("projects/{project_name}/images/{image_name}");
+        let full_path = self.app_save.get_full_path(&image_path);
+
+        let metadata = tokio::fs::metadata(&full_path)
+            .await
+            .map_err(|e| AppError::io(image_name, e))?;
+        let etag = Self::file_etag(&metadata);
+
+        // Check cache first
+        if let Some(cached) = self.full_image_cache.get(&key) {
+            if cached.etag == etag {
+                return Ok(cached);
+            }
+        }
+
+        let image = if image_decoders::needs_transcoding(&full_path) {
+            let jpeg = tokio::task::spawn_blocking(move || Self::encode_upright_jpeg(&full_path))
+                .await??;
+            ImageBytes {
+                bytes: Arc::new(jpeg),
+                etag,
+                content_type: "image/jpeg",
+            }
+        } else {
+            let bytes = tokio::fs::read(&full_path)
+                .await
+                .map_err(|e| AppError::io(format!("Failed to read image {image_name}"), e))?;
+            ImageBytes {
+                bytes: Arc::new(bytes),
+                etag,
+                content_type: Self::content_type_for(&full_path),
+            }
         };
 
         // Store in cache
+        self.full_image_cache.insert(key, image.clone());
+
+        Ok(image)
+    }
+
+    /// Returns the JPEG thumbnail of an image, generating it first if `.thumbs/` has no
+    /// up to date copy
+    pub async fn read_thumbnail(
+        &self,
+        project_name: &str,
+        image_name: &str,
+    ) -> AppResult<ImageBytes> {
+        let image_path = format!("projects/{project_name}/images/{image_name}");
+        let full_path = self.app_save.get_full_path(&image_path);
+        if let Some(thumbnail) = self
+            .thumbnails
+            .load_jpeg(project_name, image_name, &full_path)
         {
-            let mut cache = self.full_image_cache.write().await;
-            cache.insert(key, model.clone());
+            return Ok(thumbnail);
         }
 
-        Ok(model)
+        let (preview, key) = {
+            let _permit = self
+                .preview_semaphore
+                .acquire()
+                .await
+                .map_err(|e| AppError::Internal(e.to_string()))?;
+            Self::generate_preview_async(
+                full_path.clone(),
+                image_name.to_string(),
+                project_name.to_string(),
+                None,
+                None,
+                Arc::clone(&self.thumbnails),
+            )
+            .await?
+        };
+        self.preview_cache.write().await.insert(key, preview);
+
+        self.thumbnails
+            .load_jpeg(project_name, image_name, &full_path)
+            .ok_or_else(|| AppError::Internal(format!("Failed to save thumbnail for {image_name}")))
+    }
+
+    /// Quoted entity tag built from the file's size and modification time
+    fn file_etag(metadata: &fs::Metadata) -> String {
+        let modified_nanos = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+        format!("\"{:x}-{modified_nanos:x}\"", metadata.len())
+    }
+
+    /// Returns hit/miss/eviction counters and memory use of the full image cache
+    pub fn full_image_cache_stats(&self) -> ImageCacheStats {
+        self.full_image_cache.stats()
+    }
+
+    /// Changes the memory budget of the full image cache, kept across restarts
+    pub fn set_full_image_cache_capacity(&self, capacity_bytes: u64) -> AppResult<()> {
+        self.app_save
+            .save_json(CACHE_CAPACITY_PATH, &capacity_bytes)?;
+        self.full_image_cache.set_capacity(capacity_bytes);
+        Ok(())
+    }
+
+    /// The saved memory budget of the full image cache, the default if none was set
+    fn read_cache_capacity(app_save: &AppSaveService) -> u64 {
+        if !app_save.get_full_path(CACHE_CAPACITY_PATH).exists() {
+            return DEFAULT_FULL_IMAGE_CACHE_BYTES;
+        }
+        app_save.read_json(CACHE_CAPACITY_PATH).unwrap_or_else(|e| {
+            log::warn!("Failed to read the image cache capacity: {e}");
+            DEFAULT_FULL_IMAGE_CACHE_BYTES
+        })
     }
 
     /// Moves images to a different folder (or root) within the project.
@@ -305,21 +1253,24 @@ impl ImageLoaderComponent {
         &self,
         project_name: &str,
         image_names: Vec<String>,
-        target_folder: Option<String>,
-    ) -> Result<Vec<String>, String> {
+        target_folder: Option<&str>,
+    ) -> AppResult<Vec<String>> {
         let images_base = format!("projects/{project_name}/images");
-        let target_prefix = target_folder
-            .as_deref()
-            .filter(|f| !f.is_empty())
-            .map(|f| format!("{f}/"))
-            .unwrap_or_default();
+        let target_prefix = target_folder.map(|f| format!("{f}/")).unwrap_or_default();
+        if let Some(folder) = target_folder {
+            // Nested folders may not exist yet
+            let folder_path = self
+                .app_save
+                .get_full_path(&format!("{images_base}/{folder}"));
+            fs::create_dir_all(&folder_path).map_err(|e| AppError::io(folder, e))?;
+        }
 
         let mut new_names = Vec::new();
         for image_name in &image_names {
             // Extract just the filename (strip any existing folder prefix)
             let file_name = std::path::Path::new(image_name)
                 .file_name()
-                .ok_or_else(|| format!("Invalid image name: {image_name}"))?
+                .ok_or_else(|| AppError::InvalidName(format!("Invalid image name: {image_name}")))?
                 .to_string_lossy()
                 .to_string();
 
@@ -334,7 +1285,7 @@ impl ImageLoaderComponent {
             }
 
             fs::rename(&src_path, &dst_path).map_err(|e| {
-                format!("Failed to move {image_name} to {new_rel_name}: {e}")
+                AppError::io(format!("Failed to move {image_name} to {new_rel_name}"), e)
             })?;
 
             new_names.push(new_rel_name);
@@ -361,13 +1312,14 @@ impl ImageLoaderComponent {
     }
 
     /// Load JPEG using turbojpeg (SIMD-accelerated, 2-5x faster)
-    fn load_jpeg_turbo(path: &std::path::Path) -> Result<DynamicImage, String> {
-        let jpeg_data = std::fs::read(path).map_err(|e| e.to_string())?;
+    fn load_jpeg_turbo(path: &std::path::Path) -> AppResult<DynamicImage> {
+        let jpeg_data = std::fs::read(path).map_err(|e| AppError::io(path.display(), e))?;
 
-        let mut decompressor = Decompressor::new().map_err(|e| e.to_string())?;
+        let mut decompressor =
+            Decompressor::new().map_err(|e| AppError::Internal(e.to_string()))?;
         let header = decompressor
             .read_header(&jpeg_data)
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| AppError::Decode(e.to_string()))?;
 
         let width = header.width;
         let height = header.height;
@@ -387,18 +1339,28 @@ impl ImageLoaderComponent {
         // Decompress to RGBA
         decompressor
             .decompress(&jpeg_data, output)
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| AppError::Decode(e.to_string()))?;
 
         // Convert to DynamicImage
         let rgba_image = image::RgbaImage::from_raw(width as u32, height as u32, pixels)
-            .ok_or("Failed to create image from turbojpeg output")?;
+            .ok_or_else(|| {
+                AppError::Decode("Failed to create image from turbojpeg output".to_string())
+            })?;
 
         Ok(DynamicImage::ImageRgba8(rgba_image))
     }
 
     /// Load image with format hint for faster decoding
-    /// Uses turbojpeg for JPEGs, falls back to image crate for others
-    fn load_image_fast(path: &std::path::Path) -> Result<DynamicImage, String> {
+    /// Uses turbojpeg for JPEGs, libheif for HEIC/HEIF, the embedded preview for RAW files
+    /// and falls back to image crate for others
+    fn load_image_fast(path: &std::path::Path) -> AppResult<DynamicImage> {
+        if image_decoders::is_heif(path) {
+            return image_decoders::decode_heif(path);
+        }
+        if image_decoders::is_raw(path) {
+            return image_decoders::decode_raw_preview(path);
+        }
+
         // Use turbojpeg for JPEG files (much faster)
         if let Some(format) = Self::format_from_extension(path) {
             if format == ImageFormat::Jpeg {
@@ -411,7 +1373,7 @@ impl ImageLoaderComponent {
 
         // For non-JPEG formats, try format hint first, then fall back to auto-detection
         if let Some(format) = Self::format_from_extension(path) {
-            let file = File::open(path).map_err(|e| e.to_string())?;
+            let file = File::open(path).map_err(|e| AppError::io(path.display(), e))?;
             let reader = BufReader::new(file);
             let mut img_reader = ImageReader::new(reader);
             img_reader.set_format(format);
@@ -423,22 +1385,131 @@ impl ImageLoaderComponent {
         }
 
         // Fallback to auto-detection (handles mismatched extensions)
-        let file = File::open(path).map_err(|e| e.to_string())?;
+        let file = File::open(path).map_err(|e| AppError::io(path.display(), e))?;
         let reader = BufReader::new(file);
-        ImageReader::new(reader)
+        Ok(ImageReader::new(reader)
             .with_guessed_format()
-            .map_err(|e| e.to_string())?
-            .decode()
-            .map_err(|e| e.to_string())
+            .map_err(|e| AppError::io(path.display(), e))?
+            .decode()?)
+    }
+
+    /// Returns the EXIF orientation of an image, or `NoTransforms` if it has none or it can't be read
+    pub fn read_orientation(path: &Path) -> Orientation {
+        // HEIF stores rotation in the container, which libheif already applies while decoding
+        if image_decoders::is_heif(path) {
+            return Orientation::NoTransforms;
+        }
+        let Ok(file) = File::open(path) else {
+            return Orientation::NoTransforms;
+        };
+        exif::Reader::new()
+            .read_from_container(&mut BufReader::new(file))
+            .ok()
+            .and_then(|exif| {
+                exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)?
+                    .value
+                    .get_uint(0)
+            })
+            .and_then(|value| Orientation::from_exif(u8::try_from(value).ok()?))
+            .unwrap_or(Orientation::NoTransforms)
+    }
+
+    /// Decodes an image and rotates/flips it the way its EXIF orientation says it is displayed.
+    /// Phone cameras store most photos sideways and rely on this tag.
+    fn load_image_upright(path: &Path) -> AppResult<DynamicImage> {
+        let mut img = Self::load_image_fast(path)?;
+        img.apply_orientation(Self::read_orientation(path));
+        Ok(img)
+    }
+
+    /// Reads width and height from the header, swapped if the image is stored rotated by 90°.
+    /// HEIF and RAW files are decoded, the image crate can't read their headers.
+    fn upright_dimensions(path: &Path) -> (u32, u32) {
+        if image_decoders::needs_transcoding(path) {
+            return Self::load_image_upright(path)
+                .map(|img| img.dimensions())
+                .unwrap_or((0, 0));
+        }
+        let (width, height) = image::image_dimensions(path).unwrap_or((0, 0));
+        match Self::read_orientation(path) {
+            Orientation::Rotate90
+            | Orientation::Rotate270
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH => (height, width),
+            _ => (width, height),
+        }
+    }
+
+    /// True if an image is stored rotated or flipped, or in a format (HEIF, RAW) that other
+    /// tools can't read, so it has to be handed out as an upright JPEG copy
+    pub fn needs_upright_copy(path: &Path) -> bool {
+        image_decoders::needs_transcoding(path)
+            || Self::read_orientation(path) != Orientation::NoTransforms
+    }
+
+    /// Writes an upright JPEG copy of an image to `dest` if `needs_upright_copy`.
+    /// Returns false, without writing anything, if the image can be used as it is.
+    pub fn write_upright_copy(path: &Path, dest: &Path) -> AppResult<bool> {
+        if !Self::needs_upright_copy(path) {
+            return Ok(false);
+        }
+        let jpeg = Self::encode_upright_jpeg(path)?;
+        fs::write(dest, jpeg).map_err(|e| AppError::io(dest.display(), e))?;
+        Ok(true)
+    }
+
+    /// Decodes an image, turns it upright and encodes it as a high quality JPEG
+    fn encode_upright_jpeg(path: &Path) -> AppResult<Vec<u8>> {
+        let img = Self::load_image_upright(path)?;
+        let mut buffer = Cursor::new(Vec::new());
+        let mut encoder = JpegEncoder::new_with_quality(&mut buffer, TRANSCODE_JPEG_QUALITY);
+        encoder.encode_image(&img.to_rgb8())?;
+        Ok(buffer.into_inner())
+    }
+
+    /// MIME type of an image file as served to the webview, by extension
+    fn content_type_for(path: &Path) -> &'static str {
+        let extension = path
+            .extension()
+            .map(|ext| ext.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+        match extension.as_str() {
+            "jpg" | "jpeg" => "image/jpeg",
+            "png" => "image/png",
+            "gif" => "image/gif",
+            "webp" => "image/webp",
+            "bmp" => "image/bmp",
+            "tif" | "tiff" => "image/tiff",
+            _ => "application/octet-stream",
+        }
+    }
+
+    /// Returns the hex encoded 64 bit difference hash (dHash) of an image.
+    /// Each bit records whether a pixel of the downscaled grayscale image is brighter than its
+    /// right neighbour, so re-encoded or slightly shifted shots of the same subject end up only a
+    /// few bits apart.
+    fn difference_hash(img: &DynamicImage) -> String {
+        let small = img
+            .resize_exact(DHASH_WIDTH, DHASH_HEIGHT, FilterType::Triangle)
+            .to_luma8();
+        let mut hash = 0u64;
+        for y in 0..DHASH_HEIGHT {
+            for x in 0..DHASH_WIDTH - 1 {
+                let brighter = small.get_pixel(x, y)[0] > small.get_pixel(x + 1, y)[0];
+                hash = (hash << 1) | brighter as u64;
+            }
+        }
+        format!("{hash:016x}")
     }
 
     /// Generates a thumbnail preview from an image path using fast_image_resize
-    /// Returns (base64_preview, original_width, original_height)
-    fn generate_preview(path: &std::path::Path) -> Result<(String, u32, u32), String> {
-        // Use format hints for faster decoding
-        let img = Self::load_image_fast(path)?;
+    fn generate_preview(path: &std::path::Path) -> AppResult<GeneratedPreview> {
+        // Use format hints for faster decoding, then turn the image upright so thumbnails,
+        // dimensions and hashes all match what the user sees
+        let img = Self::load_image_upright(path)?;
 
         let (width, height) = img.dimensions();
+        let perceptual_hash = Self::difference_hash(&img);
 
         // Calculate thumbnail dimensions maintaining aspect ratio
         let (thumb_width, thumb_height) = if width > height {
@@ -462,7 +1533,7 @@ impl ImageLoaderComponent {
                 src_image.into_raw(),
                 fast_image_resize::PixelType::U8x4,
             )
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| AppError::Internal(e.to_string()))?;
 
             let mut dst = Image::new(
                 thumb_width,
@@ -474,20 +1545,403 @@ impl ImageLoaderComponent {
             let options = ResizeOptions::new().resize_alg(ResizeAlg::Nearest);
             resizer
                 .resize(&src, &mut dst, &options)
-                .map_err(|e| e.to_string())?;
+                .map_err(|e| AppError::Internal(e.to_string()))?;
 
             image::RgbaImage::from_raw(thumb_width, thumb_height, dst.into_vec())
-                .ok_or("Failed to create thumbnail image")?
+                .ok_or_else(|| AppError::Internal("Failed to create thumbnail image".to_string()))?
         };
 
         // Encode as JPEG with lower quality for speed
         let mut buffer = Cursor::new(Vec::new());
         let mut encoder = JpegEncoder::new_with_quality(&mut buffer, 70);
-        encoder
-            .encode_image(&thumbnail)
-            .map_err(|e| e.to_string())?;
+        encoder.encode_image(&thumbnail)?;
+
+        Ok(GeneratedPreview {
+            jpeg: buffer.into_inner(),
+            width,
+            height,
+            perceptual_hash,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    const PROJECT: &str = "p";
+    static NEXT_TEST_DIR: AtomicUsize = AtomicUsize::new(0);
+
+    /// A project saved into its own temp dir next to a directory to import from, both removed
+    /// when dropped
+    struct TestProject {
+        save_dir: PathBuf,
+        source_dir: PathBuf,
+        images_dir: PathBuf,
+        image_index: Arc<ImageIndexComponent>,
+        loader: ImageLoaderComponent,
+    }
+
+    impl TestProject {
+        fn new(test_name: &str) -> Self {
+            let save_dir = std::env::temp_dir().join(format!(
+                "get-crap-done-import-{test_name}-{}-{}",
+                std::process::id(),
+                NEXT_TEST_DIR.fetch_add(1, Ordering::Relaxed)
+            ));
+            let source_dir = save_dir.join("source");
+            let images_dir = save_dir.join(format!("projects/{PROJECT}/images"));
+            fs::create_dir_all(&images_dir).unwrap();
+            let app_save = Arc::new(AppSaveService::new(save_dir.clone()));
+            let image_index = Arc::new(ImageIndexComponent::new(app_save.clone()));
+            let loader = ImageLoaderComponent::new(app_save, image_index.clone());
+            Self {
+                save_dir,
+                source_dir,
+                images_dir,
+                image_index,
+                loader,
+            }
+        }
+
+        /// Writes a 4x4 PNG whose content is unique to `shade`
+        fn write_png(path: &Path, shade: u8) {
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            image::RgbImage::from_pixel(4, 4, image::Rgb([shade, 0, 0]))
+                .save(path)
+                .unwrap();
+        }
+
+        /// Writes a file to import and returns its path
+        fn source_png(&self, rel_path: &str, shade: u8) -> String {
+            let path = self.source_dir.join(rel_path);
+            Self::write_png(&path, shade);
+            path.display().to_string()
+        }
+
+        /// Every file and folder below `images/` by relative path, with the file's content hash
+        fn project_tree(&self) -> Vec<(String, Option<String>)> {
+            WalkDir::new(&self.images_dir)
+                .min_depth(1)
+                .sort_by_file_name()
+                .into_iter()
+                .map(|entry| {
+                    let entry = entry.unwrap();
+                    let rel_path = entry.path().strip_prefix(&self.images_dir).unwrap();
+                    let hash = entry
+                        .file_type()
+                        .is_file()
+                        .then(|| ImageIndexComponent::hash_file(entry.path()).unwrap());
+                    (rel_path.to_string_lossy().replace('\\', "/"), hash)
+                })
+                .collect()
+        }
+
+        /// Imports `sources` into the project root, resolving name conflicts with `policy`
+        async fn import(&self, sources: Vec<String>, policy: ImportConflictPolicy) -> ImportReport {
+            let session = ImportSession {
+                import_id: "test",
+                cancel: &CancellationToken::new(),
+                on_progress: &|_| {},
+            };
+            let options = ImportOptions {
+                policy,
+                transcode_to_jpeg: false,
+            };
+            self.loader
+                .import_images_to_project(PROJECT, sources, None, options, &session)
+                .await
+                .unwrap()
+        }
+
+        /// Imports everything in the source directory into `folder`
+        async fn import_directory(
+            &self,
+            folder: Option<&str>,
+            preserve_structure: bool,
+        ) -> ImportReport {
+            let session = ImportSession {
+                import_id: "test",
+                cancel: &CancellationToken::new(),
+                on_progress: &|_| {},
+            };
+            let options = ImportOptions {
+                policy: ImportConflictPolicy::AutoRename,
+                transcode_to_jpeg: false,
+            };
+            self.loader
+                .import_directory_to_project(
+                    PROJECT,
+                    &self.source_dir,
+                    folder,
+                    preserve_structure,
+                    options,
+                    &session,
+                )
+                .await
+                .unwrap()
+        }
+
+        /// Indexed image names with their content hashes
+        async fn indexed(&self) -> Vec<(String, String)> {
+            let index = self.image_index.read_index(PROJECT).await.unwrap();
+            index
+                .images
+                .into_iter()
+                .map(|(name, entry)| (name, entry.content_hash))
+                .collect()
+        }
+    }
+
+    impl Drop for TestProject {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.save_dir);
+        }
+    }
+
+    #[tokio::test]
+    async fn cancelled_import_leaves_the_project_as_it_was() {
+        let project = TestProject::new("rollback");
+        TestProject::write_png(&project.images_dir.join("existing.png"), 1);
+        let tree_before = project.project_tree();
+        let indexed_before = project.indexed().await;
+        project.source_png("DCIM/100APPLE/a.png", 2);
+        project.source_png("DCIM/100APPLE/b.png", 3);
+        project.source_png("existing.png", 4);
+        project.source_png("later.png", 5);
+
+        let cancel = CancellationToken::new();
+        // Stops the import once the nested folders were created and the existing image replaced
+        let on_progress = |progress: ImportProgress| {
+            if progress.index == 2 {
+                cancel.cancel();
+            }
+        };
+        let session = ImportSession {
+            import_id: "test",
+            cancel: &cancel,
+            on_progress: &on_progress,
+        };
+        let options = ImportOptions {
+            policy: ImportConflictPolicy::Overwrite,
+            transcode_to_jpeg: false,
+        };
+        let report = project
+            .loader
+            .import_directory_to_project(
+                PROJECT,
+                &project.source_dir,
+                None,
+                true,
+                options,
+                &session,
+            )
+            .await
+            .unwrap();
+
+        assert!(report.cancelled);
+        assert_eq!(project.project_tree(), tree_before);
+        assert_eq!(project.indexed().await, indexed_before);
+        assert!(!project
+            .save_dir
+            .join(format!("projects/{PROJECT}/.import"))
+            .exists());
+    }
+
+    /// Imports a `photo.png` with new content into a project that already has one
+    async fn import_conflicting_photo(policy: ImportConflictPolicy) -> (TestProject, ImportReport) {
+        let project = TestProject::new("conflict");
+        TestProject::write_png(&project.images_dir.join("photo.png"), 1);
+        let source = project.source_png("photo.png", 2);
+        let report = project.import(vec![source], policy).await;
+        (project, report)
+    }
+
+    fn hash_of(path: &Path) -> String {
+        ImageIndexComponent::hash_file(path).unwrap()
+    }
+
+    #[tokio::test]
+    async fn skip_keeps_the_existing_image() {
+        let (project, report) = import_conflicting_photo(ImportConflictPolicy::Skip).await;
+        let existing_hash = hash_of(&project.images_dir.join("photo.png"));
+
+        assert!(report.imported.is_empty());
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].reason, "photo.png already exists");
+        assert_ne!(
+            existing_hash,
+            hash_of(&project.source_dir.join("photo.png"))
+        );
+        assert_eq!(
+            project.indexed().await,
+            vec![("photo.png".to_string(), existing_hash)]
+        );
+    }
+
+    #[tokio::test]
+    async fn overwrite_replaces_the_existing_image() {
+        let (project, report) = import_conflicting_photo(ImportConflictPolicy::Overwrite).await;
+        let source_hash = hash_of(&project.source_dir.join("photo.png"));
+
+        assert_eq!(report.imported, vec!["photo.png"]);
+        assert_eq!(report.overwritten, vec!["photo.png"]);
+        assert_eq!(
+            project.project_tree(),
+            vec![("photo.png".to_string(), Some(source_hash.clone()))]
+        );
+        assert_eq!(
+            project.indexed().await,
+            vec![("photo.png".to_string(), source_hash)]
+        );
+    }
+
+    #[tokio::test]
+    async fn auto_rename_numbers_the_new_image() {
+        let (project, report) = import_conflicting_photo(ImportConflictPolicy::AutoRename).await;
+        let existing_hash = hash_of(&project.images_dir.join("photo.png"));
+        let source_hash = hash_of(&project.source_dir.join("photo.png"));
+
+        assert!(report.imported.is_empty());
+        assert_eq!(report.renamed.len(), 1);
+        assert_eq!(report.renamed[0].image_name, "photo_2.png");
+        assert_eq!(
+            project.indexed().await,
+            vec![
+                ("photo.png".to_string(), existing_hash),
+                ("photo_2.png".to_string(), source_hash),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn rename_by_hash_suffixes_the_content_hash() {
+        let (project, report) = import_conflicting_photo(ImportConflictPolicy::RenameByHash).await;
+        let existing_hash = hash_of(&project.images_dir.join("photo.png"));
+        let source_hash = hash_of(&project.source_dir.join("photo.png"));
+        let renamed = format!("photo_{}.png", &source_hash[..HASH_SUFFIX_LEN]);
+
+        assert_eq!(report.renamed.len(), 1);
+        assert_eq!(report.renamed[0].image_name, renamed);
+        assert_eq!(
+            project.indexed().await,
+            vec![
+                ("photo.png".to_string(), existing_hash),
+                (renamed, source_hash),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn same_content_under_another_name_is_a_duplicate() {
+        let project = TestProject::new("duplicate");
+        TestProject::write_png(&project.images_dir.join("photo.png"), 1);
+        let tree_before = project.project_tree();
+        let copy = project.source_png("copy.png", 1);
+        let new = project.source_png("new.png", 2);
+        let new_again = project.source_png("new again.png", 2);
+
+        let report = project
+            .import(
+                vec![copy.clone(), new.clone(), new_again.clone()],
+                ImportConflictPolicy::AutoRename,
+            )
+            .await;
+
+        assert_eq!(report.imported, vec!["new.png"]);
+        let duplicates: Vec<_> = report
+            .duplicates
+            .iter()
+            .map(|d| (d.source_path.as_str(), d.existing_image_name.as_str()))
+            .collect();
+        // Also caught when the first copy came in with the same import
+        assert_eq!(
+            duplicates,
+            vec![
+                (copy.as_str(), "photo.png"),
+                (new_again.as_str(), "new.png")
+            ]
+        );
+        assert_eq!(project.project_tree().len(), tree_before.len() + 1);
+        assert_eq!(project.indexed().await.len(), 2);
+    }
+
+    /// Source images in nested folders, along with files a directory import leaves out
+    fn write_camera_folders(project: &TestProject) {
+        project.source_png("a.png", 1);
+        project.source_png("DCIM/b.png", 2);
+        project.source_png("DCIM/100APPLE/c.png", 3);
+        project.source_png(".hidden.png", 4);
+        project.source_png(".thumbnails/d.png", 5);
+        project.source_png("DCIM/.trash/e.png", 6);
+        fs::write(project.source_dir.join("DCIM/notes.txt"), "notes").unwrap();
+        fs::create_dir_all(project.source_dir.join("empty")).unwrap();
+    }
+
+    #[tokio::test]
+    async fn directory_import_keeps_the_folder_layout() {
+        let project = TestProject::new("directory");
+        write_camera_folders(&project);
+
+        let report = project.import_directory(Some("trip"), true).await;
+
+        assert_eq!(
+            report.imported,
+            vec!["trip/DCIM/100APPLE/c.png", "trip/DCIM/b.png", "trip/a.png"]
+        );
+        assert!(report.skipped.is_empty());
+        assert_eq!(
+            report.folders,
+            vec!["trip", "trip/DCIM", "trip/DCIM/100APPLE"]
+        );
+        let paths: Vec<String> = project
+            .project_tree()
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                "trip",
+                "trip/DCIM",
+                "trip/DCIM/100APPLE",
+                "trip/DCIM/100APPLE/c.png",
+                "trip/DCIM/b.png",
+                "trip/a.png",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn directory_import_can_flatten_the_folder_layout() {
+        let project = TestProject::new("directory-flat");
+        write_camera_folders(&project);
+
+        let report = project.import_directory(None, false).await;
+
+        assert_eq!(report.imported, vec!["c.png", "b.png", "a.png"]);
+        assert!(report.skipped.is_empty());
+        assert!(report.folders.is_empty());
+        assert_eq!(project.project_tree().len(), 3);
+    }
 
-        let base64_preview = STANDARD.encode(buffer.into_inner());
-        Ok((base64_preview, width, height))
+    #[test]
+    fn image_cache_capacity_is_kept_across_restarts() {
+        let project = TestProject::new("cache-capacity");
+        assert_eq!(
+            project.loader.full_image_cache_stats().capacity_bytes,
+            DEFAULT_FULL_IMAGE_CACHE_BYTES
+        );
+
+        project.loader.set_full_image_cache_capacity(1024).unwrap();
+
+        let app_save = Arc::new(AppSaveService::new(project.save_dir.clone()));
+        let restarted = ImageLoaderComponent::new(
+            app_save.clone(),
+            Arc::new(ImageIndexComponent::new(app_save)),
+        );
+        assert_eq!(restarted.full_image_cache_stats().capacity_bytes, 1024);
     }
 }
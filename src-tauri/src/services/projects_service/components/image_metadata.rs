@@ -0,0 +1,209 @@
+use std::{
+    fs::File,
+    io::{BufReader, Read},
+    path::Path,
+};
+
+use exif::{Exif, In, Tag, Value};
+
+use crate::services::projects_service::models::{GpsCoordinates, ImageMetadataModel};
+
+/// Number of bytes searched for an XMP packet. Cameras and editors write it near the start.
+const XMP_SEARCH_BYTES: u64 = 256 * 1024;
+
+/// Reads capture time, camera, GPS position and original file name from an image's EXIF data,
+/// falling back to its XMP packet for anything EXIF doesn't have.
+/// `original_file_name` is the name the file had before import, if known.
+/// Unreadable or missing metadata simply leaves fields empty.
+pub fn read_image_metadata(path: &Path, original_file_name: Option<&str>) -> ImageMetadataModel {
+    let exif = File::open(path).ok().and_then(|file| {
+        exif::Reader::new()
+            .read_from_container(&mut BufReader::new(file))
+            .ok()
+    });
+    let xmp = read_xmp_packet(path);
+    let xmp_property = |name: &str| xmp.as_deref().and_then(|xmp| xmp_property(xmp, name));
+
+    let captured_at = exif.as_ref().and_then(exif_captured_at).or_else(|| {
+        [
+            "exif:DateTimeOriginal",
+            "xmp:CreateDate",
+            "photoshop:DateCreated",
+        ]
+        .into_iter()
+        .find_map(xmp_property)
+    });
+    let camera_make = exif
+        .as_ref()
+        .and_then(|exif| exif_ascii(exif, Tag::Make))
+        .or_else(|| xmp_property("tiff:Make"));
+    let camera_model = exif
+        .as_ref()
+        .and_then(|exif| exif_ascii(exif, Tag::Model))
+        .or_else(|| xmp_property("tiff:Model"));
+    let gps = exif.as_ref().and_then(exif_gps).or_else(|| {
+        Some(GpsCoordinates {
+            latitude: parse_xmp_coordinate(&xmp_property("exif:GPSLatitude")?)?,
+            longitude: parse_xmp_coordinate(&xmp_property("exif:GPSLongitude")?)?,
+            altitude: None,
+        })
+    });
+    // A name preserved by an earlier tool predates the one the file was imported under
+    let original_file_name =
+        xmp_property("xmpMM:PreservedFileName").or_else(|| original_file_name.map(str::to_string));
+
+    ImageMetadataModel {
+        captured_at,
+        camera_make,
+        camera_model,
+        gps,
+        original_file_name,
+    }
+}
+
+/// Returns the first value of an ASCII field without padding, if it isn't blank
+fn exif_ascii(exif: &Exif, tag: Tag) -> Option<String> {
+    let Value::Ascii(values) = &exif.get_field(tag, In::PRIMARY)?.value else {
+        return None;
+    };
+    let text = String::from_utf8_lossy(values.first()?)
+        .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+        .to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+/// Formats the most specific capture time as ISO 8601, e.g. `2024-05-01T13:22:10+02:00`
+fn exif_captured_at(exif: &Exif) -> Option<String> {
+    [
+        (Tag::DateTimeOriginal, Tag::OffsetTimeOriginal),
+        (Tag::DateTimeDigitized, Tag::OffsetTimeDigitized),
+        (Tag::DateTime, Tag::OffsetTime),
+    ]
+    .into_iter()
+    .find_map(|(tag, offset_tag)| {
+        let mut date_time = exif::DateTime::from_ascii(exif_ascii(exif, tag)?.as_bytes()).ok()?;
+        if let Some(offset) = exif_ascii(exif, offset_tag) {
+            // A malformed offset leaves the time without one
+            let _ = date_time.parse_offset(offset.as_bytes());
+        }
+        let mut formatted = format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            date_time.year,
+            date_time.month,
+            date_time.day,
+            date_time.hour,
+            date_time.minute,
+            date_time.second
+        );
+        if let Some(offset) = date_time.offset {
+            let sign = if offset < 0 { '-' } else { '+' };
+            let offset = offset.unsigned_abs();
+            formatted.push_str(&format!("{sign}{:02}:{:02}", offset / 60, offset % 60));
+        }
+        Some(formatted)
+    })
+}
+
+fn exif_gps(exif: &Exif) -> Option<GpsCoordinates> {
+    let coordinate = |tag: Tag, ref_tag: Tag, negative_ref: &str| {
+        let Value::Rational(dms) = &exif.get_field(tag, In::PRIMARY)?.value else {
+            return None;
+        };
+        let degrees = dms
+            .iter()
+            .take(3)
+            .zip([1.0, 60.0, 3600.0])
+            .map(|(part, divisor)| part.to_f64() / divisor)
+            .sum::<f64>();
+        let negative =
+            exif_ascii(exif, ref_tag).is_some_and(|r| r.eq_ignore_ascii_case(negative_ref));
+        degrees
+            .is_finite()
+            .then_some(if negative { -degrees } else { degrees })
+    };
+    let latitude = coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef, "S")?;
+    let longitude = coordinate(Tag::GPSLongitude, Tag::GPSLongitudeRef, "W")?;
+
+    let altitude = exif
+        .get_field(Tag::GPSAltitude, In::PRIMARY)
+        .and_then(|field| match &field.value {
+            Value::Rational(values) => values.first().map(|r| r.to_f64()),
+            _ => None,
+        });
+    let below_sea_level = exif
+        .get_field(Tag::GPSAltitudeRef, In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        == Some(1);
+    let altitude = altitude
+        .filter(|altitude| altitude.is_finite())
+        .map(|altitude| if below_sea_level { -altitude } else { altitude });
+
+    Some(GpsCoordinates {
+        latitude,
+        longitude,
+        altitude,
+    })
+}
+
+/// Returns the `<x:xmpmeta>` packet embedded in the file, if there is one near its start
+fn read_xmp_packet(path: &Path) -> Option<String> {
+    const START: &[u8] = b"<x:xmpmeta";
+    const END: &[u8] = b"</x:xmpmeta>";
+
+    let mut bytes = Vec::new();
+    File::open(path)
+        .ok()?
+        .take(XMP_SEARCH_BYTES)
+        .read_to_end(&mut bytes)
+        .ok()?;
+    let find = |haystack: &[u8], needle: &[u8]| {
+        haystack
+            .windows(needle.len())
+            .position(|window| window == needle)
+    };
+    let start = find(&bytes, START)?;
+    let end = start + find(&bytes[start..], END)? + END.len();
+    String::from_utf8(bytes[start..end].to_vec()).ok()
+}
+
+/// Reads a simple XMP property written either as an attribute (`name="value"`) or as an element
+/// (`<name>value</name>`)
+fn xmp_property(xmp: &str, name: &str) -> Option<String> {
+    let attribute = format!("{name}=\"");
+    let value = if let Some(start) = xmp.find(&attribute) {
+        let start = start + attribute.len();
+        &xmp[start..start + xmp[start..].find('"')?]
+    } else {
+        let open = format!("<{name}>");
+        let start = xmp.find(&open)? + open.len();
+        &xmp[start..start + xmp[start..].find(&format!("</{name}>"))?]
+    };
+    let value = value
+        .trim()
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&");
+    (!value.is_empty()).then_some(value)
+}
+
+/// Parses XMP's `DDD,MM.mmK` or `DDD,MM,SSK` coordinates, where K is N, S, E or W
+fn parse_xmp_coordinate(value: &str) -> Option<f64> {
+    let value = value.trim();
+    let direction = value.chars().last()?;
+    let parts: Vec<f64> = value[..value.len() - direction.len_utf8()]
+        .split(',')
+        .map(|part| part.trim().parse().ok())
+        .collect::<Option<_>>()?;
+    let degrees = match parts.as_slice() {
+        [degrees, minutes] => degrees + minutes / 60.0,
+        [degrees, minutes, seconds] => degrees + minutes / 60.0 + seconds / 3600.0,
+        _ => return None,
+    };
+    match direction.to_ascii_uppercase() {
+        'N' | 'E' => Some(degrees),
+        'S' | 'W' => Some(-degrees),
+        _ => None,
+    }
+}
@@ -0,0 +1,138 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::app_error::{AppError, AppResult};
+
+/// Records every folder and image an import adds or replaces, so a cancelled import can be undone.
+///
+/// Images are written to a temporary file in `work_dir` and only renamed into place once complete,
+/// so an interrupted copy never leaves a partial image in the project. Images that get replaced are
+/// moved to `work_dir` instead of being deleted until the import is committed.
+pub struct ImportJournal {
+    work_dir: PathBuf,
+    /// In the order they happened, so they can be undone in reverse
+    changes: Vec<Change>,
+    next_temp: usize,
+}
+
+enum Change {
+    CreatedDir(PathBuf),
+    Added(PathBuf),
+    Replaced {
+        image_path: PathBuf,
+        backup_path: PathBuf,
+    },
+}
+
+impl ImportJournal {
+    /// `work_dir` is emptied first, it may hold leftovers of an import the app didn't finish
+    pub fn new(work_dir: PathBuf) -> Self {
+        let _ = fs::remove_dir_all(&work_dir);
+        Self {
+            work_dir,
+            changes: Vec::new(),
+            next_temp: 0,
+        }
+    }
+
+    /// Creates a folder and any missing parents, remembering each one so a rollback can remove
+    /// them again
+    pub fn ensure_dir(&mut self, path: &Path) -> AppResult<()> {
+        let missing: Vec<&Path> = path.ancestors().take_while(|dir| !dir.is_dir()).collect();
+        if missing.is_empty() {
+            return Ok(());
+        }
+        let created = fs::create_dir_all(path);
+        // Outermost first, so they are removed deepest first. Checked one by one, a failure may
+        // have left some of them behind.
+        for dir in missing.into_iter().rev() {
+            if dir.is_dir() {
+                self.changes.push(Change::CreatedDir(dir.to_path_buf()));
+            }
+        }
+        created.map_err(|e| AppError::io(path.display(), e))
+    }
+
+    /// A fresh path to write an image to before it is moved into place with `place`
+    pub fn temp_path(&mut self) -> AppResult<PathBuf> {
+        let temp_dir = self.work_dir.join("tmp");
+        fs::create_dir_all(&temp_dir).map_err(|e| AppError::io(temp_dir.display(), e))?;
+        self.next_temp += 1;
+        Ok(temp_dir.join(self.next_temp.to_string()))
+    }
+
+    /// Moves a completely written temporary file to `dest_path`, backing up the image it replaces
+    pub fn place(&mut self, temp_path: &Path, dest_path: &Path) -> AppResult<()> {
+        let backup_path = if dest_path.exists() {
+            let backup_dir = self.work_dir.join("replaced");
+            fs::create_dir_all(&backup_dir).map_err(|e| AppError::io(backup_dir.display(), e))?;
+            let backup_path = backup_dir.join(self.changes.len().to_string());
+            fs::rename(dest_path, &backup_path)
+                .map_err(|e| AppError::io(dest_path.display(), e))?;
+            Some(backup_path)
+        } else {
+            None
+        };
+
+        if let Err(e) = fs::rename(temp_path, dest_path) {
+            let _ = fs::remove_file(temp_path);
+            if let Some(backup_path) = &backup_path {
+                let _ = fs::rename(backup_path, dest_path);
+            }
+            return Err(AppError::io(dest_path.display(), e));
+        }
+        self.changes.push(match backup_path {
+            Some(backup_path) => Change::Replaced {
+                image_path: dest_path.to_path_buf(),
+                backup_path,
+            },
+            None => Change::Added(dest_path.to_path_buf()),
+        });
+        Ok(())
+    }
+
+    /// Removes the added images and folders and puts replaced images back
+    pub fn roll_back(self) {
+        for change in self.changes.iter().rev() {
+            match change {
+                Change::CreatedDir(dir) => {
+                    // Fails if something else was put there meanwhile, which is then kept
+                    let _ = fs::remove_dir(dir);
+                }
+                Change::Added(path) => {
+                    if let Err(e) = fs::remove_file(path) {
+                        log::warn!("Failed to remove {} during rollback: {e}", path.display());
+                    }
+                }
+                Change::Replaced {
+                    image_path,
+                    backup_path,
+                } => {
+                    let _ = fs::remove_file(image_path);
+                    if let Err(e) = fs::rename(backup_path, image_path) {
+                        log::warn!(
+                            "Failed to restore {} during rollback: {e}",
+                            image_path.display()
+                        );
+                    }
+                }
+            }
+        }
+        self.remove_work_dir();
+    }
+
+    /// Keeps everything imported and drops the backups of replaced images
+    pub fn commit(self) {
+        self.remove_work_dir();
+    }
+
+    fn remove_work_dir(&self) {
+        if let Err(e) = fs::remove_dir_all(&self.work_dir) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                log::warn!("Failed to remove {}: {e}", self.work_dir.display());
+            }
+        }
+    }
+}
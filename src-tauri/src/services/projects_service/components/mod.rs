@@ -1,3 +1,17 @@
+pub mod archive_extractor;
+pub mod barcode_reader;
+pub mod cancellation_registry;
+pub mod evaluation_providers;
+pub mod evaluation_retry;
+pub mod full_image_cache;
+pub mod image_decoders;
 pub mod image_evaluations;
+pub mod image_index;
 pub mod image_loader;
-pub mod image_exporter;
\ No newline at end of file
+pub mod image_metadata;
+pub mod import_journal;
+pub mod image_exporter;
+pub mod offline_ocr;
+pub mod project_locks;
+pub mod project_watcher;
+pub mod thumbnail_cache;
\ No newline at end of file
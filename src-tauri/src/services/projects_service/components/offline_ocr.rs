@@ -0,0 +1,378 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, OnceLock},
+};
+
+use futures::future::BoxFuture;
+use ocr_image_thing::ImageEvaluationResult;
+use regex::Regex;
+
+use crate::{
+    app_error::{AppError, AppResult},
+    services::projects_service::components::evaluation_providers::{
+        EvaluationFailure, EvaluationProvider, ProviderResult,
+    },
+};
+
+/// Directory in the app save dir the OCR models are read from
+pub const OCR_MODELS_DIR: &str = "ocr_models";
+/// The ocrs text detection and recognition models, in the `.rten` format
+pub const OCR_MODEL_FILES: [&str; 2] = ["text-detection.rten", "text-recognition.rten"];
+
+/// Unit tags as they're painted or labeled on equipment, e.g. `AHU-1`, `RTU 12`, `P-3A`.
+/// Single letter prefixes need a dash so words like "B 12" aren't read as tags. Ratings like
+/// `AC 208V` or `HP 1/2` match too, `NameplateIdentifiers::extract` filters them out.
+fn unit_tag_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"\b(?:(?P<prefix>AHU|RTU|MAU|DOAS|ERV|HRV|FCU|VAV|ACCU|CU|AC|HP|EF|SF|RF|UH|CUH|CH|CT|WH|BLR|HX)(?:-|(?P<space> ))|(?P<short_prefix>P|B|E)-)(?P<number>\d{1,3})(?P<letter>[A-Z])?(?:(?P<fraction>[/.,]\d)|\b)",
+        )
+        .unwrap()
+    })
+}
+
+/// Letters of electrical units, e.g. the `V` in `208V`
+const UNIT_LETTERS: [&str; 3] = ["A", "V", "W"];
+
+/// Labeled serial numbers, e.g. `SERIAL NO. 2419G20345` or `S/N: AB1234`. Expects uppercase text.
+pub fn serial_number_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"\b(?:SERIAL(?: ?(?:NUMBER|NO|#))?|SER\.? ?NO|S/N|SN)\.?\s*[:#.]?\s*([A-Z0-9][A-Z0-9-]{3,})",
+        )
+        .unwrap()
+    })
+}
+
+fn model_number_regex() -> &'static Regex {
+    static RE: OnceLock<Regex> = OnceLock::new();
+    RE.get_or_init(|| {
+        Regex::new(
+            r"\b(?:MODEL(?: ?(?:NUMBER|NO|#))?|MOD\.? ?NO|M/N|MDL)\.?\s*[:#.]?\s*([A-Z0-9][A-Z0-9-]{2,})",
+        )
+        .unwrap()
+    })
+}
+
+/// Words on a nameplate that give away the equipment type, checked in order
+const EQUIPMENT_TYPES: &[(&str, &str)] = &[
+    ("AIR HANDL", "Air handling unit"),
+    ("ROOFTOP", "Rooftop unit"),
+    ("CONDENSING UNIT", "Condensing unit"),
+    ("HEAT PUMP", "Heat pump"),
+    ("CHILLER", "Chiller"),
+    ("COOLING TOWER", "Cooling tower"),
+    ("BOILER", "Boiler"),
+    ("WATER HEATER", "Water heater"),
+    ("FURNACE", "Furnace"),
+    ("COMPRESSOR", "Compressor"),
+    ("PUMP", "Pump"),
+    ("EXHAUST FAN", "Exhaust fan"),
+    ("FAN", "Fan"),
+    ("PANELBOARD", "Electrical panel"),
+    ("TRANSFORMER", "Transformer"),
+    ("DISCONNECT", "Disconnect switch"),
+    ("MOTOR", "Motor"),
+];
+
+/// Identifiers read off a nameplate
+#[derive(Default, Debug, PartialEq)]
+pub struct NameplateIdentifiers {
+    pub unit_tag: Option<String>,
+    pub serial_number: Option<String>,
+    pub model_number: Option<String>,
+    pub equipment_type: Option<&'static str>,
+}
+
+impl NameplateIdentifiers {
+    /// Finds identifiers in OCR text with fixed rules, since there's no model to ask
+    pub fn extract(text: &str) -> Self {
+        let text = text.to_uppercase();
+        let unit_tag = unit_tag_regex()
+            .captures_iter(&text)
+            .filter(|caps| {
+                // `HP 1/2` and `HP 1.5` are ratings, and so is `AC 208V` unless written as a tag
+                // with a dash
+                let letter = caps.name("letter").map(|m| m.as_str());
+                let unit_after_space = caps.name("space").is_some()
+                    && letter.is_some_and(|l| UNIT_LETTERS.contains(&l));
+                caps.name("fraction").is_none() && !unit_after_space
+            })
+            .map(|caps| {
+                let prefix = caps
+                    .name("prefix")
+                    .or_else(|| caps.name("short_prefix"))
+                    .map_or("", |m| m.as_str());
+                let letter = caps.name("letter").map_or("", |m| m.as_str());
+                format!("{prefix}-{}{letter}", &caps["number"])
+            })
+            .next();
+        let first_capture = |re: &Regex| re.captures(&text).map(|caps| caps[1].to_string());
+        Self {
+            unit_tag,
+            serial_number: first_capture(serial_number_regex()),
+            model_number: first_capture(model_number_regex()),
+            equipment_type: EQUIPMENT_TYPES
+                .iter()
+                .find(|(keyword, _)| text.contains(keyword))
+                .map(|(_, equipment_type)| *equipment_type),
+        }
+    }
+
+    /// The most specific identifier, preferring unit tags over serial numbers and serial numbers
+    /// over model numbers like the model prompt does.
+    /// Returns its description, filename suffix prefix and value.
+    fn best_identifier(&self) -> Option<(&'static str, &'static str, &str)> {
+        let candidates = [
+            ("unit tag", "UNIT", &self.unit_tag),
+            ("serial number", "SERIAL", &self.serial_number),
+            ("model number", "MODEL", &self.model_number),
+        ];
+        candidates
+            .into_iter()
+            .find_map(|(label, prefix, value)| Some((label, prefix, value.as_deref()?)))
+    }
+
+    pub fn filepath_suffix(&self) -> Option<String> {
+        let (_, prefix, value) = self.best_identifier()?;
+        Some(format!("_{prefix}_{value}"))
+    }
+
+    pub fn brief_description(&self) -> String {
+        let equipment_type = self.equipment_type.unwrap_or("Equipment");
+        match self.best_identifier() {
+            Some((label, _, value)) => format!("{equipment_type} with {label} {value}"),
+            None => format!("{equipment_type}, no identifier found"),
+        }
+    }
+}
+
+/// Reads nameplates with a local OCR engine and picks identifiers out of the text with fixed
+/// rules. Nothing leaves the machine. Ignores the custom prompt and temperature.
+pub struct OfflineOcrProvider {
+    models_dir: PathBuf,
+    /// Loading the models takes a while, so the engine is loaded on first use and kept
+    engine: OnceLock<Arc<OcrEngine>>,
+}
+
+impl OfflineOcrProvider {
+    /// Fails if the OCR models aren't in `models_dir`
+    pub fn new(models_dir: PathBuf) -> AppResult<Self> {
+        for file in OCR_MODEL_FILES {
+            if !models_dir.join(file).is_file() {
+                return Err(AppError::NotFound(format!(
+                    "Offline OCR needs {} in {}",
+                    OCR_MODEL_FILES.join(" and "),
+                    models_dir.display()
+                )));
+            }
+        }
+        Ok(Self {
+            models_dir,
+            engine: OnceLock::new(),
+        })
+    }
+
+    /// The engine, loaded on a blocking thread the first time it's needed. Runs started at the
+    /// same time may both load it, only one engine is kept.
+    async fn engine(&self) -> AppResult<Arc<OcrEngine>> {
+        if let Some(engine) = self.engine.get() {
+            return Ok(engine.clone());
+        }
+        let models_dir = self.models_dir.clone();
+        let engine = tokio::task::spawn_blocking(move || load_engine(&models_dir))
+            .await
+            .unwrap_or_else(|e| Err(AppError::Internal(format!("OCR task failed: {e}"))))?;
+        Ok(self.engine.get_or_init(|| Arc::new(engine)).clone())
+    }
+
+    fn outcome(image_path: String, text: AppResult<String>) -> ProviderResult {
+        let text = text.map_err(|e| EvaluationFailure::other(e.to_string()))?;
+        let identifiers = NameplateIdentifiers::extract(&text);
+        Ok(ImageEvaluationResult {
+            full_model_output: text,
+            new_suggested_filepath_suffix: identifiers.filepath_suffix(),
+            brief_description: identifiers.brief_description(),
+            original_image_path: image_path,
+        })
+    }
+}
+
+impl EvaluationProvider for OfflineOcrProvider {
+    fn evaluate_images(
+        &self,
+        image_paths: Vec<String>,
+        _custom_prompt: Option<String>,
+        _temperature: Option<f32>,
+    ) -> BoxFuture<'_, Vec<ProviderResult>> {
+        Box::pin(async move {
+            let texts = match self.engine().await {
+                Ok(engine) => {
+                    let paths = image_paths.clone();
+                    tokio::task::spawn_blocking(move || read_texts(&engine, &paths))
+                        .await
+                        .map_err(|e| AppError::Internal(format!("OCR task failed: {e}")))
+                }
+                Err(e) => Err(e),
+            };
+            match texts {
+                Ok(texts) => image_paths
+                    .into_iter()
+                    .zip(texts)
+                    .map(|(path, text)| Self::outcome(path, text))
+                    .collect(),
+                Err(e) => image_paths
+                    .into_iter()
+                    .map(|path| Self::outcome(path, Err(e.clone())))
+                    .collect(),
+            }
+        })
+    }
+}
+
+#[cfg(feature = "offline-ocr")]
+use ocrs::OcrEngine;
+
+/// Stands in for the engine in builds without offline OCR. It has no values, so it never loads.
+#[cfg(not(feature = "offline-ocr"))]
+enum OcrEngine {}
+
+/// Loads the detection and recognition models from `models_dir`
+#[cfg(feature = "offline-ocr")]
+fn load_engine(models_dir: &Path) -> AppResult<OcrEngine> {
+    let load_model = |file: &str| {
+        let path = models_dir.join(file);
+        rten::Model::load_file(&path).map_err(|e| {
+            AppError::Decode(format!(
+                "Failed to load the OCR model {}: {e}",
+                path.display()
+            ))
+        })
+    };
+    OcrEngine::new(ocrs::OcrEngineParams {
+        detection_model: Some(load_model(OCR_MODEL_FILES[0])?),
+        recognition_model: Some(load_model(OCR_MODEL_FILES[1])?),
+        ..Default::default()
+    })
+    .map_err(|e| AppError::Internal(format!("Failed to start OCR: {e}")))
+}
+
+/// Runs OCR over each image
+#[cfg(feature = "offline-ocr")]
+fn read_texts(engine: &OcrEngine, image_paths: &[String]) -> Vec<AppResult<String>> {
+    let read_text = |path: &Path| -> AppResult<String> {
+        let ocr_error = |e: &dyn std::fmt::Display| {
+            AppError::Evaluation(format!("OCR failed on {}: {e}", path.display()))
+        };
+        let image = image::open(path)
+            .map_err(|e| AppError::Decode(format!("Failed to decode {}: {e}", path.display())))?
+            .into_rgb8();
+        let source = ocrs::ImageSource::from_bytes(image.as_raw(), image.dimensions())
+            .map_err(|e| ocr_error(&e))?;
+        let input = engine.prepare_input(source).map_err(|e| ocr_error(&e))?;
+        engine.get_text(&input).map_err(|e| ocr_error(&e))
+    };
+    image_paths
+        .iter()
+        .map(|path| read_text(Path::new(path)))
+        .collect()
+}
+
+#[cfg(not(feature = "offline-ocr"))]
+fn load_engine(_models_dir: &Path) -> AppResult<OcrEngine> {
+    Err(AppError::Evaluation(
+        "This build can't run OCR offline".to_string(),
+    ))
+}
+
+#[cfg(not(feature = "offline-ocr"))]
+fn read_texts(engine: &OcrEngine, _image_paths: &[String]) -> Vec<AppResult<String>> {
+    match *engine {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit_tag(text: &str) -> Option<String> {
+        NameplateIdentifiers::extract(text).unit_tag
+    }
+
+    #[test]
+    fn reads_unit_tags() {
+        assert_eq!(unit_tag("ahu-1 supply").as_deref(), Some("AHU-1"));
+        assert_eq!(unit_tag("RTU 12").as_deref(), Some("RTU-12"));
+        assert_eq!(unit_tag("PUMP P-3A").as_deref(), Some("P-3A"));
+        assert_eq!(unit_tag("TAG: EF-4.").as_deref(), Some("EF-4"));
+        assert_eq!(unit_tag("AHU-1A, AHU-1B").as_deref(), Some("AHU-1A"));
+    }
+
+    #[test]
+    fn ignores_ratings_and_loose_numbers() {
+        assert_eq!(unit_tag("AC 208V 3PH"), None);
+        assert_eq!(unit_tag("480 VOLTS AC 60HZ"), None);
+        assert_eq!(unit_tag("HP 1/2"), None);
+        assert_eq!(unit_tag("HP 1.5 RPM 1725"), None);
+        assert_eq!(unit_tag("FLA 12A HP 5W"), None);
+        assert_eq!(unit_tag("ROOM B 12"), None);
+        assert_eq!(unit_tag("RTU12"), None);
+        assert_eq!(unit_tag("AHU-1234"), None);
+        // A rating doesn't hide a real tag after it
+        assert_eq!(unit_tag("AC 208V UNIT CU-2").as_deref(), Some("CU-2"));
+    }
+
+    #[test]
+    fn reads_serial_and_model_numbers() {
+        let identifiers =
+            NameplateIdentifiers::extract("Model No. 48TCDA04\nSerial Number: 2419G20345");
+        assert_eq!(identifiers.model_number.as_deref(), Some("48TCDA04"));
+        assert_eq!(identifiers.serial_number.as_deref(), Some("2419G20345"));
+
+        let identifiers = NameplateIdentifiers::extract("M/N: XR-16 S/N AB1234");
+        assert_eq!(identifiers.model_number.as_deref(), Some("XR-16"));
+        assert_eq!(identifiers.serial_number.as_deref(), Some("AB1234"));
+    }
+
+    #[test]
+    fn recognizes_equipment_types_in_order() {
+        let extract = |text| NameplateIdentifiers::extract(text).equipment_type;
+        assert_eq!(extract("Exhaust fan EF-1"), Some("Exhaust fan"));
+        assert_eq!(extract("Heat pump, fan motor"), Some("Heat pump"));
+        assert_eq!(extract("Nothing to see"), None);
+    }
+
+    #[test]
+    fn prefers_unit_tags_then_serial_then_model_numbers() {
+        let text = "ROOFTOP UNIT RTU-3 MODEL 48TC SERIAL 2419G20345";
+        let identifiers = NameplateIdentifiers::extract(text);
+        assert_eq!(
+            identifiers.filepath_suffix().as_deref(),
+            Some("_UNIT_RTU-3")
+        );
+        assert_eq!(
+            identifiers.brief_description(),
+            "Rooftop unit with unit tag RTU-3"
+        );
+
+        let identifiers = NameplateIdentifiers::extract("MODEL 48TC SERIAL 2419G20345");
+        assert_eq!(
+            identifiers.filepath_suffix().as_deref(),
+            Some("_SERIAL_2419G20345")
+        );
+
+        let identifiers = NameplateIdentifiers::extract("MODEL 48TC");
+        assert_eq!(
+            identifiers.filepath_suffix().as_deref(),
+            Some("_MODEL_48TC")
+        );
+
+        let identifiers = NameplateIdentifiers::extract("blank plate");
+        assert_eq!(identifiers.filepath_suffix(), None);
+        assert_eq!(
+            identifiers.brief_description(),
+            "Equipment, no identifier found"
+        );
+    }
+}
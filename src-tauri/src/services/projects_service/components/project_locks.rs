@@ -0,0 +1,28 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+/// Hands out one async lock per project so that read-modify-write cycles on project state
+/// (image files, folders, `image_evals.json`) never interleave.
+/// Hold it for a single change, not for long running work like evaluations.
+#[derive(Default)]
+pub struct ProjectLocksComponent {
+    locks: Mutex<HashMap<String, Arc<AsyncMutex<()>>>>,
+}
+
+impl ProjectLocksComponent {
+    /// Waits until no other mutation of the project is in progress.
+    /// The project stays locked until the returned guard is dropped.
+    pub async fn lock(&self, project_name: &str) -> OwnedMutexGuard<()> {
+        let lock = {
+            let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+            // Locks nobody holds or waits for are only referenced by the map
+            locks.retain(|_, lock| Arc::strong_count(lock) > 1);
+            locks.entry(project_name.to_string()).or_default().clone()
+        };
+        lock.lock_owned().await
+    }
+}
@@ -0,0 +1,80 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::{Mutex, MutexGuard},
+    time::Duration,
+};
+
+use notify_debouncer_mini::{
+    new_debouncer,
+    notify::{RecommendedWatcher, RecursiveMode},
+    DebounceEventResult, Debouncer,
+};
+
+use crate::app_error::{AppError, AppResult};
+
+/// How long a project's files must stay untouched before a change is reported, so a sync tool
+/// copying hundreds of files causes one update rather than hundreds
+const DEBOUNCE_TIMEOUT: Duration = Duration::from_millis(750);
+
+/// Watches the `images/` directory of every opened project for changes made outside the app,
+/// e.g. files dropped in with Explorer or by a sync tool
+#[derive(Default)]
+pub struct ProjectWatcherComponent {
+    /// Keyed by project name. Dropping a debouncer stops its watcher.
+    watchers: Mutex<HashMap<String, Debouncer<RecommendedWatcher>>>,
+}
+
+impl ProjectWatcherComponent {
+    /// Starts watching `images_dir` and its folders, replacing an earlier watcher of the project.
+    /// `on_change` is called on the watcher's own thread once a burst of changes settles down.
+    /// Changes that only touch hidden files, like the app's own temporary files, are ignored.
+    pub fn watch(
+        &self,
+        project_name: &str,
+        images_dir: &Path,
+        on_change: impl Fn() + Send + 'static,
+    ) -> AppResult<()> {
+        let watched_dir = images_dir.to_path_buf();
+        let handle_events = move |result: DebounceEventResult| match result {
+            Ok(events) => {
+                if events
+                    .iter()
+                    .any(|event| !Self::is_hidden(&watched_dir, &event.path))
+                {
+                    on_change();
+                }
+            }
+            Err(e) => log::warn!("Error watching {}: {e}", watched_dir.display()),
+        };
+        let mut debouncer = new_debouncer(DEBOUNCE_TIMEOUT, handle_events)
+            .map_err(|e| AppError::Internal(format!("Failed to start watching files: {e}")))?;
+        debouncer
+            .watcher()
+            .watch(images_dir, RecursiveMode::Recursive)
+            .map_err(|e| {
+                AppError::Internal(format!("Failed to watch {}: {e}", images_dir.display()))
+            })?;
+        self.lock_watchers()
+            .insert(project_name.to_string(), debouncer);
+        Ok(())
+    }
+
+    /// Stops watching a project. Returns false if it wasn't watched.
+    pub fn unwatch(&self, project_name: &str) -> bool {
+        self.lock_watchers().remove(project_name).is_some()
+    }
+
+    /// Whether any part of `path` below `watched_dir` starts with a dot
+    fn is_hidden(watched_dir: &Path, path: &Path) -> bool {
+        path.strip_prefix(watched_dir).is_ok_and(|relative| {
+            relative
+                .components()
+                .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+        })
+    }
+
+    fn lock_watchers(&self) -> MutexGuard<'_, HashMap<String, Debouncer<RecommendedWatcher>>> {
+        self.watchers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,238 @@
+use std::{
+    collections::HashSet,
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::UNIX_EPOCH,
+};
+
+use crate::{
+    app_error::{AppError, AppResult},
+    services::{
+        app_save_service::AppSaveService,
+        projects_service::{components::full_image_cache::ImageBytes, models::ImagePreviewModel},
+    },
+};
+
+/// Number of hex characters of the BLAKE3 digest used as a thumbnail file name
+const THUMBNAIL_KEY_LEN: usize = 32;
+/// Hashed into every thumbnail key. Bumping it makes all existing thumbnails stale, so they are
+/// regenerated and pruned. 2: thumbnails follow the EXIF orientation.
+const THUMBNAIL_FORMAT_VERSION: u32 = 2;
+/// Subdirectory of `.thumbs/` that thumbnails are written to before they're renamed into place.
+/// `prune` only looks at files directly in `.thumbs/`, so it never deletes a write in progress.
+const TMP_DIR: &str = "tmp";
+/// Makes temporary file names unique when the same thumbnail is written twice at once
+static TMP_FILE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Everything in an `ImagePreviewModel` except the thumbnail itself, which is stored next to it
+/// as a plain `.jpg`, and the metadata, which lives in the image index
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ThumbnailSidecar {
+    image_size_bytes: u64,
+    width: u32,
+    height: u32,
+    content_hash: Option<String>,
+    perceptual_hash: Option<String>,
+}
+
+/// Keeps generated previews in `projects/{name}/.thumbs/` so they survive restarts.
+///
+/// Each thumbnail is keyed by the image's relative name, modification time and size, so an image
+/// that is edited, moved or deleted simply stops matching its old thumbnail. Thumbnails that no
+/// longer match any image are removed by `prune`.
+pub struct ThumbnailCacheComponent {
+    app_save: Arc<AppSaveService>,
+}
+
+impl ThumbnailCacheComponent {
+    pub fn new(app_save: Arc<AppSaveService>) -> Self {
+        Self { app_save }
+    }
+
+    /// Returns the stored preview for the image at `path`, if it is still up to date
+    pub fn load(
+        &self,
+        project_name: &str,
+        image_name: &str,
+        path: &Path,
+    ) -> Option<ImagePreviewModel> {
+        let key = Self::thumbnail_key(image_name, path).ok()?;
+        let sidecar_path = self.thumbs_dir(project_name).join(format!("{key}.json"));
+        let sidecar: ThumbnailSidecar = fs::read(sidecar_path)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice(&bytes).ok())?;
+
+        Some(ImagePreviewModel {
+            image_name: image_name.to_string(),
+            image_size_bytes: sidecar.image_size_bytes,
+            width: sidecar.width,
+            height: sidecar.height,
+            content_hash: sidecar.content_hash,
+            perceptual_hash: sidecar.perceptual_hash,
+            metadata: None,
+        })
+    }
+
+    /// Returns the stored JPEG thumbnail for the image at `path` and its quoted entity tag,
+    /// if it is still up to date
+    pub fn load_jpeg(
+        &self,
+        project_name: &str,
+        image_name: &str,
+        path: &Path,
+    ) -> Option<ImageBytes> {
+        let key = Self::thumbnail_key(image_name, path).ok()?;
+        let jpeg = fs::read(self.thumbs_dir(project_name).join(format!("{key}.jpg"))).ok()?;
+        Some(ImageBytes {
+            bytes: Arc::new(jpeg),
+            etag: format!("\"{key}\""),
+            content_type: "image/jpeg",
+        })
+    }
+
+    /// Saves a freshly generated preview. `jpeg` is the encoded thumbnail.
+    /// The sidecar is written last so a half-written thumbnail is never picked up by `load`.
+    pub fn store(
+        &self,
+        project_name: &str,
+        path: &Path,
+        preview: &ImagePreviewModel,
+        jpeg: &[u8],
+    ) -> AppResult<()> {
+        let key = Self::thumbnail_key(&preview.image_name, path)?;
+        let thumbs_dir = self.thumbs_dir(project_name);
+        let tmp_dir = thumbs_dir.join(TMP_DIR);
+        fs::create_dir_all(&tmp_dir).map_err(|e| AppError::io(tmp_dir.display(), e))?;
+
+        let sidecar = ThumbnailSidecar {
+            image_size_bytes: preview.image_size_bytes,
+            width: preview.width,
+            height: preview.height,
+            content_hash: preview.content_hash.clone(),
+            perceptual_hash: preview.perceptual_hash.clone(),
+        };
+        Self::write_replacing(&tmp_dir, &thumbs_dir.join(format!("{key}.jpg")), jpeg)?;
+        Self::write_replacing(
+            &tmp_dir,
+            &thumbs_dir.join(format!("{key}.json")),
+            &serde_json::to_vec(&sidecar)?,
+        )
+    }
+
+    /// Deletes thumbnails that don't belong to any of `files`, given as
+    /// `(full_path, relative_name)` pairs covering every image in the project
+    pub fn prune(&self, project_name: &str, files: &[(PathBuf, String)]) {
+        let thumbs_dir = self.thumbs_dir(project_name);
+        let Ok(entries) = fs::read_dir(&thumbs_dir) else {
+            return;
+        };
+        let live_keys: HashSet<String> = files
+            .iter()
+            .filter_map(|(path, name)| Self::thumbnail_key(name, path).ok())
+            .collect();
+
+        for entry in entries.flatten() {
+            if !entry.file_type().is_ok_and(|t| t.is_file()) {
+                continue;
+            }
+            let path = entry.path();
+            let is_live = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .is_some_and(|stem| live_keys.contains(stem));
+            if !is_live {
+                if let Err(e) = fs::remove_file(&path) {
+                    log::warn!("Failed to remove stale thumbnail {}: {e}", path.display());
+                }
+            }
+        }
+    }
+
+    fn thumbs_dir(&self, project_name: &str) -> PathBuf {
+        self.app_save
+            .get_full_path(&format!("projects/{project_name}/.thumbs"))
+    }
+
+    /// Hashes the relative name together with the file's modification time and size and the
+    /// thumbnail format version
+    fn thumbnail_key(image_name: &str, path: &Path) -> AppResult<String> {
+        let metadata = path
+            .metadata()
+            .map_err(|e| AppError::io(path.display(), e))?;
+        let modified_nanos = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_nanos())
+            .unwrap_or_default();
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(image_name.as_bytes());
+        hasher.update(&modified_nanos.to_le_bytes());
+        hasher.update(&metadata.len().to_le_bytes());
+        hasher.update(&THUMBNAIL_FORMAT_VERSION.to_le_bytes());
+        let mut key = hasher.finalize().to_hex().to_string();
+        key.truncate(THUMBNAIL_KEY_LEN);
+        Ok(key)
+    }
+
+    /// Writes to a temporary file in `tmp_dir` and renames it into place, so two previews of the
+    /// same image being generated at once never interleave their writes
+    fn write_replacing(tmp_dir: &Path, path: &Path, bytes: &[u8]) -> AppResult<()> {
+        let counter = TMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed);
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let tmp_path = tmp_dir.join(format!("{file_name}.{counter}.tmp"));
+        fs::write(&tmp_path, bytes).map_err(|e| AppError::io(tmp_path.display(), e))?;
+        fs::rename(&tmp_path, path).map_err(|e| AppError::io(path.display(), e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn prune_keeps_live_thumbnails_and_writes_in_progress() {
+        let save_dir = std::env::temp_dir().join(format!(
+            "get-crap-done-thumbs-{}-{}",
+            std::process::id(),
+            TMP_FILE_COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let cache = ThumbnailCacheComponent::new(Arc::new(AppSaveService::new(save_dir.clone())));
+        let images_dir = save_dir.join("projects/p/images");
+        fs::create_dir_all(&images_dir).unwrap();
+        let image_path = images_dir.join("photo.jpg");
+        fs::write(&image_path, b"not really a jpeg").unwrap();
+
+        let preview = ImagePreviewModel {
+            image_name: "photo.jpg".to_string(),
+            image_size_bytes: 17,
+            width: 1,
+            height: 1,
+            content_hash: None,
+            perceptual_hash: None,
+            metadata: None,
+        };
+        cache
+            .store("p", &image_path, &preview, b"thumbnail")
+            .unwrap();
+        let thumbs_dir = cache.thumbs_dir("p");
+        let stale = thumbs_dir.join("0123456789abcdef0123456789abcdef.jpg");
+        fs::write(&stale, b"old thumbnail").unwrap();
+        let in_progress = thumbs_dir.join(TMP_DIR).join("key.jpg.7.tmp");
+        fs::write(&in_progress, b"half written").unwrap();
+
+        cache.prune("p", &[(image_path.clone(), "photo.jpg".to_string())]);
+
+        assert!(!stale.exists());
+        assert!(in_progress.exists());
+        assert!(cache.load("p", "photo.jpg", &image_path).is_some());
+        assert!(cache.load_jpeg("p", "photo.jpg", &image_path).is_some());
+        let _ = fs::remove_dir_all(&save_dir);
+    }
+}
@@ -0,0 +1,291 @@
+use std::sync::Arc;
+
+use percent_encoding::percent_decode_str;
+use tauri::http::{header, Request, Response, StatusCode};
+use tauri::{Manager, Runtime, UriSchemeContext, UriSchemeResponder};
+
+use crate::app_error::{AppError, AppResult};
+use crate::services::projects_service::{
+    names::{ImageRelPath, ProjectName},
+    ProjectsService,
+};
+
+/// Scheme the webview loads project images from, e.g.
+/// `imgreader://localhost/project/{project}/image/{image}` or `.../thumb/{image}`.
+/// On Windows the webview sees it as `http://imgreader.localhost/...`.
+pub const IMAGE_PROTOCOL_SCHEME: &str = "imgreader";
+
+enum ImageKind {
+    /// The original file
+    Full,
+    /// The JPEG preview from `.thumbs/`
+    Thumbnail,
+}
+
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    /// No usable `Range` header, send the whole file
+    Full,
+    /// Inclusive byte offsets
+    Partial(usize, usize),
+    Unsatisfiable,
+}
+
+/// Entry point registered with `register_asynchronous_uri_scheme_protocol`
+pub fn handle_request<R: Runtime>(
+    ctx: UriSchemeContext<'_, R>,
+    request: Request<Vec<u8>>,
+    responder: UriSchemeResponder,
+) {
+    // Services are managed during setup, so a request can only miss them very early on
+    let Some(service) = ctx.app_handle().try_state::<Arc<ProjectsService>>() else {
+        responder.respond(text_response(
+            StatusCode::SERVICE_UNAVAILABLE,
+            "Not ready yet",
+        ));
+        return;
+    };
+    let service = service.inner().clone();
+    tauri::async_runtime::spawn(async move {
+        let response = match serve_image(&service, &request).await {
+            Ok(response) => response,
+            Err(e) => error_response(&e),
+        };
+        responder.respond(response);
+    });
+}
+
+async fn serve_image(
+    service: &ProjectsService,
+    request: &Request<Vec<u8>>,
+) -> AppResult<Response<Vec<u8>>> {
+    let (project_name, kind, image_name) = parse_path(request.uri().path())?;
+    let image = match kind {
+        ImageKind::Full => {
+            service
+                .image_loader
+                .read_image_file(&project_name, &image_name)
+                .await?
+        }
+        ImageKind::Thumbnail => {
+            service
+                .image_loader
+                .read_thumbnail(&project_name, &image_name)
+                .await?
+        }
+    };
+
+    // The URL stays the same when an image is replaced, so the webview has to revalidate
+    let builder = Response::builder()
+        .header(header::ETAG, &image.etag)
+        .header(header::CACHE_CONTROL, "no-cache")
+        .header(header::ACCEPT_RANGES, "bytes");
+
+    let if_none_match = request
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok());
+    if if_none_match.is_some_and(|tags| {
+        tags.split(',')
+            .any(|tag| tag.trim() == image.etag || tag.trim() == "*")
+    }) {
+        return builder
+            .status(StatusCode::NOT_MODIFIED)
+            .body(Vec::new())
+            .map_err(|e| AppError::Internal(e.to_string()));
+    }
+
+    let total = image.bytes.len();
+    let range = request
+        .headers()
+        .get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .map_or(ByteRange::Full, |value| parse_range(value, total));
+    let builder = builder.header(header::CONTENT_TYPE, image.content_type);
+    let response = match range {
+        // The responder needs an owned body. Thumbnails and images too large for the cache are
+        // only referenced here and are handed over as is, only cached images are copied.
+        ByteRange::Full => builder
+            .status(StatusCode::OK)
+            .header(header::CONTENT_LENGTH, total)
+            .body(Arc::unwrap_or_clone(image.bytes)),
+        ByteRange::Partial(start, end) => builder
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(
+                header::CONTENT_RANGE,
+                format!("bytes {start}-{end}/{total}"),
+            )
+            .header(header::CONTENT_LENGTH, end - start + 1)
+            .body(image.bytes[start..=end].to_vec()),
+        ByteRange::Unsatisfiable => builder
+            .status(StatusCode::RANGE_NOT_SATISFIABLE)
+            .header(header::CONTENT_RANGE, format!("bytes */{total}"))
+            .body(Vec::new()),
+    };
+    response.map_err(|e| AppError::Internal(e.to_string()))
+}
+
+/// Parses `/project/{project}/image/{image}` or `/project/{project}/thumb/{image}`.
+/// The webview's `convertFileSrc` percent-encodes the whole path, slashes included.
+fn parse_path(path: &str) -> AppResult<(ProjectName, ImageKind, ImageRelPath)> {
+    let decoded = percent_decode_str(path.trim_start_matches('/'))
+        .decode_utf8()
+        .map_err(|_| AppError::InvalidName(format!("Invalid image URL: {path}")))?;
+    let not_found = || AppError::NotFound(format!("No image at {decoded}"));
+
+    let (project_name, rest) = decoded
+        .strip_prefix("project/")
+        .and_then(|rest| rest.split_once('/'))
+        .ok_or_else(not_found)?;
+    let (kind, image_name) = if let Some(image_name) = rest.strip_prefix("image/") {
+        (ImageKind::Full, image_name)
+    } else if let Some(image_name) = rest.strip_prefix("thumb/") {
+        (ImageKind::Thumbnail, image_name)
+    } else {
+        return Err(not_found());
+    };
+    Ok((
+        ProjectName::parse(project_name)?,
+        kind,
+        ImageRelPath::parse(image_name)?,
+    ))
+}
+
+/// Supports a single `bytes=start-end`, `bytes=start-` or `bytes=-suffix` range.
+/// Anything else is ignored and the whole file is sent, which HTTP allows.
+fn parse_range(value: &str, total: usize) -> ByteRange {
+    let Some(spec) = value.trim().strip_prefix("bytes=") else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return ByteRange::Full;
+    };
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => match suffix.parse::<usize>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(suffix) => (total.saturating_sub(suffix), total.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        },
+        (start, "") => match start.parse::<usize>() {
+            Ok(start) => (start, total.saturating_sub(1)),
+            Err(_) => return ByteRange::Full,
+        },
+        (start, end) => match (start.parse::<usize>(), end.parse::<usize>()) {
+            (Ok(start), Ok(end)) if start <= end => (start, end.min(total.saturating_sub(1))),
+            _ => return ByteRange::Full,
+        },
+    };
+    if total == 0 || start >= total {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial(start, end)
+}
+
+fn error_response(error: &AppError) -> Response<Vec<u8>> {
+    let status = match error {
+        AppError::NotFound(_) => StatusCode::NOT_FOUND,
+        AppError::InvalidName(_) => StatusCode::BAD_REQUEST,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
+    };
+    text_response(status, &error.to_string())
+}
+
+fn text_response(status: StatusCode, message: &str) -> Response<Vec<u8>> {
+    let mut response = Response::new(message.as_bytes().to_vec());
+    *response.status_mut() = status;
+    response.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("text/plain"),
+    );
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_single_ranges() {
+        assert_eq!(parse_range("bytes=0-", 1000), ByteRange::Partial(0, 999));
+        assert_eq!(
+            parse_range("bytes=100-199", 1000),
+            ByteRange::Partial(100, 199)
+        );
+        assert_eq!(
+            parse_range("bytes=900-5000", 1000),
+            ByteRange::Partial(900, 999)
+        );
+        assert_eq!(
+            parse_range("bytes=-500", 1000),
+            ByteRange::Partial(500, 999)
+        );
+        assert_eq!(parse_range("bytes=-5000", 1000), ByteRange::Partial(0, 999));
+    }
+
+    #[test]
+    fn ranges_past_the_end_are_unsatisfiable() {
+        assert_eq!(parse_range("bytes=1000-", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(
+            parse_range("bytes=1000-1100", 1000),
+            ByteRange::Unsatisfiable
+        );
+        assert_eq!(parse_range("bytes=-0", 1000), ByteRange::Unsatisfiable);
+        assert_eq!(parse_range("bytes=0-", 0), ByteRange::Unsatisfiable);
+    }
+
+    #[test]
+    fn other_ranges_get_the_whole_file() {
+        assert_eq!(parse_range("bytes=0-99,200-299", 1000), ByteRange::Full);
+        assert_eq!(parse_range("bytes=200-100", 1000), ByteRange::Full);
+        assert_eq!(parse_range("bytes=abc-", 1000), ByteRange::Full);
+        assert_eq!(parse_range("bytes=100", 1000), ByteRange::Full);
+        assert_eq!(parse_range("items=0-99", 1000), ByteRange::Full);
+    }
+
+    #[test]
+    fn parses_image_and_thumbnail_paths() {
+        let (project, kind, image) = parse_path("/project/p/image/photo.jpg").unwrap();
+        assert_eq!((&*project, &*image), ("p", "photo.jpg"));
+        assert!(matches!(kind, ImageKind::Full));
+
+        // As sent by `convertFileSrc`
+        let (project, kind, image) =
+            parse_path("/project%2Fmy%20project%2Fthumb%2Ftrip%2Fphoto%201.jpg").unwrap();
+        assert_eq!((&*project, &*image), ("my project", "trip/photo 1.jpg"));
+        assert!(matches!(kind, ImageKind::Thumbnail));
+    }
+
+    #[test]
+    fn rejects_paths_leaving_the_project() {
+        for path in [
+            "/project/p/image/../secret.jpg",
+            "/project/p/image/trip%2F..%2F..%2Fsecret.jpg",
+            "/project/p/image/%2E%2E%2Fsecret.jpg",
+            "/project/%2E%2E/image/photo.jpg",
+            "/project/p/image/%FF.jpg",
+        ] {
+            assert!(
+                matches!(parse_path(path), Err(AppError::InvalidName(_))),
+                "{path} was accepted"
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_paths_are_not_found() {
+        for path in [
+            "/",
+            "/project/p",
+            "/project/p/video/clip.mp4",
+            "/other/p/image/a.jpg",
+        ] {
+            assert!(
+                matches!(parse_path(path), Err(AppError::NotFound(_))),
+                "{path} was found"
+            );
+        }
+    }
+}
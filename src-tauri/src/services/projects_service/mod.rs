@@ -1,11 +1,18 @@
+pub mod image_protocol;
 pub mod service;
 pub mod tauri_exports;
 pub use service::ProjectsService;
 mod components;
 mod models;
-use components::image_loader::ImageLoaderComponent;
+mod names;
+use components::image_loader::{ImageLoaderComponent, ImportOptions, ImportSession};
 use models::*;
-use components::image_evaluations::ImageEvaluationsComponent;
+use names::*;
+use components::cancellation_registry::CancellationRegistryComponent;
+use components::image_evaluations::{EvaluationSession, ImageEvaluationsComponent};
 use components::image_exporter::ImageExporterComponent;
+use components::image_index::{ImageIndexComponent, DEFAULT_NEAR_DUPLICATE_DISTANCE};
+use components::project_locks::ProjectLocksComponent;
+use components::project_watcher::ProjectWatcherComponent;
 
 mod requests;
\ No newline at end of file
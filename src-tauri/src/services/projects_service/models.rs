@@ -8,6 +8,74 @@ pub struct ImageEvaluation {
     pub result: Option<ImageEvaluationResult>,
     /// The reason the evaluation failed (if any)
     pub fail_reason: Option<String>,
+    /// The image was edited outside the app after it was evaluated, so the result may no longer
+    /// match it
+    #[serde(default)]
+    pub stale: bool,
+    /// QR codes and barcodes found in the image
+    #[serde(default)]
+    pub barcodes: Vec<DecodedBarcode>,
+    /// How many times the evaluation was retried after a rate limit or server error
+    #[serde(default)]
+    pub retries: u32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodedBarcode {
+    /// e.g. `qrcode` or `code 128`
+    pub format: String,
+    pub payload: String,
+}
+
+/// Which model evaluates a project's images
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default, PartialEq, Debug)]
+#[serde(tag = "kind", rename_all = "camelCase")]
+pub enum EvaluationProviderConfig {
+    /// OpenAI, using the key sent with each evaluation request
+    #[default]
+    OpenAi,
+    /// Any server with an OpenAI compatible chat completions API, e.g. a local Ollama or
+    /// llama.cpp server
+    #[serde(rename_all = "camelCase")]
+    OpenAiCompatible {
+        /// e.g. `http://localhost:11434/v1`
+        base_url: String,
+        /// A vision capable model served by the endpoint
+        model: String,
+        /// Local servers usually don't need one
+        #[serde(default)]
+        api_key: Option<String>,
+    },
+    /// Reads identifiers off nameplates with OCR on this machine, without any network
+    OfflineOcr,
+    /// Returns made up results derived from the file names without calling any model
+    Mock,
+}
+
+/// How evaluations are retried after a rate limit or a server error. Applies to every project.
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq, Debug)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RetryPolicy {
+    /// Retries per image before its failure is kept
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for every retry after it
+    pub initial_backoff_ms: u64,
+    /// Upper bound for the wait, also for waits the provider asks for with `Retry-After`
+    pub max_backoff_ms: u64,
+    /// Images being evaluated at the same time, across all projects
+    pub max_concurrent_requests: usize,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 4,
+            initial_backoff_ms: 1_000,
+            max_backoff_ms: 60_000,
+            max_concurrent_requests: 4,
+        }
+    }
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
@@ -19,24 +87,206 @@ pub struct ProjectInfoModel {
     pub last_opened_at: Option<u64>,
 }
 
-/// Image preview with thumbnail for display in lists/grids
+/// What reconciling a project's image index with its files on disk found, by image name
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconcileReport {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Images whose contents were edited since they were indexed
+    pub changed: Vec<String>,
+}
+
+impl ReconcileReport {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Sent to the frontend when a watched project's images change outside the app
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectFilesChanged {
+    pub project_name: String,
+    pub changes: ReconcileReport,
+}
+
+/// A folder in a project's images directory along with the folders nested in it
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FolderNode {
+    /// The folder's own name, e.g. `Floor 2`
+    pub name: String,
+    /// Relative to `images/`, e.g. `Building A/Floor 2`
+    pub path: String,
+    pub children: Vec<FolderNode>,
+}
+
+/// Image info for display in lists/grids. The thumbnail is served over the `imgreader` protocol.
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct ImagePreviewModel {
     pub image_name: String,
-    pub base64_preview: String,
     pub image_size_bytes: u64,
+    /// As displayed, i.e. after applying the EXIF orientation
     pub width: u32,
     pub height: u32,
+    /// Hex encoded BLAKE3 hash of the file contents
+    pub content_hash: Option<String>,
+    /// Hex encoded 64 bit difference hash, used to find near-duplicate shots
+    pub perceptual_hash: Option<String>,
+    pub metadata: Option<ImageMetadataModel>,
+}
+
+/// Capture details read from an image's EXIF and XMP data.
+/// Every field is optional, screenshots and edited files often carry none of it.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageMetadataModel {
+    /// ISO 8601 local time the photo was taken, with the UTC offset if the camera recorded one
+    pub captured_at: Option<String>,
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub gps: Option<GpsCoordinates>,
+    /// File name the image had before it was imported (and possibly renamed)
+    pub original_file_name: Option<String>,
 }
 
-/// Full resolution image data
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy)]
+#[serde(rename_all = "camelCase")]
+pub struct GpsCoordinates {
+    /// Decimal degrees, negative south of the equator
+    pub latitude: f64,
+    /// Decimal degrees, negative west of Greenwich
+    pub longitude: f64,
+    /// Meters above sea level
+    pub altitude: Option<f64>,
+}
+
+/// Full resolution image info. The image itself is served over the `imgreader` protocol.
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct FullImageModel {
     pub image_name: String,
-    pub base64_image: String,
     pub image_size_bytes: u64,
+    /// As displayed, i.e. after applying the EXIF orientation
     pub width: u32,
     pub height: u32,
 }
+
+/// Summary of an import, listing what happened to every source file
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportReport {
+    /// Relative names of images imported under their original file name
+    pub imported: Vec<String>,
+    /// Images imported under a new name to avoid a conflict
+    pub renamed: Vec<RenamedImport>,
+    /// Source files that were not imported
+    pub skipped: Vec<SkippedImport>,
+    /// Relative names of existing images that were replaced. Also listed in `imported`.
+    pub overwritten: Vec<String>,
+    /// Source files whose exact content is already in the project
+    pub duplicates: Vec<DuplicateImport>,
+    /// Project folders images were imported into when importing a directory or archive
+    #[serde(default)]
+    pub folders: Vec<String>,
+    /// The import was cancelled and everything it copied was removed again
+    #[serde(default)]
+    pub cancelled: bool,
+}
+
+/// Sent after each source file of an import is handled
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportProgress {
+    pub import_id: String,
+    /// Zero based position of the file among all files of the import
+    pub index: usize,
+    pub total: usize,
+    pub file_name: String,
+    /// Size of the source file
+    pub bytes: u64,
+    /// Combined size of the files handled so far, this one included
+    pub bytes_done: u64,
+    pub total_bytes: u64,
+    /// Sent while a zip archive is extracted, before its images are imported. Positions are of
+    /// the archive's entries, and sizes are of the compressed entries and the archive.
+    #[serde(default)]
+    pub extracting: bool,
+}
+
+/// Sent when an image of an evaluation starts and again when it's done
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EvaluationProgress {
+    pub evaluation_id: String,
+    pub project_name: String,
+    pub image_name: String,
+    pub status: EvaluationImageStatus,
+    /// Images done so far, this one included once it's done
+    pub done: usize,
+    pub total: usize,
+    /// The evaluation as saved, once the image is done. `None` if the image was deleted or
+    /// edited while it was evaluated.
+    pub evaluation: Option<ImageEvaluation>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum EvaluationImageStatus {
+    Started,
+    Completed,
+    Failed,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RenamedImport {
+    pub source_path: String,
+    /// The relative name the image was imported as
+    pub image_name: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct DuplicateImport {
+    pub source_path: String,
+    /// The image already in the project with the same content
+    pub existing_image_name: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SkippedImport {
+    pub source_path: String,
+    pub reason: String,
+}
+
+/// Images that look nearly identical, e.g. several frames of the same subject
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NearDuplicateGroup {
+    /// Relative image names, sorted
+    pub image_names: Vec<String>,
+}
+
+/// Diagnostics for the in-memory full image cache
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageCacheStats {
+    pub capacity_bytes: u64,
+    pub used_bytes: u64,
+    pub entry_count: usize,
+    pub hits: u64,
+    pub misses: u64,
+    pub evictions: u64,
+}
+
+/// One page of previews plus the number of images in the whole project
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewPage {
+    pub previews: Vec<ImagePreviewModel>,
+    pub total: usize,
+}
@@ -0,0 +1,228 @@
+use std::{fmt, ops::Deref};
+
+use crate::app_error::{AppError, AppResult};
+
+/// Characters that are separators or otherwise invalid in Windows file names
+const FORBIDDEN_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+/// Device names Windows refuses to use as file names, with or without an extension
+const RESERVED_WINDOWS_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+/// Most file systems cap a single path component at 255 bytes
+const MAX_SEGMENT_LEN: usize = 255;
+
+/// A project directory name, e.g. `Site Survey 2024`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ProjectName(String);
+
+/// A folder inside a project's `images/` directory, nested with `/`, e.g. `Building A/Floor 2`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct FolderName(String);
+
+/// An image path relative to a project's `images/` directory, e.g. `photo.jpg` or `Floor 2/photo.jpg`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ImageRelPath(String);
+
+impl ProjectName {
+    pub fn parse(name: &str) -> AppResult<Self> {
+        validate_segment("Project name", name)?;
+        Ok(Self(name.to_string()))
+    }
+}
+
+impl FolderName {
+    /// Parses a `/`-separated folder path, validating every segment
+    pub fn parse(name: &str) -> AppResult<Self> {
+        if name.is_empty() {
+            return Err(AppError::InvalidName(
+                "Folder name can't be empty".to_string(),
+            ));
+        }
+        for segment in name.split('/') {
+            validate_segment("Folder name", segment)?;
+        }
+        Ok(Self(name.to_string()))
+    }
+
+    /// The folder this one is nested in, `None` for a top-level folder
+    pub fn parent(&self) -> Option<&str> {
+        self.0.rsplit_once('/').map(|(parent, _)| parent)
+    }
+
+    /// Parses an optional folder where `None` or an empty string means the project root
+    pub fn parse_optional(name: Option<&str>) -> AppResult<Option<Self>> {
+        name.filter(|n| !n.is_empty()).map(Self::parse).transpose()
+    }
+}
+
+impl ImageRelPath {
+    /// Parses a `/`-separated relative path, validating every segment
+    pub fn parse(path: &str) -> AppResult<Self> {
+        if path.is_empty() {
+            return Err(AppError::InvalidName(
+                "Image name can't be empty".to_string(),
+            ));
+        }
+        for segment in path.split('/') {
+            validate_segment("Image path segment", segment)?;
+        }
+        Ok(Self(path.to_string()))
+    }
+
+    pub fn parse_all(paths: &[String]) -> AppResult<Vec<Self>> {
+        paths.iter().map(|p| Self::parse(p)).collect()
+    }
+}
+
+/// Rejects anything that could escape the directory it is joined onto or that Windows can't store
+fn validate_segment(kind: &str, segment: &str) -> AppResult<()> {
+    let invalid = |reason: &str| AppError::InvalidName(format!("{kind} \"{segment}\" {reason}"));
+
+    if segment.trim().is_empty() {
+        return Err(invalid("can't be empty"));
+    }
+    // Covers `.` and `..`, and hidden names, which the index and folder tree skip so they would
+    // disappear from the app
+    if segment.starts_with('.') {
+        return Err(invalid("can't start with a dot"));
+    }
+    if segment.len() > MAX_SEGMENT_LEN {
+        return Err(invalid("is too long"));
+    }
+    if let Some(c) = segment.chars().find(|c| FORBIDDEN_CHARS.contains(c)) {
+        return Err(invalid(&format!("can't contain '{c}'")));
+    }
+    if segment.chars().any(char::is_control) {
+        return Err(invalid("can't contain control characters"));
+    }
+    // Windows silently strips these, so `name.` and `name` would collide
+    if segment.ends_with('.') || segment.ends_with(' ') {
+        return Err(invalid("can't end with a dot or a space"));
+    }
+    let stem = segment.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_WINDOWS_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        return Err(invalid("is a reserved name on Windows"));
+    }
+    Ok(())
+}
+
+macro_rules! impl_name_traits {
+    ($($name:ident),*) => {$(
+        impl Deref for $name {
+            type Target = str;
+
+            fn deref(&self) -> &str {
+                &self.0
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(&self.0)
+            }
+        }
+
+        impl From<$name> for String {
+            fn from(name: $name) -> Self {
+                name.0
+            }
+        }
+    )*};
+}
+
+impl_name_traits!(ProjectName, FolderName, ImageRelPath);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn is_valid(segment: &str) -> bool {
+        validate_segment("Name", segment).is_ok()
+    }
+
+    #[test]
+    fn accepts_ordinary_names() {
+        assert!(is_valid("photo.jpg"));
+        assert!(is_valid("Site Survey 2024"));
+        assert!(is_valid("CONSOLE.jpg"));
+        assert!(is_valid("Öl-Pumpe №3.png"));
+    }
+
+    #[test]
+    fn rejects_reserved_windows_names() {
+        for name in [
+            "CON",
+            "con",
+            "Nul",
+            "PRN.jpg",
+            "aux.tar.gz",
+            "COM1",
+            "lpt9.png",
+            "CON .txt",
+        ] {
+            assert!(!is_valid(name), "{name} should be rejected");
+        }
+    }
+
+    #[test]
+    fn rejects_trailing_dot_or_space() {
+        assert!(!is_valid("photo."));
+        assert!(!is_valid("photo "));
+        assert!(!is_valid("photo.jpg."));
+    }
+
+    #[test]
+    fn rejects_dot_segments_and_empty_names() {
+        assert!(!is_valid("."));
+        assert!(!is_valid(".."));
+        assert!(!is_valid(""));
+        assert!(!is_valid("   "));
+        assert!(ImageRelPath::parse("../photo.jpg").is_err());
+        assert!(ImageRelPath::parse("Floor 2/../../photo.jpg").is_err());
+        assert!(FolderName::parse("Floor 2//Room").is_err());
+    }
+
+    #[test]
+    fn rejects_hidden_names() {
+        assert!(!is_valid(".hidden"));
+        assert!(!is_valid(".photo.jpg"));
+        assert!(FolderName::parse("Floor 2/.thumbs").is_err());
+        assert!(ImageRelPath::parse(".upright/photo.jpg").is_err());
+        assert!(is_valid("photo.v2.jpg"));
+    }
+
+    #[test]
+    fn rejects_separators_and_forbidden_chars() {
+        for name in [
+            "a/b", "a\\b", "C:", "a*b", "a?b", "a\"b", "a<b", "a>b", "a|b", "a\0b", "a\nb",
+        ] {
+            assert!(!is_valid(name), "{name:?} should be rejected");
+        }
+        assert!(ProjectName::parse("Project/Other").is_err());
+        assert!(ImageRelPath::parse("Floor 2\\photo.jpg").is_err());
+    }
+
+    #[test]
+    fn limits_segments_to_255_bytes() {
+        assert!(is_valid(&"a".repeat(255)));
+        assert!(!is_valid(&"a".repeat(256)));
+        // Counted in bytes, not characters
+        assert!(is_valid(&"é".repeat(127)));
+        assert!(!is_valid(&"é".repeat(128)));
+        // The limit applies to each segment, not the whole path
+        let long_path = format!("{}/{}", "a".repeat(255), "b".repeat(255));
+        assert!(ImageRelPath::parse(&long_path).is_ok());
+    }
+
+    #[test]
+    fn parses_nested_paths() {
+        let folder = FolderName::parse("Building A/Floor 2").unwrap();
+        assert_eq!(folder.parent(), Some("Building A"));
+        assert_eq!(FolderName::parse_optional(Some("")).unwrap(), None);
+        assert!(ImageRelPath::parse("Building A/Floor 2/photo.jpg").is_ok());
+    }
+}
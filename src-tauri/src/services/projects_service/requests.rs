@@ -1,7 +1,92 @@
+use crate::services::projects_service::models::EvaluationProviderConfig;
+
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct RequestImageEvaluation {
     /// Image names that are already in the project
     pub image_names: Vec<String>,
+    /// Only needed when the images are evaluated by OpenAI
+    #[serde(default)]
     pub openai_api_key: String,
+    /// Overrides the project's evaluation provider for this request
+    #[serde(default)]
+    pub provider: Option<EvaluationProviderConfig>,
+    /// Use a serial number decoded from a barcode as the filename suffix instead of the
+    /// evaluator's pick. Images the evaluator failed on still get a result if they have one.
+    #[serde(default)]
+    pub prefer_barcode_serial: bool,
+}
+
+/// What to do when an imported file has the same name as an image already in the target folder
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum ImportConflictPolicy {
+    /// Keep the existing image and don't import the new file
+    Skip,
+    /// Replace the existing image and drop its now stale evaluation
+    Overwrite,
+    /// Import as `name_2.jpg`, `name_3.jpg`, ...
+    #[default]
+    AutoRename,
+    /// Import as `name_<content hash prefix>.jpg`
+    RenameByHash,
+}
+
+/// Imports every image in a directory tree
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestDirectoryImport {
+    pub directory_path: String,
+    /// Project folder to import into, the project root if not set
+    #[serde(default)]
+    pub folder: Option<String>,
+    /// Import images in subdirectories into project folders named after them
+    #[serde(default)]
+    pub preserve_structure: bool,
+    #[serde(default)]
+    pub conflict_policy: ImportConflictPolicy,
+    /// Store HEIC/HEIF and RAW photos as JPEG
+    #[serde(default)]
+    pub transcode_to_jpeg: bool,
+}
+
+/// Imports the images in a zip archive
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestZipImport {
+    pub archive_path: String,
+    /// Project folder to import into. Takes precedence over `use_archive_folder`.
+    #[serde(default)]
+    pub folder: Option<String>,
+    /// Import into a folder named after the archive, e.g. `survey.zip` into `survey`
+    #[serde(default)]
+    pub use_archive_folder: bool,
+    #[serde(default)]
+    pub conflict_policy: ImportConflictPolicy,
+    /// Store HEIC/HEIF and RAW photos as JPEG
+    #[serde(default)]
+    pub transcode_to_jpeg: bool,
+}
+
+/// Order in which paginated previews are returned
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, Default, PartialEq, Eq, Debug)]
+#[serde(rename_all = "camelCase")]
+pub enum PreviewSortKey {
+    /// Relative image name, case insensitive
+    #[default]
+    Name,
+    Size,
+    /// Last modification time of the file
+    Modified,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RequestPreviewPage {
+    pub offset: usize,
+    pub limit: usize,
+    #[serde(default)]
+    pub sort_key: PreviewSortKey,
+    #[serde(default)]
+    pub descending: bool,
 }
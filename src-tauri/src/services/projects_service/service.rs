@@ -1,9 +1,14 @@
-use std::path::PathBuf;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::{SystemTime, UNIX_EPOCH};
 
 use super::*;
-use crate::services::app_save_service::AppSaveService;
+use crate::app_error::{AppError, AppResult};
+use crate::services::app_save_service::{AppSaveService, JsonRead};
+use requests::{
+    RequestDirectoryImport, RequestImageEvaluation, RequestPreviewPage, RequestZipImport,
+};
 
 fn now_secs() -> u64 {
     SystemTime::now()
@@ -17,23 +22,44 @@ pub struct ProjectsService {
     pub image_loader: ImageLoaderComponent,
     pub image_evals: ImageEvaluationsComponent,
     pub image_exporter: ImageExporterComponent,
+    pub image_index: Arc<ImageIndexComponent>,
+    /// Serializes every mutation of a project's files and saved state
+    locks: Arc<ProjectLocksComponent>,
+    /// Preview streams the frontend can stop, keyed by stream id
+    preview_streams: CancellationRegistryComponent,
+    /// Running imports the frontend can cancel, keyed by import id
+    imports: CancellationRegistryComponent,
+    /// Running evaluations the frontend can cancel, keyed by evaluation id
+    evaluations: CancellationRegistryComponent,
+    /// Watches opened projects for changes made outside the app
+    watcher: ProjectWatcherComponent,
 }
 
 impl ProjectsService {
     pub fn new(app_save: Arc<AppSaveService>) -> Self {
-        let image_loader = ImageLoaderComponent::new(app_save.clone());
-        let image_evals = ImageEvaluationsComponent::new(app_save.clone());
+        let image_index = Arc::new(ImageIndexComponent::new(app_save.clone()));
+        let image_loader = ImageLoaderComponent::new(app_save.clone(), image_index.clone());
+        let locks = Arc::new(ProjectLocksComponent::default());
+        let image_evals =
+            ImageEvaluationsComponent::new(app_save.clone(), image_index.clone(), locks.clone());
         let image_exporter = ImageExporterComponent::default();
         Self {
             app_save,
             image_loader,
             image_evals,
             image_exporter,
+            image_index,
+            locks,
+            preview_streams: CancellationRegistryComponent::default(),
+            imports: CancellationRegistryComponent::default(),
+            evaluations: CancellationRegistryComponent::default(),
+            watcher: ProjectWatcherComponent::default(),
         }
     }
 
     /// Creates a new project and opens it
-    pub fn new_project(&self, project_name: &str) -> Result<ProjectInfoModel, String> {
+    pub async fn new_project(&self, project_name: &ProjectName) -> AppResult<ProjectInfoModel> {
+        let _guard = self.locks.lock(project_name).await;
         let relative_path = format!("projects/{project_name}");
         let images_path = format!("projects/{project_name}/images");
         let info_path = format!("projects/{project_name}/info.imgreader");
@@ -45,9 +71,7 @@ impl ProjectsService {
             project_name: project_name.to_string(),
             last_opened_at: Some(now_secs()),
         };
-        self.app_save
-            .save_json(&info_path, &model)
-            .map_err(|e| e.to_string())?;
+        self.app_save.save_json(&info_path, &model)?;
 
         // let project = Project::new(model.clone());
         // let mut l = self.active_project.lock().await;
@@ -56,13 +80,13 @@ impl ProjectsService {
         Ok(model)
     }
 
-    pub fn get_project(&self, project_name: &str) -> Result<ProjectInfoModel, String> {
+    pub fn get_project(&self, project_name: &ProjectName) -> AppResult<ProjectInfoModel> {
         let info_path = format!("projects/{project_name}/info.imgreader");
         let info = self.app_save.read_json::<ProjectInfoModel>(&info_path)?;
         Ok(info)
     }
 
-    pub fn get_project_names(&self) -> Result<Vec<String>, String> {
+    pub fn get_project_names(&self) -> AppResult<Vec<String>> {
         let relative_path = "projects";
         let items = self.app_save.get_items_in_folder(relative_path)?;
         let mut names_with_ts: Vec<(String, u64)> = Vec::new();
@@ -85,30 +109,47 @@ impl ProjectsService {
     }
 
     /// Records that a project was opened (updates last_opened_at for sorting).
-    pub fn record_project_opened(&self, project_name: &str) -> Result<(), String> {
+    /// Reports whether the project info had to be restored from its backup.
+    pub async fn record_project_opened(
+        &self,
+        project_name: &ProjectName,
+    ) -> AppResult<JsonRead<ProjectInfoModel>> {
+        let _guard = self.locks.lock(project_name).await;
         let info_path = format!("projects/{project_name}/info.imgreader");
-        let mut info = self.app_save.read_json::<ProjectInfoModel>(&info_path)?;
-        info.last_opened_at = Some(now_secs());
-        self.app_save.save_json(&info_path, &info)?;
-        Ok(())
+        let mut read = self
+            .app_save
+            .read_json_with_recovery::<ProjectInfoModel>(&info_path)?;
+        read.value.last_opened_at = Some(now_secs());
+        self.app_save.save_json(&info_path, &read.value)?;
+        Ok(read)
     }
 
     /// Permanently deletes a project and all its contents
-    pub fn delete_project(&self, project_name: &str) -> Result<(), String> {
+    pub async fn delete_project(&self, project_name: &ProjectName) -> AppResult<()> {
+        let _guard = self.locks.lock(project_name).await;
+        // Windows can't delete or move a folder that is being watched
+        self.watcher.unwatch(project_name);
         let relative_path = format!("projects/{project_name}");
-        self.app_save.delete_folder(&relative_path)
+        self.app_save.delete_folder(&relative_path)?;
+        self.image_index.forget(project_name);
+        Ok(())
     }
 
     /// Archives a project by moving it from `projects/` to `archived/`
-    pub fn archive_project(&self, project_name: &str) -> Result<(), String> {
+    pub async fn archive_project(&self, project_name: &ProjectName) -> AppResult<()> {
+        let _guard = self.locks.lock(project_name).await;
+        self.watcher.unwatch(project_name);
         self.app_save.ensure_folder_created("archived");
         let from = format!("projects/{project_name}");
         let to = format!("archived/{project_name}");
-        self.app_save.rename_folder(&from, &to)
+        self.app_save.rename_folder(&from, &to)?;
+        self.image_index.forget(project_name);
+        Ok(())
     }
 
     /// Restores an archived project by moving it from `archived/` back to `projects/`
-    pub fn unarchive_project(&self, project_name: &str) -> Result<(), String> {
+    pub async fn unarchive_project(&self, project_name: &ProjectName) -> AppResult<()> {
+        let _guard = self.locks.lock(project_name).await;
         self.app_save.ensure_folder_created("projects");
         let from = format!("archived/{project_name}");
         let to = format!("projects/{project_name}");
@@ -116,7 +157,7 @@ impl ProjectsService {
     }
 
     /// Lists archived project names, sorted by last_opened_at descending
-    pub fn get_archived_project_names(&self) -> Result<Vec<String>, String> {
+    pub fn get_archived_project_names(&self) -> AppResult<Vec<String>> {
         let relative_path = "archived";
         let items = match self.app_save.get_items_in_folder(relative_path) {
             Ok(items) => items,
@@ -142,100 +183,865 @@ impl ProjectsService {
     }
 
     /// Permanently deletes an archived project
-    pub fn delete_archived_project(&self, project_name: &str) -> Result<(), String> {
+    pub async fn delete_archived_project(&self, project_name: &ProjectName) -> AppResult<()> {
+        let _guard = self.locks.lock(project_name).await;
         let relative_path = format!("archived/{project_name}");
         self.app_save.delete_folder(&relative_path)
     }
 
-    pub fn get_image_full_path(&self, project_name: &str, image_name: &str) -> PathBuf {
+    /// Returns previews for all images in a project and records the hashes computed for them
+    /// in the project's image index
+    pub async fn get_image_previews_in_project(
+        &self,
+        project_name: &ProjectName,
+    ) -> AppResult<Vec<ImagePreviewModel>> {
+        let previews = self
+            .image_loader
+            .get_image_previews_in_project(project_name)
+            .await?;
+        self.record_previews(project_name, &previews).await;
+        Ok(previews)
+    }
+
+    /// Returns one page of previews, generating only the ones on that page
+    pub async fn get_image_preview_page(
+        &self,
+        project_name: &ProjectName,
+        request: &RequestPreviewPage,
+    ) -> AppResult<PreviewPage> {
+        let page = self
+            .image_loader
+            .get_image_preview_page(project_name, request)
+            .await?;
+        self.record_previews(project_name, &page.previews).await;
+        Ok(page)
+    }
+
+    /// Sends each preview to `on_preview` as soon as it is ready.
+    /// The stream can be stopped with `cancel_preview_stream` using the same `stream_id`,
+    /// starting another stream under that id stops the previous one.
+    pub async fn stream_image_previews_in_project(
+        &self,
+        project_name: &ProjectName,
+        stream_id: &str,
+        on_preview: impl Fn(&ImagePreviewModel),
+    ) -> AppResult<()> {
+        let registered = self.preview_streams.register(stream_id);
+        let result = self
+            .image_loader
+            .stream_image_previews_in_project(project_name, registered.token.clone(), on_preview)
+            .await;
+        self.preview_streams.finish(stream_id, &registered);
+        self.record_previews(project_name, &result?).await;
+        Ok(())
+    }
+
+    /// Stops a preview stream. Returns false if it already finished.
+    pub fn cancel_preview_stream(&self, stream_id: &str) -> bool {
+        self.preview_streams.cancel(stream_id)
+    }
+
+    /// Saves the hashes computed while generating previews. Failing to do so only costs a
+    /// recomputation later, so it is logged rather than returned.
+    /// Only fills in the index, so it doesn't wait for the project lock.
+    async fn record_previews(&self, project_name: &ProjectName, previews: &[ImagePreviewModel]) {
+        if let Err(e) = self
+            .image_index
+            .record_previews(project_name, previews)
+            .await
+        {
+            log::warn!("Failed to update image index for {project_name}: {e}");
+        }
+    }
+
+    /// Returns the capture details of an image.
+    /// They are read from the file and saved to the index if the index doesn't have them yet.
+    pub async fn get_image_metadata(
+        &self,
+        project_name: &ProjectName,
+        image_name: &ImageRelPath,
+    ) -> AppResult<ImageMetadataModel> {
+        let index = self.image_index.read_index(project_name).await?;
+        if let Some(metadata) = index
+            .images
+            .get(&**image_name)
+            .and_then(|entry| entry.metadata.clone())
+        {
+            return Ok(metadata);
+        }
+
+        let metadata = self
+            .image_loader
+            .load_image_metadata(project_name, image_name)
+            .await?;
+        self.image_index
+            .update_index(project_name, |index| {
+                match index.images.get_mut(&**image_name) {
+                    Some(entry) => {
+                        entry.metadata = Some(metadata.clone());
+                        true
+                    }
+                    None => false,
+                }
+            })
+            .await?;
+        Ok(metadata)
+    }
+
+    /// Brings the project's image index in line with its files, picking up images added, removed
+    /// or edited outside the app. Evaluations of removed images are dropped and those of edited
+    /// images marked stale, and cached previews of both are invalidated.
+    pub async fn reconcile_project_images(
+        &self,
+        project_name: &ProjectName,
+    ) -> AppResult<ReconcileReport> {
+        let _guard = self.locks.lock(project_name).await;
+        let report = self.image_index.reconcile(project_name).await?;
+        self.image_evals
+            .remove_evaluations_for_images(project_name, &report.removed)?;
+        self.image_evals.mark_stale(project_name, &report.changed)?;
+        let outdated: Vec<String> = report
+            .removed
+            .iter()
+            .chain(&report.changed)
+            .cloned()
+            .collect();
+        self.image_loader
+            .invalidate_images(project_name, &outdated)
+            .await;
+        Ok(report)
+    }
+
+    /// Watches the project's images for changes made outside the app, e.g. files dropped in with
+    /// Explorer or a sync tool. Each time they settle the project is reconciled, and `on_change`
+    /// gets what changed. Must be called from within the async runtime.
+    pub fn watch_project(
+        self: &Arc<Self>,
+        project_name: &ProjectName,
+        on_change: impl Fn(ProjectFilesChanged) + Send + Sync + 'static,
+    ) -> AppResult<()> {
+        let runtime = tokio::runtime::Handle::try_current()
+            .map_err(|e| AppError::Internal(format!("No async runtime to watch files on: {e}")))?;
+        // Weak so the watcher, which the service owns, doesn't keep the service alive
+        let service = Arc::downgrade(self);
+        let on_change = Arc::new(on_change);
+        let watched_project = project_name.clone();
+        let images_dir = self
+            .app_save
+            .get_full_path(&format!("projects/{project_name}/images"));
+        self.watcher.watch(project_name, &images_dir, move || {
+            let Some(service) = service.upgrade() else {
+                return;
+            };
+            let project_name = watched_project.clone();
+            let on_change = on_change.clone();
+            runtime.spawn(async move {
+                match service.reconcile_project_images(&project_name).await {
+                    Ok(changes) if changes.is_empty() => {}
+                    Ok(changes) => on_change(ProjectFilesChanged {
+                        project_name: project_name.to_string(),
+                        changes,
+                    }),
+                    Err(e) => log::warn!("Failed to pick up file changes in {project_name}: {e}"),
+                }
+            });
+        })
+    }
+
+    /// Stops watching a project's images. Returns false if they weren't watched.
+    pub fn unwatch_project(&self, project_name: &ProjectName) -> bool {
+        self.watcher.unwatch(project_name)
+    }
+
+    /// Groups images that look nearly identical so they can be pruned before evaluation.
+    /// Perceptual hashes missing from the index are computed first.
+    pub async fn find_near_duplicates_in_project(
+        &self,
+        project_name: &ProjectName,
+        max_distance: Option<u32>,
+    ) -> AppResult<Vec<NearDuplicateGroup>> {
+        let files = self.image_loader.list_image_files(project_name).await?;
+        let index = self.image_index.read_index(project_name).await?;
+
+        let unhashed: Vec<(PathBuf, String)> = files
+            .into_iter()
+            .filter(|(_, name)| {
+                index
+                    .images
+                    .get(name)
+                    .is_some_and(|entry| entry.perceptual_hash.is_none())
+            })
+            .collect();
+        let hashes = if unhashed.is_empty() {
+            Vec::new()
+        } else {
+            self.image_loader.compute_perceptual_hashes(unhashed).await
+        };
+        let index = self
+            .image_index
+            .update_index(project_name, |index| {
+                let mut changed = false;
+                for (name, hash) in hashes {
+                    if let Some(entry) = index.images.get_mut(&name) {
+                        entry.perceptual_hash = Some(hash);
+                        changed = true;
+                    }
+                }
+                changed
+            })
+            .await?;
+
+        let max_distance = max_distance.unwrap_or(DEFAULT_NEAR_DUPLICATE_DISTANCE);
+        Ok(index
+            .near_duplicate_groups(max_distance)
+            .into_iter()
+            .map(|image_names| NearDuplicateGroup { image_names })
+            .collect())
+    }
+
+    pub fn get_image_full_path(
+        &self,
+        project_name: &ProjectName,
+        image_name: &ImageRelPath,
+    ) -> PathBuf {
         let relative = format!("projects/{project_name}/images/{image_name}");
         self.app_save.get_full_path(&relative)
     }
 
     /// Creates a folder inside a project's images directory
-    pub fn create_folder_in_project(
+    pub async fn create_folder_in_project(
         &self,
-        project_name: &str,
-        folder_name: &str,
-    ) -> Result<(), String> {
+        project_name: &ProjectName,
+        folder_name: &FolderName,
+    ) -> AppResult<()> {
+        let _guard = self.locks.lock(project_name).await;
         let path = format!("projects/{project_name}/images/{folder_name}");
         self.app_save.ensure_folder_created(&path);
         Ok(())
     }
 
-    /// Lists folder names inside a project's images directory
-    pub fn get_folders_in_project(&self, project_name: &str) -> Result<Vec<String>, String> {
+    /// Lists the folders inside a project's images directory as a tree, sorted by name
+    pub fn get_folders_in_project(&self, project_name: &ProjectName) -> AppResult<Vec<FolderNode>> {
         let images_path = format!("projects/{project_name}/images");
-        let items = self.app_save.get_items_in_folder(&images_path)?;
-        let folders: Vec<String> = items
-            .into_iter()
-            .filter(|p| p.is_dir())
-            .filter_map(|p| p.file_name().map(|n| n.to_string_lossy().to_string()))
-            .collect();
+        Self::read_folder_tree(&self.app_save.get_full_path(&images_path), "")
+    }
+
+    fn read_folder_tree(dir: &Path, parent_path: &str) -> AppResult<Vec<FolderNode>> {
+        let read_dir = fs::read_dir(dir).map_err(|e| AppError::io(dir.display(), e))?;
+        let mut folders = Vec::new();
+        for entry in read_dir.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.starts_with('.') || !entry.path().is_dir() {
+                continue;
+            }
+            let path = if parent_path.is_empty() {
+                name.clone()
+            } else {
+                format!("{parent_path}/{name}")
+            };
+            let children = Self::read_folder_tree(&entry.path(), &path)?;
+            folders.push(FolderNode {
+                name,
+                path,
+                children,
+            });
+        }
+        folders.sort_by_cached_key(|f| f.name.to_lowercase());
         Ok(folders)
     }
 
-    /// Renames a folder inside a project's images directory and updates evaluations
-    pub fn rename_folder_in_project(
+    /// Renames or moves a folder inside a project's images directory, along with everything
+    /// nested in it, and updates evaluations. `new_folder_name` may be in a different parent.
+    pub async fn rename_folder_in_project(
         &self,
-        project_name: &str,
-        old_folder_name: &str,
-        new_folder_name: &str,
-    ) -> Result<(), String> {
+        project_name: &ProjectName,
+        old_folder_name: &FolderName,
+        new_folder_name: &FolderName,
+    ) -> AppResult<()> {
+        if new_folder_name.starts_with(&format!("{old_folder_name}/")) {
+            return Err(AppError::InvalidName(format!(
+                "Can't move {old_folder_name} into itself"
+            )));
+        }
+        let _guard = self.locks.lock(project_name).await;
         let from = format!("projects/{project_name}/images/{old_folder_name}");
         let to = format!("projects/{project_name}/images/{new_folder_name}");
-        self.app_save.rename_folder(&from, &to)?;
-
-        // Collect image file names in the (now renamed) folder to build rename pairs
-        let folder_path = format!("projects/{project_name}/images/{new_folder_name}");
-        let items = self.app_save.get_items_in_folder(&folder_path)?;
-        let renames: Vec<(String, String)> = items
+        if self.app_save.get_full_path(&to).exists() {
+            return Err(AppError::AlreadyExists(format!(
+                "A folder named {new_folder_name} already exists"
+            )));
+        }
+        if let Some(parent) = new_folder_name.parent() {
+            self.app_save
+                .ensure_folder_created(&format!("projects/{project_name}/images/{parent}"));
+        }
+        // Rename pairs for the images in the folder and its subfolders
+        let renames: Vec<(String, String)> = self
+            .image_index
+            .read_index(project_name)
+            .await?
+            .image_names_in_folder(old_folder_name)
             .into_iter()
-            .filter(|p| p.is_file())
-            .filter_map(|p| {
-                p.file_name().map(|n| {
-                    let fname = n.to_string_lossy().to_string();
-                    (
-                        format!("{old_folder_name}/{fname}"),
-                        format!("{new_folder_name}/{fname}"),
-                    )
-                })
+            .map(|old_name| {
+                let new_name = format!("{new_folder_name}{}", &old_name[old_folder_name.len()..]);
+                (old_name, new_name)
             })
             .collect();
+        self.app_save.rename_folder(&from, &to)?;
 
         if !renames.is_empty() {
             self.image_evals
                 .rename_evaluations(project_name, &renames)?;
+            self.image_index
+                .rename_images(project_name, &renames)
+                .await?;
         }
 
         Ok(())
     }
 
-    /// Deletes a folder and all its images from a project, plus their evaluations
-    pub fn delete_folder_from_project(
+    /// Deletes a folder with its subfolders and all their images from a project, plus their
+    /// evaluations
+    pub async fn delete_folder_from_project(
         &self,
-        project_name: &str,
-        folder_name: &str,
-    ) -> Result<(), String> {
+        project_name: &ProjectName,
+        folder_name: &FolderName,
+    ) -> AppResult<()> {
+        let _guard = self.locks.lock(project_name).await;
         // Collect image names in the folder so we can remove their evaluations
         let folder_path = format!("projects/{project_name}/images/{folder_name}");
-        let items = self.app_save.get_items_in_folder(&folder_path)?;
-        let image_names: Vec<String> = items
-            .into_iter()
-            .filter(|p| p.is_file())
-            .filter_map(|p| {
-                p.file_name()
-                    .map(|n| format!("{folder_name}/{}", n.to_string_lossy()))
-            })
-            .collect();
+        let image_names = self
+            .image_index
+            .read_index(project_name)
+            .await?
+            .image_names_in_folder(folder_name);
 
         // Remove evaluations for those images
         if !image_names.is_empty() {
             self.image_evals
                 .remove_evaluations_for_images(project_name, &image_names)?;
+            self.image_index
+                .remove_images(project_name, &image_names)
+                .await?;
         }
 
         // Delete the folder and its contents
         self.app_save.delete_folder(&folder_path)
     }
+
+    /// Imports images into the project root or into `folder`.
+    /// Evaluations of images replaced by the import are removed since they no longer apply.
+    /// `on_progress` is called after every file, and the import can be cancelled with
+    /// `cancel_import` using the same `import_id`, which rolls it back.
+    pub async fn import_images_to_project(
+        &self,
+        project_name: &ProjectName,
+        image_paths: Vec<String>,
+        folder: Option<FolderName>,
+        options: ImportOptions,
+        import_id: &str,
+        on_progress: impl Fn(ImportProgress) + Send + Sync,
+    ) -> AppResult<ImportReport> {
+        let registered = self.imports.register(import_id);
+        let _guard = self.locks.lock(project_name).await;
+        let session = ImportSession {
+            import_id,
+            cancel: &registered.token,
+            on_progress: &on_progress,
+        };
+        let result = self
+            .image_loader
+            .import_images_to_project(
+                project_name,
+                image_paths,
+                folder.as_deref(),
+                options,
+                &session,
+            )
+            .await;
+        self.imports.finish(import_id, &registered);
+        let report = result?;
+        self.image_evals
+            .remove_evaluations_for_images(project_name, &report.overwritten)?;
+        Ok(report)
+    }
+
+    /// Imports every supported image below a directory, see `RequestDirectoryImport`.
+    /// Progress and cancellation work like in `import_images_to_project`.
+    pub async fn import_directory_to_project(
+        &self,
+        project_name: &ProjectName,
+        request: RequestDirectoryImport,
+        import_id: &str,
+        on_progress: impl Fn(ImportProgress) + Send + Sync,
+    ) -> AppResult<ImportReport> {
+        let folder = FolderName::parse_optional(request.folder.as_deref())?;
+        let registered = self.imports.register(import_id);
+        let _guard = self.locks.lock(project_name).await;
+        let session = ImportSession {
+            import_id,
+            cancel: &registered.token,
+            on_progress: &on_progress,
+        };
+        let result = self
+            .image_loader
+            .import_directory_to_project(
+                project_name,
+                Path::new(&request.directory_path),
+                folder.as_deref(),
+                request.preserve_structure,
+                ImportOptions {
+                    policy: request.conflict_policy,
+                    transcode_to_jpeg: request.transcode_to_jpeg,
+                },
+                &session,
+            )
+            .await;
+        self.imports.finish(import_id, &registered);
+        let report = result?;
+        self.image_evals
+            .remove_evaluations_for_images(project_name, &report.overwritten)?;
+        Ok(report)
+    }
+
+    /// Imports the images in a zip archive, see `RequestZipImport`.
+    /// Progress and cancellation work like in `import_images_to_project`.
+    pub async fn import_zip_to_project(
+        &self,
+        project_name: &ProjectName,
+        request: RequestZipImport,
+        import_id: &str,
+        on_progress: impl Fn(ImportProgress) + Send + Sync,
+    ) -> AppResult<ImportReport> {
+        let archive_path = Path::new(&request.archive_path);
+        let folder = match request.folder.as_deref() {
+            Some(folder) => Some(FolderName::parse(folder)?),
+            None if request.use_archive_folder => {
+                let stem = archive_path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                Some(FolderName::parse(&stem)?)
+            }
+            None => None,
+        };
+        let registered = self.imports.register(import_id);
+        let _guard = self.locks.lock(project_name).await;
+        let session = ImportSession {
+            import_id,
+            cancel: &registered.token,
+            on_progress: &on_progress,
+        };
+        let result = self
+            .image_loader
+            .import_zip_to_project(
+                project_name,
+                archive_path,
+                folder.as_deref(),
+                ImportOptions {
+                    policy: request.conflict_policy,
+                    transcode_to_jpeg: request.transcode_to_jpeg,
+                },
+                &session,
+            )
+            .await;
+        self.imports.finish(import_id, &registered);
+        let report = result?;
+        self.image_evals
+            .remove_evaluations_for_images(project_name, &report.overwritten)?;
+        Ok(report)
+    }
+
+    /// Cancels a running import, rolling back what it copied. Returns false if it already finished.
+    pub fn cancel_import(&self, import_id: &str) -> bool {
+        self.imports.cancel(import_id)
+    }
+
+    /// Deletes images from a project along with their evaluations and index entries
+    pub async fn delete_images_from_project(
+        &self,
+        project_name: &ProjectName,
+        image_names: Vec<ImageRelPath>,
+    ) -> AppResult<()> {
+        let _guard = self.locks.lock(project_name).await;
+        let image_names: Vec<String> = image_names.into_iter().map(String::from).collect();
+        self.image_loader
+            .delete_images_from_project(project_name, image_names.clone())
+            .await?;
+        self.image_evals
+            .remove_evaluations_for_images(project_name, &image_names)?;
+        self.image_index
+            .remove_images(project_name, &image_names)
+            .await
+    }
+
+    /// Moves images to a different folder (or root) and renames their evaluations to match.
+    /// Returns the new relative image names.
+    pub async fn move_images_in_project(
+        &self,
+        project_name: &ProjectName,
+        image_names: Vec<ImageRelPath>,
+        target_folder: Option<FolderName>,
+    ) -> AppResult<Vec<String>> {
+        let _guard = self.locks.lock(project_name).await;
+        let image_names: Vec<String> = image_names.into_iter().map(String::from).collect();
+        let new_names = self
+            .image_loader
+            .move_images_in_project(project_name, image_names.clone(), target_folder.as_deref())
+            .await?;
+
+        // Update evaluation records to match the new image names
+        let renames: Vec<(String, String)> = image_names
+            .into_iter()
+            .zip(new_names.iter().cloned())
+            .filter(|(old, new)| old != new)
+            .collect();
+        if !renames.is_empty() {
+            self.image_evals
+                .rename_evaluations(project_name, &renames)?;
+            self.image_index
+                .rename_images(project_name, &renames)
+                .await?;
+        }
+
+        Ok(new_names)
+    }
+
+    /// Evaluates images and merges each result into the project's saved evaluations as it arrives.
+    /// The project is only locked while each result is saved, images moved during the run keep
+    /// their result and images deleted during it get none.
+    /// `on_progress` is called when each image starts and when it's done, and the evaluation can
+    /// be cancelled with `cancel_evaluation` using the same `evaluation_id`.
+    pub async fn evaluate_images(
+        &self,
+        project_name: &ProjectName,
+        request: RequestImageEvaluation,
+        custom_prompt: Option<String>,
+        temperature: Option<f32>,
+        evaluation_id: &str,
+        on_progress: impl Fn(EvaluationProgress) + Send + Sync,
+    ) -> AppResult<Vec<ImageEvaluation>> {
+        let registered = self.evaluations.register(evaluation_id);
+        let session = EvaluationSession {
+            evaluation_id,
+            cancel: &registered.token,
+            on_progress: &on_progress,
+        };
+        let result = self
+            .image_evals
+            .evaluate_images(project_name, request, custom_prompt, temperature, &session)
+            .await;
+        self.evaluations.finish(evaluation_id, &registered);
+        result
+    }
+
+    /// Stops a running evaluation, keeping the results saved so far.
+    /// Returns false if it already finished.
+    pub fn cancel_evaluation(&self, evaluation_id: &str) -> bool {
+        self.evaluations.cancel(evaluation_id)
+    }
+
+    /// Sets the provider the project's images are evaluated with when a request doesn't pick one
+    pub async fn set_evaluation_provider(
+        &self,
+        project_name: &ProjectName,
+        config: EvaluationProviderConfig,
+    ) -> AppResult<()> {
+        let _guard = self.locks.lock(project_name).await;
+        self.image_evals
+            .write_provider_config(project_name, &config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::future::BoxFuture;
+    use tokio_util::sync::CancellationToken;
+
+    use super::*;
+    use crate::services::projects_service::components::evaluation_providers::{
+        EvaluationFailure, EvaluationProvider, MockProvider, ProviderResult,
+    };
+
+    const IMAGE_COUNT: u8 = 12;
+
+    /// The mock provider, slowed down so the project changes while images are evaluated
+    struct SlowMockProvider;
+
+    impl EvaluationProvider for SlowMockProvider {
+        fn evaluate_images(
+            &self,
+            image_paths: Vec<String>,
+            custom_prompt: Option<String>,
+            temperature: Option<f32>,
+        ) -> BoxFuture<'_, Vec<ProviderResult>> {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                MockProvider
+                    .evaluate_images(image_paths, custom_prompt, temperature)
+                    .await
+            })
+        }
+    }
+
+    /// Fails the images named in `only`, or every image if it's empty, right away and evaluates
+    /// the others like the slow mock provider
+    struct FailingProvider {
+        failure: EvaluationFailure,
+        only: Vec<String>,
+    }
+
+    impl FailingProvider {
+        fn rate_limited(only: Vec<String>) -> Self {
+            Self {
+                failure: EvaluationFailure {
+                    reason: "HTTP 429: Slow down".to_string(),
+                    status: Some(429),
+                    code: Some("rate_limit_exceeded".to_string()),
+                    retry_after: Some(Duration::from_millis(1)),
+                    no_response: false,
+                },
+                only,
+            }
+        }
+    }
+
+    impl EvaluationProvider for FailingProvider {
+        fn evaluate_images(
+            &self,
+            image_paths: Vec<String>,
+            custom_prompt: Option<String>,
+            temperature: Option<f32>,
+        ) -> BoxFuture<'_, Vec<ProviderResult>> {
+            Box::pin(async move {
+                let mut results = Vec::new();
+                for path in image_paths {
+                    let fails = self.only.is_empty()
+                        || self.only.iter().any(|name| path.ends_with(name.as_str()));
+                    if fails {
+                        results.push(Err(self.failure.clone()));
+                    } else {
+                        results.extend(
+                            SlowMockProvider
+                                .evaluate_images(vec![path], custom_prompt.clone(), temperature)
+                                .await,
+                        );
+                    }
+                }
+                results
+            })
+        }
+    }
+
+    /// A service saving into its own temp dir, removed when dropped
+    struct TestProject {
+        save_dir: PathBuf,
+        service: ProjectsService,
+        project_name: ProjectName,
+    }
+
+    impl TestProject {
+        /// Creates a project with distinct images named `img_0.png`, `img_1.png`, ...
+        async fn new(test_name: &str) -> Self {
+            let save_dir = std::env::temp_dir().join(format!(
+                "get-crap-done-{test_name}-{}-{}",
+                std::process::id(),
+                now_secs()
+            ));
+            let service = ProjectsService::new(Arc::new(AppSaveService::new(save_dir.clone())));
+            let project_name = ProjectName::parse("Test Project").unwrap();
+            service.new_project(&project_name).await.unwrap();
+            let images_dir = save_dir.join(format!("projects/{project_name}/images"));
+            for i in 0..IMAGE_COUNT {
+                image::RgbImage::from_pixel(4, 4, image::Rgb([i, i, i]))
+                    .save(images_dir.join(Self::image_name(i)))
+                    .unwrap();
+            }
+            Self {
+                save_dir,
+                service,
+                project_name,
+            }
+        }
+
+        fn image_name(i: u8) -> String {
+            format!("img_{i}.png")
+        }
+
+        async fn evaluate_all(&self) -> AppResult<Vec<ImageEvaluation>> {
+            self.evaluate_all_with(&SlowMockProvider).await
+        }
+
+        async fn evaluate_all_with(
+            &self,
+            provider: &dyn EvaluationProvider,
+        ) -> AppResult<Vec<ImageEvaluation>> {
+            let image_names: Vec<String> = (0..IMAGE_COUNT).map(Self::image_name).collect();
+            let cancel = CancellationToken::new();
+            let session = EvaluationSession {
+                evaluation_id: "test",
+                cancel: &cancel,
+                on_progress: &|_| {},
+            };
+            self.service
+                .image_evals
+                .evaluate_images_with(
+                    &self.project_name,
+                    &image_names,
+                    provider,
+                    None,
+                    None,
+                    false,
+                    &session,
+                )
+                .await
+        }
+
+        /// Saved evaluations sorted by image name
+        fn saved_evaluation_names(&self) -> Vec<String> {
+            let mut names: Vec<String> = self
+                .service
+                .image_evals
+                .read_images_eval_json(&self.project_name)
+                .unwrap()
+                .into_iter()
+                .map(|eval| eval.image_name)
+                .collect();
+            names.sort();
+            names
+        }
+
+        async fn indexed_image_names(&self) -> Vec<String> {
+            let index = self
+                .service
+                .image_index
+                .read_index(&self.project_name)
+                .await
+                .unwrap();
+            index.images.into_keys().collect()
+        }
+    }
+
+    impl Drop for TestProject {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.save_dir);
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn moving_images_during_evaluation_keeps_their_results() {
+        let project = TestProject::new("move").await;
+        let folder = FolderName::parse("Moved").unwrap();
+        project
+            .service
+            .create_folder_in_project(&project.project_name, &folder)
+            .await
+            .unwrap();
+        let moved: Vec<ImageRelPath> = (0..IMAGE_COUNT)
+            .step_by(2)
+            .map(|i| ImageRelPath::parse(&TestProject::image_name(i)).unwrap())
+            .collect();
+
+        let (evaluated, moved_names) = tokio::join!(project.evaluate_all(), async {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            project
+                .service
+                .move_images_in_project(&project.project_name, moved, Some(folder.clone()))
+                .await
+        });
+        evaluated.unwrap();
+        let moved_names = moved_names.unwrap();
+        assert!(moved_names.iter().all(|name| name.starts_with("Moved/")));
+
+        // One result per image, saved under the name it has now
+        let indexed = project.indexed_image_names().await;
+        assert_eq!(indexed.len(), IMAGE_COUNT as usize);
+        assert_eq!(project.saved_evaluation_names(), indexed);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn deleting_images_during_evaluation_drops_only_their_results() {
+        let project = TestProject::new("delete").await;
+        let deleted: Vec<ImageRelPath> = (0..IMAGE_COUNT)
+            .step_by(3)
+            .map(|i| ImageRelPath::parse(&TestProject::image_name(i)).unwrap())
+            .collect();
+
+        let (evaluated, deleted_result) = tokio::join!(project.evaluate_all(), async {
+            tokio::time::sleep(Duration::from_millis(30)).await;
+            project
+                .service
+                .delete_images_from_project(&project.project_name, deleted)
+                .await
+        });
+        evaluated.unwrap();
+        deleted_result.unwrap();
+
+        // Every image left has its result and the deleted ones have none
+        let indexed = project.indexed_image_names().await;
+        assert_eq!(indexed.len(), IMAGE_COUNT as usize - 4);
+        assert_eq!(project.saved_evaluation_names(), indexed);
+    }
+
+    #[tokio::test]
+    async fn failures_are_saved_per_image_whatever_numbers_their_reason_holds() {
+        let project = TestProject::new("undecodable").await;
+        let provider = FailingProvider {
+            failure: EvaluationFailure::other("Failed to decode IMG_1429.jpg"),
+            only: Vec::new(),
+        };
+
+        let evaluations = project.evaluate_all_with(&provider).await.unwrap();
+
+        assert_eq!(evaluations.len(), IMAGE_COUNT as usize);
+        assert!(evaluations.iter().all(|eval| eval.result.is_none()
+            && eval.fail_reason.as_deref() == Some("Failed to decode IMG_1429.jpg")));
+    }
+
+    #[tokio::test]
+    async fn one_rate_limited_image_doesnt_stop_the_run() {
+        let project = TestProject::new("one-rate-limited").await;
+        let provider = FailingProvider::rate_limited(vec![TestProject::image_name(0)]);
+
+        let evaluations = project.evaluate_all_with(&provider).await.unwrap();
+
+        assert_eq!(evaluations.len(), IMAGE_COUNT as usize);
+        let failed: Vec<&str> = evaluations
+            .iter()
+            .filter(|eval| eval.result.is_none())
+            .map(|eval| eval.image_name.as_str())
+            .collect();
+        assert_eq!(failed, vec![TestProject::image_name(0)]);
+    }
+
+    #[tokio::test]
+    async fn rate_limits_on_every_image_fail_the_run() {
+        let project = TestProject::new("all-rate-limited").await;
+
+        let result = project
+            .evaluate_all_with(&FailingProvider::rate_limited(Vec::new()))
+            .await;
+
+        assert!(matches!(result, Err(AppError::RateLimited(_))));
+        assert!(project.saved_evaluation_names().is_empty());
+    }
+
+    #[tokio::test]
+    async fn a_rejected_key_stops_the_run() {
+        let project = TestProject::new("rejected-key").await;
+        let provider = FailingProvider {
+            failure: EvaluationFailure {
+                reason: "HTTP 401: Incorrect API key".to_string(),
+                status: Some(401),
+                ..Default::default()
+            },
+            only: vec![TestProject::image_name(3)],
+        };
+
+        let result = project.evaluate_all_with(&provider).await;
+
+        assert!(matches!(result, Err(AppError::Auth(_))));
+    }
 }
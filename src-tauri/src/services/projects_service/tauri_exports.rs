@@ -3,88 +3,190 @@ use std::sync::Arc;
 #[cfg(target_os = "windows")]
 use std::os::windows::process::CommandExt;
 
-use tauri::State;
+use tauri::{ipc::Channel, AppHandle, Emitter, State};
 
+use crate::app_error::{AppError, AppResult};
+use crate::services::app_save_service::JsonRead;
 use crate::services::projects_service::{
-    models::*, requests::RequestImageEvaluation, ProjectsService,
+    models::*,
+    names::{FolderName, ImageRelPath, ProjectName},
+    requests::{
+        ImportConflictPolicy, RequestDirectoryImport, RequestImageEvaluation, RequestPreviewPage,
+        RequestZipImport,
+    },
+    ImportOptions, ProjectsService,
 };
 
 #[tauri::command]
-pub fn new_project(
+pub async fn new_project(
     service: State<'_, Arc<ProjectsService>>,
     project_name: &str,
-) -> Result<ProjectInfoModel, String> {
-    service.new_project(project_name)
+) -> AppResult<ProjectInfoModel> {
+    service
+        .new_project(&ProjectName::parse(project_name)?)
+        .await
 }
 
 #[tauri::command]
 pub fn get_project(
     service: State<'_, Arc<ProjectsService>>,
     project_name: &str,
-) -> Result<ProjectInfoModel, String> {
-    service.get_project(project_name)
+) -> AppResult<ProjectInfoModel> {
+    service.get_project(&ProjectName::parse(project_name)?)
 }
 
 #[tauri::command]
-pub fn get_project_names(service: State<'_, Arc<ProjectsService>>) -> Result<Vec<String>, String> {
+pub fn get_project_names(service: State<'_, Arc<ProjectsService>>) -> AppResult<Vec<String>> {
     service.get_project_names()
 }
 
 #[tauri::command]
-pub fn record_project_opened(
+pub async fn record_project_opened(
     service: State<'_, Arc<ProjectsService>>,
     project_name: &str,
-) -> Result<(), String> {
-    service.record_project_opened(project_name)
+) -> AppResult<JsonRead<ProjectInfoModel>> {
+    service
+        .record_project_opened(&ProjectName::parse(project_name)?)
+        .await
 }
 
 #[tauri::command]
-pub fn delete_project(
+pub async fn delete_project(
     service: State<'_, Arc<ProjectsService>>,
     project_name: &str,
-) -> Result<(), String> {
-    service.delete_project(project_name)
+) -> AppResult<()> {
+    service
+        .delete_project(&ProjectName::parse(project_name)?)
+        .await
 }
 
 #[tauri::command]
-pub fn archive_project(
+pub async fn archive_project(
     service: State<'_, Arc<ProjectsService>>,
     project_name: &str,
-) -> Result<(), String> {
-    service.archive_project(project_name)
+) -> AppResult<()> {
+    service
+        .archive_project(&ProjectName::parse(project_name)?)
+        .await
 }
 
 #[tauri::command]
-pub fn unarchive_project(
+pub async fn unarchive_project(
     service: State<'_, Arc<ProjectsService>>,
     project_name: &str,
-) -> Result<(), String> {
-    service.unarchive_project(project_name)
+) -> AppResult<()> {
+    service
+        .unarchive_project(&ProjectName::parse(project_name)?)
+        .await
 }
 
 #[tauri::command]
 pub fn get_archived_project_names(
     service: State<'_, Arc<ProjectsService>>,
-) -> Result<Vec<String>, String> {
+) -> AppResult<Vec<String>> {
     service.get_archived_project_names()
 }
 
 #[tauri::command]
-pub fn delete_archived_project(
+pub async fn delete_archived_project(
     service: State<'_, Arc<ProjectsService>>,
     project_name: &str,
-) -> Result<(), String> {
-    service.delete_archived_project(project_name)
+) -> AppResult<()> {
+    service
+        .delete_archived_project(&ProjectName::parse(project_name)?)
+        .await
 }
 
 #[tauri::command]
 pub async fn get_image_previews_in_project(
     service: State<'_, Arc<ProjectsService>>,
     project_name: &str,
-) -> Result<Vec<ImagePreviewModel>, String> {
+) -> AppResult<Vec<ImagePreviewModel>> {
     service
-        .image_loader
-        .get_image_previews_in_project(project_name)
+        .get_image_previews_in_project(&ProjectName::parse(project_name)?)
+        .await
+}
+
+#[tauri::command]
+pub async fn get_image_preview_page(
+    service: State<'_, Arc<ProjectsService>>,
+    project_name: &str,
+    request: RequestPreviewPage,
+) -> AppResult<PreviewPage> {
+    service
+        .get_image_preview_page(&ProjectName::parse(project_name)?, &request)
+        .await
+}
+
+/// Resolves once every preview has been sent over `on_preview` or the stream was cancelled
+#[tauri::command]
+pub async fn stream_image_previews_in_project(
+    service: State<'_, Arc<ProjectsService>>,
+    project_name: &str,
+    stream_id: &str,
+    on_preview: Channel<ImagePreviewModel>,
+) -> AppResult<()> {
+    service
+        .stream_image_previews_in_project(
+            &ProjectName::parse(project_name)?,
+            stream_id,
+            |preview| {
+                if let Err(e) = on_preview.send(preview.clone()) {
+                    log::warn!("Failed to send preview {}: {e}", preview.image_name);
+                }
+            },
+        )
+        .await
+}
+
+#[tauri::command]
+pub fn cancel_preview_stream(service: State<'_, Arc<ProjectsService>>, stream_id: &str) -> bool {
+    service.cancel_preview_stream(stream_id)
+}
+
+#[tauri::command]
+pub async fn reconcile_project_images(
+    service: State<'_, Arc<ProjectsService>>,
+    project_name: &str,
+) -> AppResult<ReconcileReport> {
+    service
+        .reconcile_project_images(&ProjectName::parse(project_name)?)
+        .await
+}
+
+/// Event the backend emits with a ProjectFilesChanged when a watched project changes on disk
+pub const PROJECT_FILES_CHANGED_EVENT: &str = "project-files-changed";
+
+/// Async so the watcher can hand its work to the async runtime
+#[tauri::command]
+pub async fn watch_project(
+    app: AppHandle,
+    service: State<'_, Arc<ProjectsService>>,
+    project_name: &str,
+) -> AppResult<()> {
+    service.watch_project(&ProjectName::parse(project_name)?, move |changed| {
+        if let Err(e) = app.emit(PROJECT_FILES_CHANGED_EVENT, changed) {
+            log::warn!("Failed to send project file changes: {e}");
+        }
+    })
+}
+
+#[tauri::command]
+pub fn unwatch_project(
+    service: State<'_, Arc<ProjectsService>>,
+    project_name: &str,
+) -> AppResult<bool> {
+    Ok(service.unwatch_project(&ProjectName::parse(project_name)?))
+}
+
+#[tauri::command]
+pub async fn find_near_duplicates_in_project(
+    service: State<'_, Arc<ProjectsService>>,
+    project_name: &str,
+    max_distance: Option<u32>,
+) -> AppResult<Vec<NearDuplicateGroup>> {
+    service
+        .find_near_duplicates_in_project(&ProjectName::parse(project_name)?, max_distance)
         .await
 }
 
@@ -93,63 +195,233 @@ pub async fn load_image_from_project(
     service: State<'_, Arc<ProjectsService>>,
     project_name: &str,
     image_name: &str,
-) -> Result<FullImageModel, String> {
+) -> AppResult<FullImageModel> {
     service
         .image_loader
-        .load_image_from_project(project_name, image_name)
+        .load_image_from_project(
+            &ProjectName::parse(project_name)?,
+            &ImageRelPath::parse(image_name)?,
+        )
         .await
 }
 
 #[tauri::command]
+pub async fn get_image_metadata(
+    service: State<'_, Arc<ProjectsService>>,
+    project_name: &str,
+    image_name: &str,
+) -> AppResult<ImageMetadataModel> {
+    service
+        .get_image_metadata(
+            &ProjectName::parse(project_name)?,
+            &ImageRelPath::parse(image_name)?,
+        )
+        .await
+}
+
+#[tauri::command]
+pub fn get_image_cache_stats(service: State<'_, Arc<ProjectsService>>) -> ImageCacheStats {
+    service.image_loader.full_image_cache_stats()
+}
+
+/// Set the memory budget of the full image cache, kept across restarts
+#[tauri::command]
+pub fn set_image_cache_capacity(
+    service: State<'_, Arc<ProjectsService>>,
+    capacity_bytes: u64,
+) -> AppResult<()> {
+    service
+        .image_loader
+        .set_full_image_cache_capacity(capacity_bytes)
+}
+
+/// Event carrying an `ImportProgress` after each file of an import
+pub const IMPORT_PROGRESS_EVENT: &str = "import-progress";
+
+fn emit_import_progress(app: &AppHandle) -> impl Fn(ImportProgress) + Send + Sync + '_ {
+    move |progress| {
+        if let Err(e) = app.emit(IMPORT_PROGRESS_EVENT, progress) {
+            log::warn!("Failed to send import progress: {e}");
+        }
+    }
+}
+
+#[tauri::command]
+#[allow(clippy::too_many_arguments)]
 pub async fn import_images_to_project(
+    app: AppHandle,
     service: State<'_, Arc<ProjectsService>>,
     project_name: &str,
     image_paths: Vec<String>,
     folder: Option<String>,
-) -> Result<(), String> {
+    conflict_policy: Option<ImportConflictPolicy>,
+    transcode_to_jpeg: Option<bool>,
+    import_id: &str,
+) -> AppResult<ImportReport> {
     service
-        .image_loader
-        .import_images_to_project(project_name, image_paths, folder)
+        .import_images_to_project(
+            &ProjectName::parse(project_name)?,
+            image_paths,
+            FolderName::parse_optional(folder.as_deref())?,
+            ImportOptions {
+                policy: conflict_policy.unwrap_or_default(),
+                transcode_to_jpeg: transcode_to_jpeg.unwrap_or(false),
+            },
+            import_id,
+            emit_import_progress(&app),
+        )
         .await
 }
 
+#[tauri::command]
+pub async fn import_directory_to_project(
+    app: AppHandle,
+    service: State<'_, Arc<ProjectsService>>,
+    project_name: &str,
+    request: RequestDirectoryImport,
+    import_id: &str,
+) -> AppResult<ImportReport> {
+    service
+        .import_directory_to_project(
+            &ProjectName::parse(project_name)?,
+            request,
+            import_id,
+            emit_import_progress(&app),
+        )
+        .await
+}
+
+#[tauri::command]
+pub async fn import_zip_to_project(
+    app: AppHandle,
+    service: State<'_, Arc<ProjectsService>>,
+    project_name: &str,
+    request: RequestZipImport,
+    import_id: &str,
+) -> AppResult<ImportReport> {
+    service
+        .import_zip_to_project(
+            &ProjectName::parse(project_name)?,
+            request,
+            import_id,
+            emit_import_progress(&app),
+        )
+        .await
+}
+
+#[tauri::command]
+pub fn cancel_import(service: State<'_, Arc<ProjectsService>>, import_id: &str) -> bool {
+    service.cancel_import(import_id)
+}
+
 #[tauri::command]
 pub async fn delete_images_from_project(
     service: State<'_, Arc<ProjectsService>>,
     project_name: &str,
     image_names: Vec<String>,
-) -> Result<(), String> {
+) -> AppResult<()> {
     service
-        .image_loader
-        .delete_images_from_project(project_name, image_names.clone())
-        .await?;
-    service
-        .image_evals
-        .remove_evaluations_for_images(project_name, &image_names)?;
-    Ok(())
+        .delete_images_from_project(
+            &ProjectName::parse(project_name)?,
+            ImageRelPath::parse_all(&image_names)?,
+        )
+        .await
+}
+
+/// Event carrying an `EvaluationProgress` when an image of an evaluation starts and when it's done
+pub const EVALUATION_PROGRESS_EVENT: &str = "evaluation-progress";
+
+fn emit_evaluation_progress(app: &AppHandle) -> impl Fn(EvaluationProgress) + Send + Sync + '_ {
+    move |progress| {
+        if let Err(e) = app.emit(EVALUATION_PROGRESS_EVENT, progress) {
+            log::warn!("Failed to send evaluation progress: {e}");
+        }
+    }
 }
 
 #[tauri::command]
 pub async fn evaluate_images(
+    app: AppHandle,
     service: State<'_, Arc<ProjectsService>>,
     project_name: &str,
     request: RequestImageEvaluation,
     custom_prompt: Option<String>,
     temperature: Option<f32>,
-) -> Result<Vec<ImageEvaluation>, String> {
+    evaluation_id: &str,
+) -> AppResult<Vec<ImageEvaluation>> {
     service
-        .image_evals
-        .evaluate_images(project_name, request, custom_prompt, temperature)
+        .evaluate_images(
+            &ProjectName::parse(project_name)?,
+            request,
+            custom_prompt,
+            temperature,
+            evaluation_id,
+            emit_evaluation_progress(&app),
+        )
         .await
 }
 
-/// Get the existing image evaluations for the project
+#[tauri::command]
+pub fn cancel_evaluation(service: State<'_, Arc<ProjectsService>>, evaluation_id: &str) -> bool {
+    service.cancel_evaluation(evaluation_id)
+}
+
+/// Get the existing image evaluations for the project, and whether they had to be restored from
+/// the backup
 #[tauri::command]
 pub async fn get_image_evaluations(
     service: State<'_, Arc<ProjectsService>>,
     project_name: &str,
-) -> Result<Vec<ImageEvaluation>, String> {
-    service.image_evals.read_images_eval_json(project_name)
+) -> AppResult<JsonRead<Vec<ImageEvaluation>>> {
+    service
+        .image_evals
+        .read_images_eval_json_with_recovery(&ProjectName::parse(project_name)?)
+}
+
+/// Get the provider the project's images are evaluated with by default
+#[tauri::command]
+pub async fn get_evaluation_provider(
+    service: State<'_, Arc<ProjectsService>>,
+    project_name: &str,
+) -> AppResult<EvaluationProviderConfig> {
+    service
+        .image_evals
+        .read_provider_config(&ProjectName::parse(project_name)?)
+}
+
+#[tauri::command]
+pub fn get_evaluation_retry_policy(service: State<'_, Arc<ProjectsService>>) -> RetryPolicy {
+    service.image_evals.retry_policy()
+}
+
+/// Set how evaluations are retried after rate limits and server errors, for every project
+#[tauri::command]
+pub fn set_evaluation_retry_policy(
+    service: State<'_, Arc<ProjectsService>>,
+    policy: RetryPolicy,
+) -> AppResult<()> {
+    service.image_evals.set_retry_policy(policy)
+}
+
+/// Get the directory offline OCR reads its models from
+#[tauri::command]
+pub fn get_ocr_models_dir(service: State<'_, Arc<ProjectsService>>) -> String {
+    service
+        .image_evals
+        .ocr_models_dir()
+        .to_string_lossy()
+        .to_string()
+}
+
+#[tauri::command]
+pub async fn set_evaluation_provider(
+    service: State<'_, Arc<ProjectsService>>,
+    project_name: &str,
+    config: EvaluationProviderConfig,
+) -> AppResult<()> {
+    service
+        .set_evaluation_provider(&ProjectName::parse(project_name)?, config)
+        .await
 }
 
 /// Export evaluated images to a directory
@@ -159,47 +431,63 @@ pub async fn export_evaluated_images(
     evaluations: Vec<ImageEvaluation>,
     output_dir_path: &str,
     mode: Option<String>,
-) -> Result<Vec<String>, String> {
+) -> AppResult<Vec<String>> {
     service
         .image_exporter
         .export_evaluated_images(evaluations, output_dir_path, mode.as_deref())
-        .map_err(|e| e.to_string())
+        .map_err(|e| AppError::io(output_dir_path, e))
 }
 
 #[tauri::command]
-pub fn create_folder_in_project(
+pub async fn create_folder_in_project(
     service: State<'_, Arc<ProjectsService>>,
     project_name: &str,
     folder_name: &str,
-) -> Result<(), String> {
-    service.create_folder_in_project(project_name, folder_name)
+) -> AppResult<()> {
+    service
+        .create_folder_in_project(
+            &ProjectName::parse(project_name)?,
+            &FolderName::parse(folder_name)?,
+        )
+        .await
 }
 
 #[tauri::command]
 pub fn get_folders_in_project(
     service: State<'_, Arc<ProjectsService>>,
     project_name: &str,
-) -> Result<Vec<String>, String> {
-    service.get_folders_in_project(project_name)
+) -> AppResult<Vec<FolderNode>> {
+    service.get_folders_in_project(&ProjectName::parse(project_name)?)
 }
 
 #[tauri::command]
-pub fn rename_folder_in_project(
+pub async fn rename_folder_in_project(
     service: State<'_, Arc<ProjectsService>>,
     project_name: &str,
     old_folder_name: &str,
     new_folder_name: &str,
-) -> Result<(), String> {
-    service.rename_folder_in_project(project_name, old_folder_name, new_folder_name)
+) -> AppResult<()> {
+    service
+        .rename_folder_in_project(
+            &ProjectName::parse(project_name)?,
+            &FolderName::parse(old_folder_name)?,
+            &FolderName::parse(new_folder_name)?,
+        )
+        .await
 }
 
 #[tauri::command]
-pub fn delete_folder_from_project(
+pub async fn delete_folder_from_project(
     service: State<'_, Arc<ProjectsService>>,
     project_name: &str,
     folder_name: &str,
-) -> Result<(), String> {
-    service.delete_folder_from_project(project_name, folder_name)
+) -> AppResult<()> {
+    service
+        .delete_folder_from_project(
+            &ProjectName::parse(project_name)?,
+            &FolderName::parse(folder_name)?,
+        )
+        .await
 }
 
 #[tauri::command]
@@ -208,25 +496,14 @@ pub async fn move_images_in_project(
     project_name: &str,
     image_names: Vec<String>,
     target_folder: Option<String>,
-) -> Result<Vec<String>, String> {
-    let new_names = service
-        .image_loader
-        .move_images_in_project(project_name, image_names.clone(), target_folder)
-        .await?;
-
-    // Update evaluation records to match the new image names
-    let renames: Vec<(String, String)> = image_names
-        .into_iter()
-        .zip(new_names.iter().cloned())
-        .filter(|(old, new)| old != new)
-        .collect();
-    if !renames.is_empty() {
-        service
-            .image_evals
-            .rename_evaluations(project_name, &renames)?;
-    }
-
-    Ok(new_names)
+) -> AppResult<Vec<String>> {
+    service
+        .move_images_in_project(
+            &ProjectName::parse(project_name)?,
+            ImageRelPath::parse_all(&image_names)?,
+            FolderName::parse_optional(target_folder.as_deref())?,
+        )
+        .await
 }
 
 /// Open a project image in the OS default application
@@ -235,12 +512,15 @@ pub fn open_image_in_default_app(
     service: State<'_, Arc<ProjectsService>>,
     project_name: &str,
     image_name: &str,
-) -> Result<(), String> {
-    let full_path = service.get_image_full_path(project_name, image_name);
+) -> AppResult<()> {
+    let full_path = service.get_image_full_path(
+        &ProjectName::parse(project_name)?,
+        &ImageRelPath::parse(image_name)?,
+    );
     open_path_in_default_app(full_path.to_string_lossy().as_ref())
 }
 
-fn open_path_in_default_app(path: &str) -> Result<(), String> {
+fn open_path_in_default_app(path: &str) -> AppResult<()> {
     #[cfg(target_os = "windows")]
     {
         const CREATE_NO_WINDOW: u32 = 0x0800_0000;
@@ -248,48 +528,48 @@ fn open_path_in_default_app(path: &str) -> Result<(), String> {
             .args(["/C", "start", "", path])
             .creation_flags(CREATE_NO_WINDOW)
             .spawn()
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| AppError::io(path, e))?;
     }
     #[cfg(target_os = "macos")]
     {
         Command::new("open")
             .arg(path)
             .spawn()
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| AppError::io(path, e))?;
     }
     #[cfg(not(any(target_os = "windows", target_os = "macos")))]
     {
         Command::new("xdg-open")
             .arg(path)
             .spawn()
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| AppError::io(path, e))?;
     }
     Ok(())
 }
 
 /// Open a path (file or directory) in the system's default file manager
 #[tauri::command]
-pub fn open_path_in_file_manager(path: &str) -> Result<(), String> {
+pub fn open_path_in_file_manager(path: &str) -> AppResult<()> {
     #[cfg(target_os = "windows")]
     {
         Command::new("explorer")
             .arg(path)
             .spawn()
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| AppError::io(path, e))?;
     }
     #[cfg(target_os = "macos")]
     {
         Command::new("open")
             .arg(path)
             .spawn()
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| AppError::io(path, e))?;
     }
     #[cfg(not(any(target_os = "windows", target_os = "macos")))]
     {
         Command::new("xdg-open")
             .arg(path)
             .spawn()
-            .map_err(|e| e.to_string())?;
+            .map_err(|e| AppError::io(path, e))?;
     }
     Ok(())
 }